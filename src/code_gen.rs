@@ -1,8 +1,9 @@
 //! Functionality for generating the code for the generated tests.
 
+use crate::emitter::{Emitter, JsEmitter};
 use crate::functions::*;
-use crate::module_reps::{AccessPathModuleCentred, NpmModule};
-use crate::tests::{FunctionCall, Test};
+use crate::module_reps::{AccessPathModuleCentred, ModuleLoadMode, NpmModule};
+use crate::tests::{ExtensionPoint, ExtensionType, FunctionCall, Test};
 
 /// Code generation for `Callback` objects.
 impl Callback {
@@ -26,13 +27,19 @@ impl Callback {
     /// Get the string representation of the code of this callback.
     /// Optional parameters for adding `extra_body_code` instrumentation code in the body of the
     /// callback, and `context_uniq_id` to be added as part of the ID of the callback arguments.
+    /// `depth` is the nesting depth of the call this callback is passed to (see
+    /// `get_function_call_code`), logged alongside this callback's own `in_`/`callback_exec_`
+    /// events so the trace records how deep in the call tree the callback actually fired --
+    /// `None` when this callback isn't being instrumented.
     pub fn get_string_rep(
         &self,
         extra_body_code: Option<String>,
         context_uniq_id: Option<String>,
         print_instrumented: bool,
+        depth: Option<usize>,
     ) -> String {
         let cb_arg_name_base = self.get_cb_arg_name_base(&context_uniq_id);
+        let depth_str = depth.map(|d| d.to_string()).unwrap_or_default();
         // code to print the values of all the callback arguments;
         // included if we're instrumenting
         let print_args = self
@@ -48,10 +55,12 @@ impl Callback {
                         &cb_arg_name_base,
                         "_",
                         &i.to_string(),
-                        "\": ",
+                        "\": nessieSerialize(",
                         &cb_arg_name_base,
                         &i.to_string(),
-                        "});",
+                        ")}, ",
+                        &depth_str,
+                        ");",
                     ]
                     .join("")
                 } else {
@@ -84,7 +93,9 @@ impl Callback {
                         Some(pos_id) => pos_id.to_string(),
                         None => String::new(),
                     },
-                    "});",
+                    "}, ",
+                    &depth_str,
+                    ");",
                 ]
                 .join("")
             } else {
@@ -112,21 +123,47 @@ impl Test {
     /// Get the code representation for the test;
     /// Options to instrument the test, and to generate the test as a function
     /// that can then be called as part of a `mocha` test suite.
-    pub(crate) fn get_code(&self, print_instrumented: bool, print_as_test_fct: bool) -> String {
-        let setup_code = self.js_for_basic_cjs_import.clone();
-        let (test_header, test_footer) = if print_instrumented {
-            (get_instrumented_header(), get_instrumented_footer())
+    /// `async_driver`, if set, wraps the test body in an `async` IIFE (or the `mocha`
+    /// function, if `print_as_test_fct`), awaits each top-level call's returned promise at
+    /// its call site, and awaits `nessieDrain()` -- which pumps the event loop until every
+    /// in-flight call anywhere in the tree (including ones made from inside non-`async`
+    /// nested callback bodies, where an inline `await` isn't possible) has settled -- before
+    /// the test is considered done. This makes the ordering of logged `callback_exec_`/
+    /// `done_`/`error_` events deterministic for tests that schedule timers/I/O/pending
+    /// promises, instead of racing them against the driver's own completion.
+    pub(crate) fn get_code(
+        &self,
+        print_instrumented: bool,
+        print_as_test_fct: bool,
+        async_driver: bool,
+    ) -> String {
+        // an ESM `import(...)` binding can only be awaited inside an async context, so
+        // loading such a module always pulls in the async driver regardless of what was
+        // requested (see `ModuleLoadMode::Esm`)
+        let async_driver = async_driver || self.module_load_mode == ModuleLoadMode::Esm;
+        let setup_code = self.js_for_module_import.clone();
+        let test_header = if print_instrumented {
+            get_instrumented_header()
         } else {
-            ("", "")
+            ""
         };
 
-        let (test_fct_header, test_fct_footer) = if print_as_test_fct {
-            *self.root_level_tabs.borrow_mut() = 1;
-            ("module.exports = function() {", "}")
-        } else {
-            *self.root_level_tabs.borrow_mut() = 0;
-            ("", "")
+        // `EsmStatic`/`TypeScript` tests are real ES modules (`.mjs`/`.ts`), which have no
+        // `module.exports` -- the mocha driver instead dynamic-`import()`s the file and
+        // calls its default export (see `get_meta_test_code`).
+        let is_static_esm_like = matches!(
+            self.module_load_mode,
+            ModuleLoadMode::EsmStatic | ModuleLoadMode::TypeScript
+        );
+        let (test_fct_header, test_fct_footer) = match (print_as_test_fct, async_driver, is_static_esm_like) {
+            (true, true, true) => ("export default async function() {", "\n\tawait nessieDrain();\n}"),
+            (true, false, true) => ("export default function() {", "}"),
+            (true, true, false) => ("module.exports = async function() {", "\n\tawait nessieDrain();\n}"),
+            (true, false, false) => ("module.exports = function() {", "}"),
+            (false, true, _) => ("(async () => {", "\n\tawait nessieDrain();\n})();"),
+            (false, false, _) => ("", ""),
         };
+        *self.root_level_tabs.borrow_mut() = if print_as_test_fct || async_driver { 1 } else { 0 };
 
         let base_var_name = self.mod_js_var_name.clone();
         // traverse the tree of function calls and create the test code
@@ -134,17 +171,69 @@ impl Test {
             base_var_name,
             self.include_basic_callback,
             print_instrumented,
+            async_driver,
         );
 
-        [
-            test_header,
-            &setup_code,
-            test_fct_header,
-            &test_body,
-            test_fct_footer,
-            test_footer,
-        ]
-        .join("\n")
+        // a CJS `require(...)` is synchronous, so it's fine outside the async wrapper; an
+        // ESM `import(...)` needs to run (and be awaited) inside it
+        if self.module_load_mode == ModuleLoadMode::Esm {
+            [
+                test_header,
+                test_fct_header,
+                &setup_code,
+                &test_body,
+                test_fct_footer,
+            ]
+            .join("\n")
+        } else {
+            [
+                test_header,
+                &setup_code,
+                test_fct_header,
+                &test_body,
+                test_fct_footer,
+            ]
+            .join("\n")
+        }
+    }
+
+    /// Get the code representation for the test, for the persistent-runner execution
+    /// backend (see `crate::runner`). Identical to `get_code(true, false, true)` (always
+    /// instrumented, never wrapped as a mocha test function, always the async driver) except
+    /// for the footer: rather than relying on `beforeExit` to guess when the test is done, it
+    /// explicitly awaits `nessieDrain()` and only then prints `sentinel`, so the resident
+    /// harness script (`js_tools/persistent_runner.js`) can't observe it before the test's
+    /// in-flight work has actually settled.
+    pub(crate) fn get_code_for_persistent_runner(&self, sentinel: &str) -> String {
+        let setup_code = self.js_for_module_import.clone();
+        *self.root_level_tabs.borrow_mut() = 1;
+        let base_var_name = self.mod_js_var_name.clone();
+        let test_body = self.fct_tree_code(
+            base_var_name,
+            self.include_basic_callback,
+            true,
+            true, /* async_driver */
+        );
+
+        if self.module_load_mode == ModuleLoadMode::Esm {
+            [
+                get_instrumented_header(),
+                "(async () => {",
+                &setup_code,
+                &test_body,
+                &get_instrumented_footer_for_persistent_runner(sentinel),
+            ]
+            .join("\n")
+        } else {
+            [
+                get_instrumented_header(),
+                &setup_code,
+                "(async () => {",
+                &test_body,
+                &get_instrumented_footer_for_persistent_runner(sentinel),
+            ]
+            .join("\n")
+        }
     }
 
     /// Get the code for the tree of function calls in the test.
@@ -153,38 +242,31 @@ impl Test {
         base_var_name: String,
         include_basic_callback: bool,
         print_instrumented: bool,
+        async_driver: bool,
     ) -> String {
         // no function calls, return the empty string
         if self.is_empty() {
             return String::new();
         }
-        // get root
-        let mut iter = self.fct_tree.iter();
-        let mut root_node = iter.next().unwrap();
-        let mut test_body = self.dfs_print(
-            &base_var_name,
-            root_node,
-            *self.root_level_tabs.borrow(),
-            include_basic_callback,
-            print_instrumented,
-        );
-
-        // then get root siblings
-        let mut next_node = iter.next();
-        while next_node.is_some() {
-            root_node = next_node.unwrap();
-            // if it's a root node sibling
-            if root_node.parent().is_none() {
-                test_body = test_body
-                    + &self.dfs_print(
-                        &base_var_name,
-                        root_node,
-                        *self.root_level_tabs.borrow(),
-                        include_basic_callback,
-                        print_instrumented,
-                    );
-            }
-            next_node = iter.next();
+        let emitter = JsEmitter;
+        let mut test_body = String::new();
+        // every root-level call (the tree's actual root, plus any root siblings appended
+        // by `Test::extend(.., ExtensionType::Sequential, ..)`) is a sequential extension
+        // of the test as a whole
+        for root_node in self.fct_tree.iter().filter(|node| node.parent().is_none()) {
+            let call_code = self.dfs_print(
+                &base_var_name,
+                root_node,
+                *self.root_level_tabs.borrow(),
+                include_basic_callback,
+                print_instrumented,
+                async_driver,
+            );
+            let point = ExtensionPoint::new(
+                self.fct_tree.get_node_id(root_node).unwrap(),
+                ExtensionType::Sequential,
+            );
+            emitter.emit_extension(&point, &call_code, &mut test_body);
         }
         test_body
     }
@@ -197,6 +279,7 @@ impl Test {
         num_tabs: usize,
         include_basic_callback: bool,
         print_instrumented: bool,
+        async_driver: bool,
     ) -> String {
         let cur_call_uniq_id = self.get_uniq_id_for_call(cur_root);
         let cur_call_node_id = self.fct_tree.get_node_id(cur_root).unwrap();
@@ -232,19 +315,21 @@ impl Test {
                             let ret_val = if cur_child_node.get().get_parent_call_id()
                                 == Some(cur_call_node_id.to_string())
                             {
-                                Some(
-                                    [
-                                        self.dfs_print(
-                                            base_var_name,
-                                            cur_child_node,
-                                            num_tabs + 1,
-                                            include_basic_callback,
-                                            print_instrumented,
-                                        ),
-                                        "\n".to_string(),
-                                    ]
-                                    .join(""),
-                                )
+                                let call_code = self.dfs_print(
+                                    base_var_name,
+                                    cur_child_node,
+                                    num_tabs + 1,
+                                    include_basic_callback,
+                                    print_instrumented,
+                                    async_driver,
+                                );
+                                let point = ExtensionPoint::new(
+                                    self.fct_tree.get_node_id(cur_child_node).unwrap(),
+                                    ExtensionType::Nested,
+                                );
+                                let mut nested_code = String::new();
+                                JsEmitter.emit_extension(&point, &call_code, &mut nested_code);
+                                Some(nested_code)
                             } else {
                                 None
                             };
@@ -257,6 +342,7 @@ impl Test {
                         extra_body_code,
                         Some(cur_call_uniq_id.clone()),
                         print_instrumented,
+                        Some(num_tabs),
                     )
                     .as_ref()
                     .unwrap()
@@ -270,9 +356,14 @@ impl Test {
             None | Some(ArgVal::Variable(_))
         )); // receiver needs to be a variable
         let fct_call_base_var = match &cur_node_call.receiver {
-            Some(rec) => rec.get_string_rep(None, None, print_instrumented),
+            Some(rec) => rec.get_string_rep(None, None, print_instrumented, None),
             None => base_var_name.to_string(),
         };
+        // root-level calls run directly inside the test's top-level `async` IIFE, so they
+        // can literally use `await`; nested calls are embedded inside a callback's plain,
+        // non-`async` function body (see `Callback::get_string_rep`), where `await` is
+        // syntactically illegal -- those rely solely on `nessieTrack`/`nessieDrain` instead
+        let in_async_context = async_driver && cur_root.parent().is_none();
         get_function_call_code(
             &cur_node_call.sig,
             cur_node_call.get_name(),
@@ -283,33 +374,72 @@ impl Test {
             cur_call_uniq_id,
             indents,
             print_instrumented,
+            async_driver,
+            in_async_context,
         )
     }
 }
 
 /// Code generation for modules.
 impl NpmModule {
-    /// Return JS code to import this module.
-    pub fn get_js_for_basic_cjs_import(&self, api_src_dir: Option<String>) -> String {
-        [
-            "let ",
-            &self.get_mod_js_var_name(),
-            " = ",
-            &match &self.import_code {
-                Some(code) => code.clone(),
-                None => [
-                    "require(\"",
-                    &match api_src_dir {
-                        Some(dir) => dir,
-                        None => self.lib.clone(),
-                    },
-                    "\")",
-                ]
-                .join(""),
+    /// Return JS code to import this module, per `self.module_load_mode`: a plain
+    /// `require(...)` for `Cjs`, an awaited dynamic `import(...)` for `Esm`, or a static
+    /// `import * as ...` declaration for `EsmStatic`/`TypeScript` (the latter relies on the
+    /// mocha driver registering a `ts-node`-compatible loader -- see `get_meta_test_code` --
+    /// rather than on anything emitted here). Named exports are accessed the same way any
+    /// of these modes binds them: as properties on the single bound variable
+    /// (`m.fct_name(...)`, same as `Callback::get_string_rep`'s existing dispatch against a
+    /// CJS `module.exports` object) -- there's no separate per-export binding to plumb
+    /// through `get_function_call_code`.
+    pub fn get_js_for_module_import(&self, api_src_dir: Option<String>) -> String {
+        self.get_js_for_module_import_as(api_src_dir, &self.get_mod_js_var_name())
+    }
+
+    /// Same as `get_js_for_module_import`, but binds the import to `var_name` instead of
+    /// `get_mod_js_var_name()` -- used by `ModuleRegistry` when two registered modules' own
+    /// var names would otherwise collide in the same generated test file.
+    pub fn get_js_for_module_import_as(&self, api_src_dir: Option<String>, var_name: &str) -> String {
+        let module_specifier = match &self.import_code {
+            Some(code) => {
+                return match self.module_load_mode {
+                    ModuleLoadMode::EsmStatic | ModuleLoadMode::TypeScript => code.clone(),
+                    ModuleLoadMode::Cjs | ModuleLoadMode::Esm => {
+                        ["let ", var_name, " = ", code, ";"].join("")
+                    }
+                }
+            }
+            None => match api_src_dir {
+                Some(dir) => dir,
+                None => self.lib.clone(),
             },
-            ";",
-        ]
-        .join("")
+        };
+        match self.module_load_mode {
+            ModuleLoadMode::Cjs => [
+                "let ",
+                var_name,
+                " = require(\"",
+                &module_specifier,
+                "\");",
+            ]
+            .join(""),
+            ModuleLoadMode::Esm => [
+                "let ",
+                var_name,
+                " = await import(\"",
+                &module_specifier,
+                "\");",
+            ]
+            .join(""),
+            // a static import declaration binds its own name; it can't be assigned via `let`
+            ModuleLoadMode::EsmStatic | ModuleLoadMode::TypeScript => [
+                "import * as ",
+                var_name,
+                " from \"",
+                &module_specifier,
+                "\";",
+            ]
+            .join(""),
+        }
     }
 }
 
@@ -326,17 +456,71 @@ pub fn basic_callback_with_id(cur_call_uniq_id: String) -> String {
         + "\": true}); }"
 }
 
-/// Returns a string of JS code that redefines the `console.log`
-/// printing function so that it pushes the argument to `console.log`
-/// onto an array.
-/// This instrumentation allows us to track what's being printed and
-/// in what order.
+/// Env var the generated test's instrumented `console.log` override reads to find its
+/// dedicated events file (see `get_instrumented_header`): `Test::execute` sets it to a
+/// path derived from the test's own file (mirroring `NODE_V8_COVERAGE`'s per-test
+/// directory convention) before spawning `node`; `crate::runner::PersistentRunner`'s
+/// harness sets it in its own `process.env` right before each `require`/`import`, since
+/// its test file (and thus events file) changes on every call. Routing events through a
+/// file instead of stdout means a chatty library's own prints can never corrupt, or get
+/// misread as, our instrumentation.
+pub(crate) const NESSIE_EVENTS_FILE_ENV: &str = "NESSIE_EVENTS_FILE";
+
+/// Sentinel every instrumentation event is printed on its own stdout line with (see
+/// `get_instrumented_header`), so `diagnose_test_correctness` can pick our events out of
+/// stdout even when the library under test prints other things via its own `console.log`
+/// calls. Kept in sync by hand with the literal baked into the generated JS below. Only
+/// still used as a fallback when `NESSIE_EVENTS_FILE_ENV` isn't set or `fs` isn't
+/// available (real ESM `.mjs`/TypeScript test files -- see `get_instrumented_header`).
+pub(crate) const NESSIE_EVENT_PREFIX: &str = "__NESSIE__";
+
+/// Returns a string of JS code that redefines the `console.log` printing function so
+/// that each call is appended, immediately and as its own line, to the dedicated events
+/// file named by `NESSIE_EVENTS_FILE_ENV` -- instead of being pushed onto an array and
+/// dumped all at once at process exit (the original approach), or printed inline on
+/// stdout (the approach before this one, which a chatty library's own stdout writes
+/// could corrupt). Printing immediately means a test that crashes partway through still
+/// leaves behind whatever `done_`/`error_`/`callback_exec_` events it managed to emit
+/// before the crash. `require` isn't defined in real ESM (`.mjs`/TypeScript) test files,
+/// so those fall back to the old stdout-plus-prefix scheme rather than the events file;
+/// the prefix lets `diagnose_test_correctness` scan past any unrelated output the library under test
+/// prints via its own `console.log` calls, rather than that output corrupting a single
+/// whole-stdout parse the way it used to. Also defines `nessieSerialize`, used everywhere
+/// a captured argument or return value is logged so it round-trips back into a strongly
+/// typed value on the Rust side (see `crate::serialization::decode_captured_value`) instead
+/// of collapsing through `.toString()`/`typeof`.
 pub fn get_instrumented_header() -> &'static str {
     r#"
+// Every event is wrapped in a `{seq, t, depth, payload}` envelope before being printed:
+// `seq` is a monotonic counter (events can arrive interleaved with a library's own async
+// callbacks, so printed order alone doesn't always match causal order), `t` is a
+// high-resolution timestamp (`process.hrtime.bigint()`, as a string since `BigInt` isn't
+// valid JSON), and `depth` is the call's nesting depth in the test's call tree (`null` for
+// events not tied to a particular call depth). `payload` is the original flattened-key
+// event object (`done_<id>`, `error_<id>`, etc.) that `parse_nessie_events` already knows
+// how to read -- this wrapping is additive, not a replacement for that schema.
+let __nessie_seq = 0;
 let orig_log = console.log;
-let output_log = [];
-console.log = function(e) {
-	output_log.push(e);
+// `require` isn't defined in real ESM (`.mjs`/TypeScript) test files -- `typeof` is safe
+// to use on it regardless, since (unlike a bare reference) it never throws on a missing
+// global -- so those fall back to the stdout-plus-prefix scheme below instead of a
+// dedicated events file.
+const __nessie_fs = (typeof require !== "undefined") ? require("fs") : null;
+console.log = function(e, depth) {
+	const __nessie_line = JSON.stringify({
+		seq: __nessie_seq++,
+		t: process.hrtime.bigint().toString(),
+		depth: (depth === undefined ? null : depth),
+		payload: e,
+	});
+	// kept in sync by hand with `code_gen::NESSIE_EVENTS_FILE_ENV`
+	const __nessie_events_file = process.env.NESSIE_EVENTS_FILE;
+	if (__nessie_fs && __nessie_events_file) {
+		__nessie_fs.appendFileSync(__nessie_events_file, __nessie_line + "\n");
+	} else {
+		// kept in sync by hand with `code_gen::NESSIE_EVENT_PREFIX`
+		orig_log("__NESSIE__" + __nessie_line);
+	}
 }
 function getTypeDiffObjFromPromise(val) {
     if (val.toString() === "[object Promise]") {
@@ -344,18 +528,117 @@ function getTypeDiffObjFromPromise(val) {
     }
     return typeof val;
 }
+// Serialize a captured value (argument or return value) into a tagged envelope
+// `{v: <repr>, t: <conversion tag>, fmt: <optional>}` that the Rust side can decode back into
+// a strongly typed value (see `serialization::decode_captured_value`), instead of collapsing
+// everything through `.toString()`/`typeof` the way plain `console.log` does.
+function nessieSerialize(val) {
+    if (typeof val === "bigint") {
+        return {v: val.toString(), t: "int"};
+    }
+    if (typeof val === "number") {
+        if (Number.isNaN(val)) { return {v: "NaN", t: "float"}; }
+        if (val === Infinity) { return {v: "Infinity", t: "float"}; }
+        if (val === -Infinity) { return {v: "-Infinity", t: "float"}; }
+        return {v: val.toString(), t: Number.isInteger(val) ? "int" : "float"};
+    }
+    if (typeof val === "boolean") {
+        return {v: val.toString(), t: "bool"};
+    }
+    if (typeof val === "function") {
+        return {v: "[function]", t: "function"};
+    }
+    if (val instanceof Date) {
+        return {v: val.toISOString(), t: "timestamp", fmt: "iso"};
+    }
+    if (typeof Buffer !== "undefined" && Buffer.isBuffer(val)) {
+        return {v: val.toString("base64"), t: "bytes"};
+    }
+    if (val === null || typeof val === "undefined") {
+        return {v: String(val), t: "string"};
+    }
+    if (getTypeDiffObjFromPromise(val) === "DIFFTYPE_Promise") {
+        return {v: "[promise]", t: "string"};
+    }
+    if (typeof val === "object") {
+        return {v: val.toString(), t: "string"};
+    }
+    return {v: val.toString(), t: "string"};
+}
+// Best-effort guess at which argument position an error message is complaining about --
+// e.g. Node's own `The "data" argument must be...` / `The first argument must be...`, or a
+// library's own `argument 2 is invalid` -- so the generator can bias away from that
+// position's current argument type on retry (see `errors::ErrorInfo::arg_hint`). Returns
+// null when no such pattern is found, which is most errors.
+const NESSIE_ORDINALS = ["first", "second", "third", "fourth", "fifth", "sixth", "seventh", "eighth", "ninth", "tenth"];
+function nessieGuessArgHint(message) {
+    if (!message) { return null; }
+    let m = message.match(/\bargument(?:s)?\s*#?(\d+)\b/i);
+    if (m) { return parseInt(m[1], 10); }
+    m = message.match(/\b(\w+)\s+argument\b/i);
+    if (m) {
+        const ord = NESSIE_ORDINALS.indexOf(m[1].toLowerCase());
+        if (ord !== -1) { return ord; }
+    }
+    return null;
+}
+// Classify a thrown/rejected value for an `error_<id>` event (see `errors::ErrorInfo`),
+// rather than just logging `true` and losing everything about *why* the call failed.
+// `isRejection` distinguishes an unhandled promise rejection (the call itself returned
+// fine) from a synchronous throw caught at the call site.
+function nessieSerializeError(e, isRejection) {
+    const message = (e && e.message !== undefined) ? String(e.message) : null;
+    return {
+        class_name: (e && e.constructor && e.constructor.name) || null,
+        message: message,
+        stack: (e && e.stack !== undefined) ? String(e.stack) : null,
+        arg_hint: nessieGuessArgHint(message),
+        is_rejection: isRejection,
+        timed_out: false,
+    };
+}
+// Event-loop-aware completion tracking: rather than firing off each call's returned
+// promise and forgetting about it (which races the test's own completion against
+// whatever I/O/timers/pending promises that call scheduled), every call result -- at
+// any nesting depth, sync or async context -- is handed to `nessieTrack`, which bumps
+// `__nessie_in_flight` until the value (or the promise it resolves to) has settled.
+// `nessieDrain` is then awaited once, at the very end of the test, to pump the event
+// loop (via a zero-delay `setTimeout`) until nothing is left in flight -- this is what
+// actually catches work scheduled from inside a nested callback's plain (non-`async`)
+// function body, where an inline `await` at the call site isn't possible.
+let __nessie_in_flight = 0;
+function nessieTrack(val) {
+    if (getTypeDiffObjFromPromise(val) === "DIFFTYPE_Promise") {
+        __nessie_in_flight++;
+        val.then(() => { __nessie_in_flight--; }, () => { __nessie_in_flight--; });
+    }
+    return val;
+}
+async function nessieDrain() {
+    while (__nessie_in_flight > 0) {
+        await new Promise((resolve) => setTimeout(resolve, 0));
+    }
+}
 "#
 }
 
-/// Returns a string of JS code that prints the global array that
-/// `console.log` is redefined to add to, to the console on process exit
-/// (if there are async functions, this will be after all the async functions
-/// have finished executing).
-pub fn get_instrumented_footer() -> &'static str {
-    r#"
-process.on("exit", function f() {
-	orig_log(JSON.stringify(output_log));
-})"#
+/// Completion signal for the persistent-runner backend (`crate::runner`): the host
+/// `node` process never exits between tests, so `exit` (which only ever fires once, at
+/// real process shutdown) can't be used to know when a given test is done. Rather than
+/// relying on `beforeExit`'s event-loop-idle heuristic (which only coincidentally lines
+/// up with "this test's calls have all settled"), the test body is always run with the
+/// async driver (see `Test::get_code_for_persistent_runner`), so this footer just closes
+/// that async IIFE with an explicit `await nessieDrain()` and prints `sentinel` right
+/// after -- deliberately left unprefixed with `NESSIE_EVENT_PREFIX` so it reads as a
+/// distinct marker, not another event -- to tell the harness this test's output is
+/// complete.
+pub fn get_instrumented_footer_for_persistent_runner(sentinel: &str) -> String {
+    [
+        "\tawait nessieDrain();".to_string(),
+        format!("\torig_log(\"{}\");", sentinel),
+        "})();".to_string(),
+    ]
+    .join("\n")
 }
 
 /// Generate the code for a given function call:
@@ -374,7 +657,14 @@ pub fn get_function_call_code(
     cur_call_uniq_id: String,
     indents: String,
     print_instrumented: bool,
+    async_driver: bool,
+    in_async_context: bool,
 ) -> String {
+    // nesting depth of this call -- `indents` is one tab per level (see `dfs_print`) --
+    // threaded through as a second argument to every `console.log` below, so the
+    // `{seq, t, depth, payload}` envelope `get_instrumented_header` wraps each event in
+    // (see there) records how deep in the call tree it was emitted from
+    let depth = indents.len().to_string();
     // print the arguments to the specified signature
     let print_args = |title: String| {
         if print_instrumented {
@@ -386,7 +676,9 @@ pub fn get_function_call_code(
                     &cur_call_uniq_id,
                     "_",
                     &ret_val_basename,
-                    "_args\": args});",
+                    "_args\": args.map(nessieSerialize)}, ",
+                    &depth,
+                    ");",
                 ]
                 .join("")
             } else {
@@ -403,13 +695,15 @@ pub fn get_function_call_code(
                             &ret_val_basename,
                             "_arg",
                             &i.to_string(),
-                            "\": ",
+                            "\": nessieSerialize(",
                             &fct_arg
                                 .get_string_rep_arg_val_short()
                                 .as_ref()
                                 .unwrap()
                                 .clone(),
-                            "});",
+                            ")}, ",
+                            &depth,
+                            ");",
                         ]
                         .join("")
                     })
@@ -425,71 +719,108 @@ pub fn get_function_call_code(
         "try { ",
         &extra_cb_code,
         &print_args("before_cb".to_string()),
-        &("\t".to_owned()
-            + &ret_val_basename
-            + " = "
-            + base_var_name
-            + "."
-            + &fct_name
-            + "("
-            + &args_rep
-            + ");"),
+        &{
+            let raw_call = base_var_name.to_string() + "." + &fct_name + "(" + &args_rep + ")";
+            let tracked_call = if async_driver {
+                "nessieTrack(".to_owned() + &raw_call + ")"
+            } else {
+                raw_call
+            };
+            "\t".to_owned()
+                + &ret_val_basename
+                + " = "
+                + &(if in_async_context {
+                    "await ".to_owned() + &tracked_call
+                } else {
+                    tracked_call
+                })
+                + ";"
+        },
         &print_args("after_cb".to_string()),
-        // print the list of function properties on the acc path if it's an Object type
-        // note: we're deliberately ignoring primitives, can explicitly code those cases
-        // in if we want (eg for promise chains), but if you want to test all function props
-        // on an acc path regardless of type just remove the if statement
+        // print the list of function properties discoverable on the acc path, so
+        // `AccessPathModuleCentred::ReturnPath` extension can chain through them. We cover:
+        // -- objects (including arrays): own property names *and* prototype-chain property
+        //    names (deduped), so array/builder-style chainable methods like `push`/`then`
+        //    that live on the prototype rather than the instance are no longer missed;
+        // -- promises: special-cased to just `then`/`catch`, since walking the prototype
+        //    chain would surface every other `Promise.prototype` internal;
+        // -- functions and strings: prototype-chain property names only, since these never
+        //    carry useful own function properties the way objects do.
+        // `null`/`undefined` are skipped outright, since there's nothing to discover and
+        // `getTypeDiffObjFromPromise` itself would throw on them.
         &(if print_instrumented && ret_val_acc_path.is_some() {
-            "\tif (getTypeDiffObjFromPromise(".to_owned()
+            let acc_path_key = ret_val_acc_path
+                .as_ref()
+                .unwrap()
+                .to_string()
+                .replace("\"", "\\\"");
+            "\tif (".to_owned()
+                + &ret_val_basename
+                + " === null || "
+                + &ret_val_basename
+                + " === undefined){"
+                + "\n\t\t// nothing to discover"
+                + "\n\t} else if (getTypeDiffObjFromPromise("
                 + &ret_val_basename
                 + ") == \"object\"){"
                 + "\n\t\tconsole.log({\""
-                + &ret_val_acc_path
-                    .as_ref()
-                    .unwrap()
-                    .to_string()
-                    .replace("\"", "\\\"")
+                + &acc_path_key
                 + "\": Object.getOwnPropertyNames("
                 + &ret_val_basename
-                + ").filter((p) => typeof ret_val_jsonfile_1[p] === \"function\")"
-                // NOTE: the next lines get more properties; including `toString` etc. 
-                // uncomment if you want the prototype properties too
-                // + ".concat(Object.getOwnPropertyNames(Object.getPrototypeOf("
-                // + &ret_val_basename
-                // + ")))"
-                + "});"
+                + ").concat(Object.getOwnPropertyNames(Object.getPrototypeOf("
+                + &ret_val_basename
+                + ") || {}))"
+                + ".filter((p, i, arr) => arr.indexOf(p) === i)"
+                + ".filter((p) => typeof "
+                + &ret_val_basename
+                + "[p] === \"function\")"
+                + "}, "
+                + &depth
+                + ");"
                 // special case for promises: we only want `then` and `catch`
                 + "\n\t} else if (getTypeDiffObjFromPromise("
                 + &ret_val_basename
                 + ") == \"DIFFTYPE_Promise\"){"
                 + "\n\t\tconsole.log({\""
-                + &ret_val_acc_path
-                    .as_ref()
-                    .unwrap()
-                    .to_string()
-                    .replace("\"", "\\\"")
-                + "\": [\"then\", \"catch\"]});"
-                + "\n\t}"
-        } else {
-            String::new()
-        }),
-        &(if print_instrumented {
-            "\tconsole.log({\"".to_owned()
+                + &acc_path_key
+                + "\": [\"then\", \"catch\"]}, "
+                + &depth
+                + ");"
+                // functions and strings don't carry useful own function properties, but their
+                // prototypes (Function.prototype / String.prototype, or a custom callable's
+                // own prototype chain) might
+                + "\n\t} else if (getTypeDiffObjFromPromise("
+                + &ret_val_basename
+                + ") == \"function\" || typeof "
                 + &ret_val_basename
-                + "\": getTypeDiffObjFromPromise("
+                + " === \"string\"){"
+                + "\n\t\tconsole.log({\""
+                + &acc_path_key
+                + "\": Object.getOwnPropertyNames(Object.getPrototypeOf("
                 + &ret_val_basename
-                + ") == \"function\"? \"[function]\" : "
+                + "))"
+                + ".filter((p, i, arr) => arr.indexOf(p) === i)"
+                + ".filter((p) => typeof "
                 + &ret_val_basename
-                + ".toString()});"
+                + "[p] === \"function\")"
+                + "}, "
+                + &depth
+                + ");"
+                + "\n\t}"
         } else {
             String::new()
         }),
+        // single tagged envelope replaces the old separate `.toString()`/`_type` logging, so
+        // feedback analysis can re-type the captured return value exactly instead of
+        // string-matching it (see `serialization::decode_captured_value`)
         &(if print_instrumented {
             "\tconsole.log({\"".to_owned()
                 + &ret_val_basename
-                + "_type\": getTypeDiffObjFromPromise("
+                + "\": nessieSerialize("
                 + &ret_val_basename
-                + ")});"
+                + ")}, "
+                + &depth
+                + ");"
         } else {
             String::new()
         }),
@@ -502,21 +833,31 @@ pub fn get_function_call_code(
                     .unwrap()
                     .to_string()
                     .replace("\"", "\\\"")
-                + "\"});"
+                + "\"}, "
+                + &depth
+                + ");"
         } else {
             String::new()
         }),
-        // rejected promise
+        // rejected promise -- `is_rejection: true` since the call itself already returned
+        // fine; only the promise it returned rejected afterwards
         &("\tPromise.resolve(".to_owned()
             + &ret_val_basename
             + ").catch(e => { console.log({\"error_"
             + &cur_call_uniq_id
-            + "\": true}); });"),
+            + "\": nessieSerializeError(e, true)}, "
+            + &depth
+            + "); });"),
         "} catch(e) {",
-        &("\tconsole.log({\"error_".to_owned() + &cur_call_uniq_id + "\": true});"),
+        // synchronous throw -- `is_rejection: false`
+        &("\tconsole.log({\"error_".to_owned()
+            + &cur_call_uniq_id
+            + "\": nessieSerializeError(e, false)}, "
+            + &depth
+            + ");"),
         "}",
         &(if print_instrumented {
-            "console.log({\"done_".to_owned() + &cur_call_uniq_id + "\": true});"
+            "console.log({\"done_".to_owned() + &cur_call_uniq_id + "\": true}, " + &depth + ");"
         } else {
             String::new()
         }),
@@ -534,20 +875,51 @@ pub fn get_function_call_code(
 
 /// Generate the code for the `mocha` test suite driver
 /// for `num_tests` number of generated tests.
-pub fn get_meta_test_code(num_tests: i32) -> String {
+/// `seed` is the RNG seed the campaign was generated with, recorded as a comment so the
+/// suite can be regenerated bit-for-bit later (e.g. via `nessie --seed <seed>`).
+/// `module_load_mode` is the campaign's `ModuleLoadMode`, which determines both the test
+/// files' extension (see `ModuleLoadMode::file_extension`) and how each one is loaded and
+/// invoked: `require(...)()` for `Cjs`/`Esm` (both still plain `.js`, `module.exports`-based
+/// files -- see `Test::get_code`), or `(await import(...)).default()` for `EsmStatic`/
+/// `TypeScript` (real ES modules with no `module.exports`). `TypeScript` additionally needs
+/// a loader registered before any `.ts` file can be `import()`-ed at all; the best this
+/// driver can do from inside an already-running script is `ts-node/register`, which covers
+/// CJS-style `require`/transpile-on-load but -- unlike the CLI's `--loader ts-node/esm`
+/// flag -- can't hook native ESM `import()` of a `.ts` file, so genuinely ESM-only
+/// TypeScript packages still need that flag passed to `mocha`/`node` by hand.
+pub fn get_meta_test_code(num_tests: i32, seed: u64, module_load_mode: ModuleLoadMode) -> String {
+    let ext = module_load_mode.file_extension();
     // async error handler -- this avoids the test suite bailing out early if
     // there is an error in one of the tests
     let mut ret_code = [
+        &format!("// Generated with test generation seed: {}", seed),
+        if module_load_mode == ModuleLoadMode::TypeScript {
+            "require(\"ts-node/register\");"
+        } else {
+            ""
+        },
         "if (!process.hasUncaughtExceptionCaptureCallback()) process.setUncaughtExceptionCaptureCallback(() => {",
         "\tconsole.log(\"{\\\"async_error_in_test\\\": true}\");",
-        "});",          
+        "});",
     ].join("\n");
+    let is_static_esm_like = matches!(
+        module_load_mode,
+        ModuleLoadMode::EsmStatic | ModuleLoadMode::TypeScript
+    );
     for i in 1..=num_tests {
+        let invocation = if is_static_esm_like {
+            format!(
+                "\t\tawait (await import('./test{}.{}')).default();",
+                i, ext
+            )
+        } else {
+            format!("\t\tawait require('./test{}.{}')();", i, ext)
+        };
         ret_code.push_str(
             &[
                 &("\ndescribe('test".to_owned() + &i.to_string() + "!', function () {"),
                 "\tit('', async () => {",
-                &("\t\tawait require('./test".to_owned() + &i.to_string() + ".js')();"),
+                &invocation,
                 "\t});\n});",
             ]
             .join("\n"),
@@ -0,0 +1,114 @@
+//! Support for `--config <file>` (see `main`): invocation parameters normally come only from
+//! CLI flags on `Opt`, but a config file lets a reproducible per-library generation recipe be
+//! checked into version control instead of a long shell command line.
+//!
+//! Config files are TOML by default, or JSON if the path's extension is `.json`, with keys
+//! mirroring `Opt`'s field names, plus one nessie-specific directive: a top-level `%include`
+//! key gives the path (resolved relative to the including file) of another config file whose
+//! settings are merged in as a lower-precedence base. Includes are resolved recursively;
+//! an include cycle is an error rather than an infinite loop.
+//!
+//! Precedence (highest wins): explicit CLI flags > the top-level `--config` file's own keys >
+//! keys it pulled in via `%include` (and, transitively, whatever *that* file itself included).
+//! Applying CLI-over-config precedence is `main`'s job (it's the one place that knows the
+//! shape of `Opt`); this module only resolves a `--config` file (and its includes) down to a
+//! single merged table.
+
+use crate::errors::DFError;
+
+use std::path::{Path, PathBuf};
+
+/// Top-level key recognized in a config file as an include directive.
+const INCLUDE_KEY: &str = "%include";
+
+/// Load `path` and recursively merge in anything it `%include`s, returning the combined
+/// top-level table with `path`'s own keys taking precedence over included ones.
+pub fn load_config_with_includes(path: &Path) -> Result<toml::value::Table, DFError> {
+    let mut include_chain = Vec::new();
+    load_config_recursive(path, &mut include_chain)
+}
+
+/// `include_chain` holds the canonicalized paths of every config file currently being
+/// resolved as an ancestor of `path` (i.e. the chain of `%include`s that led here), so a
+/// file that tries to (transitively) include itself is caught instead of recursing forever.
+/// The same file being included from two different, non-cyclic branches is fine and isn't
+/// flagged -- only a cycle back to an ancestor on the *current* chain is an error.
+fn load_config_recursive(
+    path: &Path,
+    include_chain: &mut Vec<PathBuf>,
+) -> Result<toml::value::Table, DFError> {
+    let canonical = std::fs::canonicalize(path)
+        .map_err(|e| DFError::ConfigFileError(format!("could not read config file {:?}: {}", path, e)))?;
+    if include_chain.contains(&canonical) {
+        return Err(DFError::ConfigFileError(format!(
+            "include cycle detected: {:?} is already on the include chain {:?}",
+            canonical, include_chain
+        )));
+    }
+
+    let contents = std::fs::read_to_string(&canonical)
+        .map_err(|e| DFError::ConfigFileError(format!("could not read config file {:?}: {}", path, e)))?;
+    let mut table = parse_config_table(&canonical, &contents)?;
+
+    // pull out (and consume) the include directive so it isn't later mistaken for an
+    // `Opt` field
+    let include_target = table.remove(INCLUDE_KEY);
+
+    let merged = match include_target {
+        None => table,
+        Some(include_value) => {
+            let include_file = include_value.as_str().ok_or_else(|| {
+                DFError::ConfigFileError(format!(
+                    "{:?} in {:?} must be a string path",
+                    INCLUDE_KEY, canonical
+                ))
+            })?;
+            let include_path = canonical
+                .parent()
+                .unwrap_or_else(|| Path::new("."))
+                .join(include_file);
+
+            include_chain.push(canonical.clone());
+            let mut base = load_config_recursive(&include_path, include_chain)?;
+            include_chain.pop();
+
+            // this file's own keys override whatever the include supplied
+            for (key, value) in table {
+                base.insert(key, value);
+            }
+            base
+        }
+    };
+
+    Ok(merged)
+}
+
+/// Parse `contents` as TOML, unless `path`'s extension is `.json`, in which case parse it as
+/// JSON and convert to `toml::Value` (so the rest of this module, and `main`'s field lookups,
+/// only ever deal with one value type regardless of which format the file was written in).
+fn parse_config_table(path: &Path, contents: &str) -> Result<toml::value::Table, DFError> {
+    let is_json = path.extension().and_then(|ext| ext.to_str()) == Some("json");
+    let value = if is_json {
+        let json_value: serde_json::Value = serde_json::from_str(contents).map_err(|e| {
+            DFError::ConfigFileError(format!("invalid JSON in config file {:?}: {}", path, e))
+        })?;
+        toml::Value::try_from(json_value).map_err(|e| {
+            DFError::ConfigFileError(format!(
+                "could not convert config file {:?} to a config table: {}",
+                path, e
+            ))
+        })?
+    } else {
+        contents
+            .parse::<toml::Value>()
+            .map_err(|e| DFError::ConfigFileError(format!("invalid TOML in config file {:?}: {}", path, e)))?
+    };
+
+    match value {
+        toml::Value::Table(table) => Ok(table),
+        _ => Err(DFError::ConfigFileError(format!(
+            "config file {:?} must be a table/object at the top level",
+            path
+        ))),
+    }
+}
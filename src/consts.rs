@@ -1,10 +1,16 @@
 //! Configuration values for the test generator.
-//! TODO these should be the default values, add user functionality
-//! for specification via config file
+//! The handful of values a user might reasonably want to tune per-package without
+//! recompiling (rather than ones that encode a fixed structural decision about the
+//! generator, e.g. `ALLOW_MULTIPLE_CALLBACK_ARGS`) are also exposed as fields on
+//! `Config`, with these constants as its `Default` -- see `Config`.
 
 // configuration for the test generator itself
 /// Number of tests generated per function in the API discovery phase.
 pub const DISCOVERY_PHASE_TESTING_BUDGET: i32 = 10;
+/// Maximum number of repair candidates tried per failing discovery call, across all of its
+/// already-known-good target signatures (see `legacy::discovery::repair_candidates`). Bounds
+/// the cost of the repair search: each candidate costs one extra test execution.
+pub const MAX_REPAIR_CANDIDATES_TRIED: usize = 5;
 /// Timeout (in seconds) after which an executing test is killed.
 pub const TEST_TIMEOUT_SECONDS: u64 = 30;
 /// If we specify a nested extension but there's no valid test that can be extended
@@ -39,6 +45,52 @@ pub const RECHOOSE_FCT_SIG_WEIGHT_FACTOR: f64 = 0.8;
 pub const USE_MINED_NESTING_EXAMPLE: f64 = 0.5;
 // Chance of using a mined API call signature example, if one is available.
 pub const USE_MINED_API_CALL_SIG: f64 = 0.5;
+// Chance that a `CallbackType` argument position in the legacy discovery phase is filled with
+// a reference to one of the module's other functions (optionally curried), instead of the
+// default bare named callback -- see `decisions::TestGenDB::gen_random_fn_ref_callback`.
+pub const CHOOSE_FN_REF_CB_PCT: f64 = 0.2;
+// Upper bound (inclusive) on the number of leading arguments bound into a generated
+// `CallbackVal::FnRef`'s `bound_prefix`.
+pub const MAX_FN_REF_BOUND_ARGS: usize = 2;
+// Coverage-guided weight feedback: when a call hits `n` previously-uncovered (file, line)
+// pairs, its function/signature weight is scaled by `1.0 + n * this factor`, instead of the
+// blind `RECHOOSE_*_WEIGHT_FACTOR` decay applied when it adds no new coverage.
+pub const COVERAGE_WEIGHT_BOOST_FACTOR: f64 = 0.1;
+// Extension-point weight floor: a test that contributes zero new whole-test V8 coverage
+// is still retained in the extension-point pool (it may still be useful for nesting/sequencing),
+// but its weight is floored at this value rather than at 0, so it's picked rarely rather than never.
+pub const MIN_EXTENSION_POINT_WEIGHT: f64 = 0.01;
+// Signature retirement: once a function/signature pair has gone this many consecutive
+// `apply_coverage_weight_feedback` rounds without hitting any new coverage line, it's
+// excluded from `gen_new_sig_with_cb`'s candidate pool entirely (rather than just decayed
+// towards, but never reaching, zero weight) -- see `TestGenDB::non_retired_sig_weights`.
+pub const MAX_STALE_COVERAGE_ATTEMPTS: u32 = 5;
+// Fallback size of the `node`-subprocess worker pool in `run_testgen_phase`, used only
+// if `std::thread::available_parallelism` can't determine the real core count.
+pub const DEFAULT_TEST_EXEC_POOL_SIZE: usize = 4;
+// Initial probability of choosing `ExtensionType::Nested` under
+// `decisions::ExtensionStrategy::Weighted`, before any adaptive feedback is applied.
+pub const DEFAULT_NESTED_WEIGHT: f64 = 0.5;
+// Adaptive extension-strategy feedback: each test's `nested_weight` is nudged by this much
+// towards `MAX_NESTED_WEIGHT` (if a callback was found to nest into) or `MIN_NESTED_WEIGHT`
+// (if not), see `decisions::TestGenDB::update_ext_strategy_weights`.
+pub const NESTED_WEIGHT_ADAPTIVE_STEP: f64 = 0.05;
+// Clamps on the adaptive `nested_weight`, so neither `ExtensionType` variant is ever starved
+// out entirely under `decisions::ExtensionStrategy::Weighted`.
+pub const MIN_NESTED_WEIGHT: f64 = 0.05;
+pub const MAX_NESTED_WEIGHT: f64 = 0.95;
+// Argument-type weight feedback: every top-level argument type used by a just-executed call
+// has its `decisions::TestGenDB::arg_type_weights` entry scaled by this much when that call
+// hit a `FunctionCallResult::ExecutionError` (mirrors `RECHOOSE_*_WEIGHT_FACTOR`'s blind
+// decay-on-no-progress shape, but for `choose_random_arg_type` rather than whole
+// functions/signatures).
+pub const ARG_TYPE_ERROR_WEIGHT_FACTOR: f64 = 0.8;
+// Counterpart boost applied to a top-level argument type's weight when its call did *not*
+// error: scaled by `1.0 + this factor`, same shape as `COVERAGE_WEIGHT_BOOST_FACTOR`.
+pub const ARG_TYPE_SUCCESS_WEIGHT_BOOST_FACTOR: f64 = 0.05;
+// Chance that a generated array/object element (or bound `gen_random_fn_ref_callback`
+// argument) is a number rather than a string.
+pub const ARRAY_OBJ_NUMBER_ELT_PCT: f64 = 0.5;
 
 /// Metadata for the file system setup required before tests are generated.
 pub mod setup {
@@ -51,3 +103,112 @@ pub mod setup {
     /// Prefix for the file name of the generated tests.
     pub const TEST_FILE_PREFIX: &str = "test";
 }
+
+/// The subset of this module's constants a user can reasonably want to tune per-package
+/// (weight factors, choice probabilities, the toy filesystem layout) without recompiling,
+/// loaded from an optional `[generation]` table in a `--config` file (see `crate::config`)
+/// and threaded into `decisions::TestGenDB::new` and `testgen::run_testgen_phase` instead of
+/// those reading the bare constants above directly. Anything not exposed here (e.g.
+/// `ALLOW_MULTIPLE_CALLBACK_ARGS`) is a fixed structural decision about how the generator
+/// works, not a per-package tuning knob, so it stays a plain constant.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// See `DISCOVERY_PHASE_TESTING_BUDGET`.
+    pub discovery_phase_testing_budget: i32,
+    /// See `TEST_TIMEOUT_SECONDS`.
+    pub test_timeout_seconds: u64,
+    /// See `MAX_GENERATED_NUM`.
+    pub max_generated_num: f64,
+    /// See `CHOOSE_NEW_SIG_PCT`. Must be in `[0, 1]`.
+    pub choose_new_sig_pct: f64,
+    /// See `RECHOOSE_LIB_FCT_WEIGHT_FACTOR`. Must be in `[0, 1]`.
+    pub rechoose_lib_fct_weight_factor: f64,
+    /// See `RECHOOSE_FCT_SIG_WEIGHT_FACTOR`. Must be in `[0, 1]`.
+    pub rechoose_fct_sig_weight_factor: f64,
+    /// See `USE_MINED_NESTING_EXAMPLE`. Must be in `[0, 1]`.
+    pub use_mined_nesting_example: f64,
+    /// See `USE_MINED_API_CALL_SIG`. Must be in `[0, 1]`.
+    pub use_mined_api_call_sig: f64,
+    /// See `ARG_TYPE_ERROR_WEIGHT_FACTOR`. Must be in `[0, 1]`.
+    pub arg_type_error_weight_factor: f64,
+    /// See `ARG_TYPE_SUCCESS_WEIGHT_BOOST_FACTOR`.
+    pub arg_type_success_weight_boost_factor: f64,
+    /// Probability that a generated array/object element, or a `gen_random_fn_ref_callback`
+    /// bound argument, is a number rather than a string. Must be in `[0, 1]`; sampled via
+    /// `TestGenDB::sample_bernoulli` rather than the old fixed-50/50 `gen_range(0..=1) < 1`
+    /// idiom, so this actually takes effect instead of always behaving like a coin flip.
+    pub array_obj_number_elt_pct: f64,
+    /// See `MAX_GENERATED_ARRAY_LENGTH`.
+    pub max_generated_array_length: usize,
+    /// See `MAX_GENERATED_OBJ_LENGTH`.
+    pub max_generated_obj_length: usize,
+    /// See `RANDOM_STRING_LENGTH`.
+    pub random_string_length: usize,
+    /// See `setup::TOY_FS_DIRS`.
+    pub toy_fs_dirs: Vec<String>,
+    /// See `setup::TOY_FS_FILES`.
+    pub toy_fs_files: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            discovery_phase_testing_budget: DISCOVERY_PHASE_TESTING_BUDGET,
+            test_timeout_seconds: TEST_TIMEOUT_SECONDS,
+            max_generated_num: MAX_GENERATED_NUM,
+            choose_new_sig_pct: CHOOSE_NEW_SIG_PCT,
+            rechoose_lib_fct_weight_factor: RECHOOSE_LIB_FCT_WEIGHT_FACTOR,
+            rechoose_fct_sig_weight_factor: RECHOOSE_FCT_SIG_WEIGHT_FACTOR,
+            use_mined_nesting_example: USE_MINED_NESTING_EXAMPLE,
+            use_mined_api_call_sig: USE_MINED_API_CALL_SIG,
+            arg_type_error_weight_factor: ARG_TYPE_ERROR_WEIGHT_FACTOR,
+            arg_type_success_weight_boost_factor: ARG_TYPE_SUCCESS_WEIGHT_BOOST_FACTOR,
+            array_obj_number_elt_pct: ARRAY_OBJ_NUMBER_ELT_PCT,
+            max_generated_array_length: MAX_GENERATED_ARRAY_LENGTH,
+            max_generated_obj_length: MAX_GENERATED_OBJ_LENGTH,
+            random_string_length: RANDOM_STRING_LENGTH,
+            toy_fs_dirs: setup::TOY_FS_DIRS.iter().map(|s| s.to_string()).collect(),
+            toy_fs_files: setup::TOY_FS_FILES.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+impl Config {
+    /// Check that every field that's supposed to be a probability or weight-decay factor
+    /// (as opposed to e.g. `max_generated_num`, which has no such constraint) is in
+    /// `[0, 1]`, returning `DFError::InvalidConfigValue` naming the first offending field.
+    /// Called right after a `Config` is loaded from a `--config` file, before it's handed
+    /// to `decisions::TestGenDB::new` -- a generator built from an out-of-range probability
+    /// would either never or always take a given branch, silently, which is worse than
+    /// failing fast at load time.
+    pub fn validate(&self) -> Result<(), crate::errors::DFError> {
+        let probabilities = [
+            ("choose_new_sig_pct", self.choose_new_sig_pct),
+            (
+                "rechoose_lib_fct_weight_factor",
+                self.rechoose_lib_fct_weight_factor,
+            ),
+            (
+                "rechoose_fct_sig_weight_factor",
+                self.rechoose_fct_sig_weight_factor,
+            ),
+            ("use_mined_nesting_example", self.use_mined_nesting_example),
+            ("use_mined_api_call_sig", self.use_mined_api_call_sig),
+            (
+                "arg_type_error_weight_factor",
+                self.arg_type_error_weight_factor,
+            ),
+            ("array_obj_number_elt_pct", self.array_obj_number_elt_pct),
+        ];
+        for (name, value) in probabilities {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(crate::errors::DFError::InvalidConfigValue(format!(
+                    "{:?} must be in [0, 1], got {:?}",
+                    name, value
+                )));
+            }
+        }
+        Ok(())
+    }
+}
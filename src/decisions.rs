@@ -6,39 +6,94 @@ use crate::consts::*;
 use crate::errors::*;
 use crate::functions::*;
 use crate::mined_seed_reps;
-use crate::mined_seed_reps::{LibMinedCallData, LibMinedData, MinedAPICall, MinedNestingPairJSON};
+use crate::mined_seed_reps::{MinedAPICall, MinedAPICallIndex, MinedNestingIndex, MinedNestingPairJSON};
 use crate::module_reps::*;
+use crate::sandbox::SandboxPolicy;
 use crate::tests::*;
+use crate::FxHashMap;
 use crate::TestGenMode;
 
 use rand::{
-    distributions::{Alphanumeric, WeightedIndex},
+    distributions::{Alphanumeric, Bernoulli, WeightedIndex},
     prelude::*,
+    rngs::SmallRng,
 };
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
 use strum::IntoEnumIterator;
 
+/// Per-position type-frequency distribution: `pos_type_freq[i][ty]` is how often `ty`
+/// succeeded (produced a non-`ExecutionError` result) at argument position `i` so far.
+/// Used by `gen_new_sig_with_cb` to sample each freshly-generated position from its
+/// observed distribution instead of uniformly, analogous to tracking the "active
+/// parameter" at a call site.
+pub type PosTypeFreq = Vec<HashMap<ArgType, f64>>;
+
+/// A stable 64-bit fingerprint over a discovery call's function name and its ordered
+/// argument `ArgType`s -- never concrete `ArgVal`s, so two calls with the same shape hash
+/// identically regardless of which values happened to be generated for them. Modeled on
+/// rhai's `calc_fn_hash`: used as the key of `TestGenDB::discovery_sig_cache`, so
+/// `legacy::discovery::run_discovery_phase` can recognize "we've already executed this
+/// exact type-shape of this function" across every receiver access path that aliases to it,
+/// instead of re-running one node process per access path for what's really the same call.
+pub fn calc_discovery_sig_hash(fct_name: &str, arg_types: &[ArgType]) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    fct_name.hash(&mut hasher);
+    arg_types.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A stable 64-bit fingerprint over `test`'s whole call tree (every call, not only the
+/// top-level ones `corpus_fingerprint` covers) and the `ext_type` it was built with --
+/// unlike `calc_discovery_sig_hash`, this also hashes each call's concrete `FunctionArgument`
+/// values, not just its abstract arg types, since two calls with the same shape but
+/// different argument values can execute completely differently. Modeled on proptest's
+/// `result_cache`: used as the key of `TestGenDB::result_cache`, so `run_testgen_phase` can
+/// recognize a freshly-generated test as equivalent to one it already ran and reuse that
+/// run's `TestDiagnostics` instead of spawning another `node` subprocess for it. A collision
+/// (or two tests this fingerprint can't tell apart, e.g. differing only in
+/// `FunctionCall::receiver`) only costs an unnecessary cache hit for a library whose calls
+/// aren't actually pure -- which is exactly what `--no-cache` (see `TestGenDB::
+/// set_result_cache_enabled`) is the escape hatch for.
+pub fn calc_test_result_cache_hash(test: &Test, ext_type: ExtensionType) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    ext_type.hash(&mut hasher);
+    for node in test.get_fct_tree().iter() {
+        let call = node.get();
+        call.get_name().hash(&mut hasher);
+        call.sig.get_arg_list().hash(&mut hasher);
+        node.parent().is_some().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
 /// Generate a new signature with `num_args` arguments.
 /// `sigs` is a list of previous signatures, and there's a `CHOOSE_SIG_PCT` chance of
-/// returning a signature from this list.
+/// returning a signature from this list. `weighted_sigs`' weights bias which previous
+/// signature gets picked -- a higher weight (e.g. from `sig_success_counts` in
+/// `legacy::discovery::run_discovery_phase`) means it's chosen more often.
 /// There's also an optional `cb_position` specifying a position for a callback argument.
-/// `testgen_db` is the state of the current test generation run.
+/// `testgen_db` is the state of the current test generation run. `pos_type_freq`, if
+/// given, biases brand-new (not chosen from `weighted_sigs`) positions towards
+/// previously-successful types at that position, instead of sampling uniformly.
 pub fn gen_new_sig_with_cb(
     num_args: Option<usize>,
     weighted_sigs: &HashMap<Vec<ArgType>, f64>,
     cb_position: Option<i32>,
-    testgen_db: &TestGenDB,
+    testgen_db: &mut TestGenDB,
     test_gen_mode: &TestGenMode,
+    pos_type_freq: Option<&PosTypeFreq>,
 ) -> FunctionSignature {
     // look at the list of signatures CHOOSE_NEW_SIG_PCT of the time (if the list is non-empty)
     if !weighted_sigs.is_empty()
-        && (thread_rng().gen_range(0..=100) as f64) / 100. > CHOOSE_NEW_SIG_PCT
+        && !testgen_db.sample_bernoulli(testgen_db.config.choose_new_sig_pct)
     {
         let vec_sigs_weights = weighted_sigs.iter().collect::<Vec<(&Vec<ArgType>, &f64)>>();
         let dist = WeightedIndex::new(vec_sigs_weights.iter().map(|(_, weight)| **weight)).unwrap();
-        let rand_sig_index = dist.sample(&mut thread_rng());
+        let rand_sig_index = dist.sample(&mut testgen_db.rng);
         let (abstract_sig, _) = &vec_sigs_weights[rand_sig_index].clone();
         if !test_gen_mode.tracks_prim_types() {
             FunctionSignature::from(&testgen_db.randomize_prim_arg_types(abstract_sig))
@@ -47,7 +102,7 @@ pub fn gen_new_sig_with_cb(
         }
     } else {
         let num_args =
-            num_args.unwrap_or_else(|| thread_rng().gen_range(0..=DEFAULT_MAX_ARG_LENGTH));
+            num_args.unwrap_or_else(|| testgen_db.rng.gen_range(0..=DEFAULT_MAX_ARG_LENGTH));
         let mut args: Vec<FunctionArgument> = Vec::with_capacity(num_args);
 
         // generate random values for all arguments, unless `cb_position` is a valid
@@ -57,13 +112,17 @@ pub fn gen_new_sig_with_cb(
                 if cb_position.is_some() && i32::try_from(arg_index) == Ok(cb_position.unwrap()) {
                     FunctionArgument::new(ArgType::CallbackType, None)
                 } else {
-                    FunctionArgument::new(
-                        testgen_db.choose_random_arg_type(
+                    let observed_freq = pos_type_freq
+                        .and_then(|freqs| freqs.get(arg_index))
+                        .filter(|freq| !freq.is_empty());
+                    let arg_type = match observed_freq {
+                        Some(freq) => testgen_db.choose_weighted_arg_type(freq),
+                        None => testgen_db.choose_random_arg_type(
                             ALLOW_MULTIPLE_CALLBACK_ARGS,
                             ALLOW_ANY_TYPE_ARGS,
                         ),
-                        None,
-                    )
+                    };
+                    FunctionArgument::new(arg_type, None)
                 },
             );
         }
@@ -75,20 +134,189 @@ pub fn gen_new_sig_with_cb(
     }
 }
 
+/// Strategy `TestGenDB::gen_random_ext_type` uses to choose between `ExtensionType::Nested`
+/// (depth: nesting a new call in a found callback) and `ExtensionType::Sequential` (breadth:
+/// chaining a new call after an existing one) when extending a test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtensionStrategy {
+    /// Always extend with `ExtensionType::Nested`.
+    FixedNested,
+    /// Always extend with `ExtensionType::Sequential`.
+    FixedSequential,
+    /// Uniformly random choice between the two `ExtensionType` variants (the previous,
+    /// only, behaviour of `gen_random_ext_type`).
+    UniformRandom,
+    /// Weighted choice, biased towards whichever variant recent tests suggest is more
+    /// useful: see `TestGenDB::update_ext_strategy_weights`.
+    Weighted,
+}
+
+/// Autocast from strings to ExtensionStrategy
+impl std::str::FromStr for ExtensionStrategy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "FixedNested" => Ok(Self::FixedNested),
+            "FixedSequential" => Ok(Self::FixedSequential),
+            "UniformRandom" => Ok(Self::UniformRandom),
+            "Weighted" => Ok(Self::Weighted),
+            _ => Err(()),
+        }
+    }
+}
+
+impl ExtensionStrategy {
+    /// Short form label for the type of the extension strategy
+    pub fn label(&self) -> String {
+        match self {
+            Self::FixedNested => "FixedNested",
+            Self::FixedSequential => "FixedSequential",
+            Self::UniformRandom => "UniformRandom",
+            Self::Weighted => "Weighted",
+        }
+        .to_string()
+    }
+}
+
 type ExtensionPoints = Vec<(
     ExtensionType,
     (Test, Option<ExtensionPointID>, Option<String>),
+    f64,
 )>;
 
+/// One recorded edge in the campaign's test-extension graph: test `child_test_id` was
+/// produced by extending test `parent_test_id` at `parent_ext_id`, optionally inside the
+/// callback-argument position `cb_arg_pos` (only set for `ExtensionType::Nested`), via
+/// `ext_type`. Recorded by `Test::extend` for every extension that actually had a base
+/// test to extend -- a fresh, non-extending test (the very first call in a lineage) gets a
+/// node (see `ExtensionGraphNode`) but no incoming edge. Consumed by
+/// `crate::graphviz::write_extension_graph_dot`.
+#[derive(Debug, Clone)]
+pub struct ExtensionEdge {
+    pub parent_test_id: usize,
+    pub parent_ext_id: ExtensionPointID,
+    pub cb_arg_pos: Option<String>,
+    pub ext_type: ExtensionType,
+    pub child_test_id: usize,
+}
+
+/// Minimal per-test metadata retained only for `crate::graphviz::write_extension_graph_dot`:
+/// a test's id and the names of every function call in its tree, snapshotted by
+/// `Test::extend` at the point the test is created. Cheap relative to keeping the whole
+/// `Test` (which `possible_ext_points`/the corpus already don't do for every test), and
+/// enough to render a readable DOT node without it.
+#[derive(Debug, Clone)]
+pub struct ExtensionGraphNode {
+    pub test_id: usize,
+    pub call_names: Vec<String>,
+}
+
+/// Indexed by receiver access path (mirroring `module_reps::NpmModule`'s own `AccessPathIndex`)
+/// rather than a flat `Vec` over every function in the library, so `gen_random_call` only has
+/// to visit the (typically few) access paths actually in scope -- via `NpmModule::fns_rooted_in`
+/// -- instead of scanning every function the module has, in or out of scope, on every call.
 type LibFctWeightedMap = HashMap<
     String,
-    Vec<(
-        (AccessPathModuleCentred, String),
-        f64,
-        HashMap<Vec<ArgType>, f64>,
-    )>,
+    HashMap<
+        AccessPathModuleCentred,
+        Vec<(
+            String,
+            f64,
+            // weight, and number of consecutive coverage-feedback rounds this signature has
+            // gone without contributing any new coverage (see `MAX_STALE_COVERAGE_ATTEMPTS`).
+            HashMap<Vec<ArgType>, (f64, u32)>,
+        )>,
+    >,
 >;
 
+/// Structural fingerprint of a single top-level call in an "interesting" test, persisted
+/// alongside its `CorpusCaseJSON` purely for diagnostics: it's printed if a replayed case
+/// doesn't reproduce (e.g. after an upstream signature change), so a human can see at a
+/// glance how far the regenerated test drifted from what was originally found. It is *not*
+/// used to rebuild the test -- only `CorpusCaseJSON::seed`/`rng_draw_count_before` can do
+/// that, by replaying the same generation decisions (see `crate::testgen::replay_corpus`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CorpusCallFingerprintJSON {
+    /// See `FunctionCall::get_name`.
+    pub fct_name: String,
+    /// See `FunctionSignature::get_abstract_sig`.
+    pub abstract_sig: Vec<ArgType>,
+}
+
+/// Structural fingerprint of `test`'s top-level calls (see `CorpusCallFingerprintJSON`),
+/// shared by `TestGenDB::record_corpus_case` (to record it) and `crate::testgen::
+/// replay_corpus` (to compare a freshly-regenerated case against what was recorded).
+pub fn corpus_fingerprint(test: &Test) -> Vec<CorpusCallFingerprintJSON> {
+    let fct_tree = test.get_fct_tree();
+    test.get_top_level_call_ids()
+        .iter()
+        .map(|&id| {
+            let call = fct_tree[id].get();
+            CorpusCallFingerprintJSON {
+                fct_name: call.get_name().to_string(),
+                abstract_sig: call.sig.get_abstract_sig(),
+            }
+        })
+        .collect()
+}
+
+/// One "interesting" test (an execution error, or a novel signature/extension point --
+/// see `crate::testgen::fold_test_result_into_db`) preserved so a later run can replay it
+/// before doing any fresh random generation, modeled on proptest's `failure_persistence`.
+/// Rather than serializing the `Test` itself (its call tree isn't fully serde-enabled, and
+/// doing so would bypass the same generation logic that found it in the first place), a
+/// case is just enough to deterministically reproduce the same generation decisions: the
+/// campaign `seed`, and how many values had already been drawn from its RNG (see
+/// `TestGenDB::resume_from_checkpoint`) right before the `Test::extend` call that built it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorpusCaseJSON {
+    /// `TestGenDB::seed` this case was generated under.
+    pub seed: u64,
+    /// `TestGenDB::rng_draw_count` immediately before the `Test::extend` call that produced
+    /// this case, so replay can fast-forward a freshly-seeded RNG to exactly that point in
+    /// the stream before regenerating it.
+    pub rng_draw_count_before: u64,
+    /// Extension type the regenerated test must be extended with to reproduce this case.
+    pub ext_type: ExtensionType,
+    /// See `CorpusCallFingerprintJSON`.
+    pub fingerprint: Vec<CorpusCallFingerprintJSON>,
+}
+
+/// Serializable snapshot of the learned weight model and progress counters for a
+/// generation campaign: enough to resume the campaign (or warm-start a new one) with
+/// the same biases it had built up. `Vec`s are used in place of `libs_fcts_weights`'s
+/// inner `HashMap`s since the latter are keyed by non-string types, which `serde_json`
+/// can't represent as object keys.
+/// NOTE: `possible_ext_points` is deliberately not part of this snapshot -- it holds
+/// full `Test` call trees (`indextree::Arena`s), which aren't serde-enabled, so a
+/// resumed campaign starts with an empty extension-point pool but keeps its weights.
+#[derive(Debug, Serialize, Deserialize)]
+struct TestGenDBSnapshotJSON {
+    /// See `TestGenDB::libs_fcts_weights`, flattened for serialization.
+    libs_fcts_weights: HashMap<String, Vec<LibFctWeightEntryJSON>>,
+    /// See `TestGenDB::cur_test_index`.
+    cur_test_index: usize,
+    /// See `TestGenDB::seed`.
+    seed: u64,
+    /// See `TestGenDB::rng_draw_count`.
+    rng_draw_count: u64,
+}
+
+/// One entry of `libs_fcts_weights[lib_name]`, flattened for serialization.
+#[derive(Debug, Serialize, Deserialize)]
+struct LibFctWeightEntryJSON {
+    /// Access path of the function's receiver.
+    fct_acc_path: AccessPathModuleCentred,
+    /// Name of the function.
+    fct_name: String,
+    /// Weight of this function (within its library).
+    weight: f64,
+    /// Weight, and consecutive stale-coverage-round count, of each abstract signature
+    /// previously tried for this function. See `LibFctWeightedMap`.
+    sig_weights: Vec<(Vec<ArgType>, f64, u32)>,
+}
+
 /// Representation of the state of the test generator: configuration for
 /// random value generation, informed by previous tests generated/tried.
 pub struct TestGenDB {
@@ -100,48 +328,408 @@ pub struct TestGenDB {
     possible_ext_points: ExtensionPoints,
     /// Current test index.
     cur_test_index: usize,
-    /// Keep track of all the functions tested, per library,
-    /// so we can bias the generator to choose functions that haven't
-    /// been tested yet.
+    /// Keep track of all the functions tested, per library and per receiver access path,
+    /// so we can bias the generator to choose functions that haven't been tested yet. See
+    /// `LibFctWeightedMap`.
     libs_fcts_weights: LibFctWeightedMap,
-    /// Mined nesting data.
-    lib_mined_data: LibMinedData,
-    /// Mined api call data.
-    lib_mined_call_data: LibMinedCallData,
+    /// Mined nesting data, indexed by `(outer_pkg, outer_fct, outer_arity)` (see
+    /// `mined_seed_reps::MinedNestingIndex`).
+    mined_nesting_index: MinedNestingIndex,
+    /// Mined api call data, indexed by `(pkg, fct_name)` (see
+    /// `mined_seed_reps::MinedAPICallIndex`).
+    mined_api_call_index: MinedAPICallIndex,
     /// Directory the generated tests are written to.
     pub test_dir_path: String,
     /// Prefix for the test files (just the file, not the path).
     pub test_file_prefix: String,
     /// Optional: directory of the source code of the package we're generating tests for.
     pub api_src_dir: Option<String>,
+    /// Seed used to initialize `rng`, kept around so it can be surfaced in emitted
+    /// test metadata and a campaign can be replayed bit-for-bit from the same seed.
+    seed: u64,
+    /// Single, centralized source of randomness for the whole generator: every random
+    /// choice made during generation (weighted sampling, random values, extension type,
+    /// etc.) must go through this RNG so that a fixed `seed` deterministically reproduces
+    /// a generation campaign. `SmallRng` rather than `StdRng`: this is mining/generation-
+    /// internal randomness with no adversarial input, so the faster, non-cryptographic PRNG
+    /// is worth it, and it's still `SeedableRng` so a fixed `seed` reproduces bit-for-bit.
+    rng: SmallRng,
+    /// Number of values drawn from `rng` so far. Combined with `seed`, this lets a
+    /// resumed campaign fast-forward the RNG back to exactly where it left off.
+    rng_draw_count: u64,
+    /// Cumulative set of `(file, line)` pairs covered by any test run so far, used to
+    /// compute coverage deltas for the weight feedback in `add_extension_points_for_test`.
+    seen_coverage_lines: std::collections::HashSet<(String, u64)>,
+    /// Strategy `gen_random_ext_type` uses to choose between nesting and chaining.
+    ext_strategy: ExtensionStrategy,
+    /// Current probability of choosing `ExtensionType::Nested` under
+    /// `ExtensionStrategy::Weighted`; adaptively nudged by `update_ext_strategy_weights`.
+    /// Unused under any other strategy.
+    nested_weight: f64,
+    /// Discovery-phase cache from `calc_discovery_sig_hash` fingerprint to the
+    /// `FunctionCallResult` previously observed for that (function name, argument-type-shape)
+    /// pair, so `legacy::discovery::run_discovery_phase` can skip spawning node again for a
+    /// shape it's already executed -- even one that turns up again via a different access
+    /// path aliased to the same function.
+    discovery_sig_cache: HashMap<u64, FunctionCallResult>,
+    /// Execution-result cache from `calc_test_result_cache_hash` fingerprint to the
+    /// `TestDiagnostics` previously observed for that exact test, so `run_testgen_phase` can
+    /// skip re-executing a freshly-generated test that's structurally (and value-for-value)
+    /// identical to one already run this campaign. Modeled on proptest's `result_cache`.
+    /// Never consulted when `result_cache_enabled` is `false` (see `--no-cache`).
+    result_cache: HashMap<u64, TestDiagnostics>,
+    /// Whether `result_cache` is consulted/populated at all (see `set_result_cache_enabled`).
+    /// Defaults to `true`; `--no-cache` turns it off for libraries whose calls aren't pure,
+    /// where identical generated code can legitimately yield different outcomes run to run.
+    result_cache_enabled: bool,
+    /// Per-`ArgType` sampling weight used by `choose_random_arg_type` in place of a flat
+    /// draw, keyed by whichever of the six variants it can produce (`NumberType`/
+    /// `StringType`/`ArrayType`/`ObjectType`/`CallbackType`/`LibFunctionType`/`AnyType`). A
+    /// type missing from this map defaults to weight `1.0` (the old uniform behavior);
+    /// `apply_arg_type_weight_feedback` decays (or boosts) an entry after every call, based
+    /// on whether that call's own top-level argument types led to a `FunctionCallResult::
+    /// ExecutionError` -- mirrors how `apply_coverage_weight_feedback` re-weights
+    /// `libs_fcts_weights`, but for argument types rather than library functions/signatures.
+    arg_type_weights: HashMap<ArgType, f64>,
+    /// Every test-extension edge recorded this campaign (see `ExtensionEdge`), in the
+    /// order `Test::extend` produced them. Exported as a GraphViz digraph by
+    /// `crate::graphviz::write_extension_graph_dot`.
+    extension_edges: Vec<ExtensionEdge>,
+    /// Every test node recorded this campaign (see `ExtensionGraphNode`), in the order
+    /// `Test::extend` produced them.
+    extension_graph_nodes: Vec<ExtensionGraphNode>,
+    /// Capability allow-list enforced around every generated test's subprocess (see
+    /// `crate::sandbox`). Deny-by-default; set via `set_sandbox_policy` before generation
+    /// starts to loosen it for a trusted library.
+    pub sandbox_policy: SandboxPolicy,
+    /// Per-package-tunable generation values (weight factors, choice probabilities, toy
+    /// filesystem layout; see `consts::Config`), resolved once at construction from a
+    /// `--config` file's `[generation]` table (or `Config::default()` if absent) and used
+    /// in place of reading the bare `consts::*` constants directly.
+    config: Config,
+    /// Every package known to be under test this campaign, besides whichever one
+    /// `gen_random_call` is currently generating a call for -- e.g. the other libraries
+    /// registered in a `module_reps::ModuleRegistry` multi-module campaign (see
+    /// `legacy::discovery::run_discovery_phase_multi`). Passed to
+    /// `mined_seed_reps::get_rel_mined_data_nested_extensions` as the set of packages a
+    /// mined nested extension is allowed to cross into (e.g. `fs.realpath` nesting into
+    /// `q.reject`); empty for an ordinary single-module campaign, which restricts nesting to
+    /// the one package actually under test, same as before this field existed.
+    pkgs_under_test: std::collections::HashSet<String>,
 }
 
 impl<'cxt> TestGenDB {
     /// Constructor -- initial state of the generator before making any tests.
+    /// If `seed` is `None`, a fresh seed is drawn from entropy (and surfaced via `get_seed`
+    /// so the resulting campaign can still be replayed later). Pass `seed` explicitly (what
+    /// would otherwise be a separate `new_seeded` constructor) to reproduce a prior
+    /// campaign bit-for-bit: every `self.rng_mut()` call site (`gen_new_sig_with_cb`,
+    /// `choose_random_arg_type`, `gen_random_value_of_type`, `gen_random_number_val`,
+    /// `gen_random_string_val`, `gen_random_call`, `get_test_to_extend`, ...) already draws
+    /// exclusively from this seeded `rng`, never `rand::thread_rng()` directly.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         test_dir_path: String,
         test_file_prefix: String,
         mined_data: Option<Vec<MinedNestingPairJSON>>,
         mined_api_call_data: Option<Vec<MinedAPICall>>,
         api_src_dir: Option<String>,
+        seed: Option<u64>,
+        ext_strategy: ExtensionStrategy,
+        config: Config,
     ) -> Self {
+        let seed = seed.unwrap_or_else(|| thread_rng().gen());
         Self {
             fs_strings: Vec::new(),
             toy_dir_base: String::from("."),
             possible_ext_points: Vec::new(),
             cur_test_index: 0,
             libs_fcts_weights: HashMap::new(),
-            lib_mined_data: match mined_data {
-                Some(lmd) => MinedNestingPairJSON::lib_map_from_list(lmd),
-                None => HashMap::new(),
-            },
-            lib_mined_call_data: match mined_api_call_data {
-                Some(lmd) => MinedAPICall::lib_map_from_list(lmd),
-                None => HashMap::new(),
-            },
+            mined_nesting_index: MinedNestingIndex::from_list(mined_data.unwrap_or_default()),
+            mined_api_call_index: MinedAPICallIndex::from_list(
+                mined_api_call_data.unwrap_or_default(),
+            ),
             test_dir_path,
             test_file_prefix,
             api_src_dir,
+            seed,
+            rng: SmallRng::seed_from_u64(seed),
+            rng_draw_count: 0,
+            seen_coverage_lines: std::collections::HashSet::new(),
+            ext_strategy,
+            nested_weight: DEFAULT_NESTED_WEIGHT,
+            discovery_sig_cache: HashMap::new(),
+            result_cache: HashMap::new(),
+            result_cache_enabled: true,
+            arg_type_weights: HashMap::new(),
+            extension_edges: Vec::new(),
+            extension_graph_nodes: Vec::new(),
+            sandbox_policy: SandboxPolicy::default(),
+            config,
+            pkgs_under_test: std::collections::HashSet::new(),
+        }
+    }
+
+    /// Restore a `TestGenDB` from a previous checkpoint: same seed, with the RNG
+    /// fast-forwarded past the `rng_draw_count` values it had already produced.
+    /// This is what lets `set_cur_test_index`/the extension-point machinery resume
+    /// a campaign on the exact same random stream it was on before.
+    #[allow(clippy::too_many_arguments)]
+    pub fn resume_from_checkpoint(
+        test_dir_path: String,
+        test_file_prefix: String,
+        mined_data: Option<Vec<MinedNestingPairJSON>>,
+        mined_api_call_data: Option<Vec<MinedAPICall>>,
+        api_src_dir: Option<String>,
+        seed: u64,
+        rng_draw_count: u64,
+        ext_strategy: ExtensionStrategy,
+        config: Config,
+    ) -> Self {
+        let mut new_db = Self::new(
+            test_dir_path,
+            test_file_prefix,
+            mined_data,
+            mined_api_call_data,
+            api_src_dir,
+            Some(seed),
+            ext_strategy,
+            config,
+        );
+        // fast-forward the RNG stream back to where the checkpoint left off.
+        // NOTE: this advances the stream by `rng_draw_count` logical draws (i.e., the
+        // same number of times `rng_mut` was called), not necessarily the same bytes
+        // that were consumed originally (e.g. a rejection-sampled `gen_range` can consume
+        // a variable number of words) -- good enough to keep later draws' *order*
+        // consistent, but not a byte-for-byte guarantee.
+        for _ in 0..rng_draw_count {
+            let _: u64 = new_db.rng_mut().gen();
+        }
+        new_db
+    }
+
+    /// Write a snapshot of this generator's learned weight model (`libs_fcts_weights`)
+    /// and progress counters (`cur_test_index`, `seed`, `rng_draw_count`) to `path`, so the
+    /// campaign can be stopped and resumed later, or so the weights can be shipped as a
+    /// pre-trained model for a given npm module (see `from_snapshot`).
+    pub fn write_snapshot(&self, path: &PathBuf) -> Result<(), DFError> {
+        let snapshot = TestGenDBSnapshotJSON {
+            libs_fcts_weights: self
+                .libs_fcts_weights
+                .iter()
+                .map(|(lib_name, fct_weights_by_path)| {
+                    (
+                        lib_name.clone(),
+                        fct_weights_by_path
+                            .iter()
+                            .flat_map(|(fct_acc_path, entries)| {
+                                entries.iter().map(move |(fct_name, weight, sig_weights)| {
+                                    LibFctWeightEntryJSON {
+                                        fct_acc_path: fct_acc_path.clone(),
+                                        fct_name: fct_name.clone(),
+                                        weight: *weight,
+                                        sig_weights: sig_weights
+                                            .iter()
+                                            .map(|(sig, (w, stale_rounds))| {
+                                                (sig.clone(), *w, *stale_rounds)
+                                            })
+                                            .collect(),
+                                    }
+                                })
+                            })
+                            .collect(),
+                    )
+                })
+                .collect(),
+            cur_test_index: self.cur_test_index,
+            seed: self.seed,
+            rng_draw_count: self.rng_draw_count,
+        };
+        let serialized = serde_json::to_string_pretty(&snapshot)
+            .map_err(|_| DFError::WritingSnapshotError(path.to_string_lossy().to_string()))?;
+        std::fs::write(path, serialized)
+            .map_err(|_| DFError::WritingSnapshotError(path.to_string_lossy().to_string()))
+    }
+
+    /// Load a generator snapshot written by `write_snapshot` and rehydrate a `TestGenDB`
+    /// warm-started from it: same learned weights and progress as when the snapshot was
+    /// taken, same constructor arguments otherwise. Function access paths in the snapshot
+    /// that no longer resolve against `mod_rep.get_fns()` (e.g. the library changed between
+    /// runs) are dropped, rather than kept around with a now-meaningless weight.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_snapshot(
+        path: &PathBuf,
+        mod_rep: &NpmModule,
+        test_dir_path: String,
+        test_file_prefix: String,
+        mined_data: Option<Vec<MinedNestingPairJSON>>,
+        mined_api_call_data: Option<Vec<MinedAPICall>>,
+        api_src_dir: Option<String>,
+        ext_strategy: ExtensionStrategy,
+        config: Config,
+    ) -> Result<Self, DFError> {
+        let file_conts_string = std::fs::read_to_string(path)
+            .map_err(|_| DFError::ReadingSnapshotError(path.to_string_lossy().to_string()))?;
+        let snapshot: TestGenDBSnapshotJSON = serde_json::from_str(&file_conts_string)
+            .map_err(|_| DFError::ReadingSnapshotError(path.to_string_lossy().to_string()))?;
+
+        let mut new_db = Self::resume_from_checkpoint(
+            test_dir_path,
+            test_file_prefix,
+            mined_data,
+            mined_api_call_data,
+            api_src_dir,
+            snapshot.seed,
+            snapshot.rng_draw_count,
+            ext_strategy,
+            config,
+        );
+        new_db.cur_test_index = snapshot.cur_test_index;
+        new_db.libs_fcts_weights = snapshot
+            .libs_fcts_weights
+            .into_iter()
+            .map(|(lib_name, fct_entries)| {
+                let mut fct_weights_by_path: HashMap<
+                    AccessPathModuleCentred,
+                    Vec<(String, f64, HashMap<Vec<ArgType>, (f64, u32)>)>,
+                > = HashMap::new();
+                for entry in fct_entries {
+                    if !mod_rep
+                        .get_fns()
+                        .contains_key(&(entry.fct_acc_path.clone(), entry.fct_name.clone()))
+                    {
+                        continue;
+                    }
+                    fct_weights_by_path
+                        .entry(entry.fct_acc_path)
+                        .or_insert_with(Vec::new)
+                        .push((
+                            entry.fct_name,
+                            entry.weight,
+                            entry
+                                .sig_weights
+                                .into_iter()
+                                .map(|(sig, w, stale_rounds)| (sig, (w, stale_rounds)))
+                                .collect(),
+                        ));
+                }
+                (lib_name, fct_weights_by_path)
+            })
+            .collect();
+        Ok(new_db)
+    }
+
+    /// Single point of access to the generator's RNG: every random choice in this module
+    /// must go through this method (instead of `thread_rng()`) so that a fixed seed
+    /// deterministically reproduces a generation campaign.
+    fn rng_mut(&mut self) -> &mut SmallRng {
+        self.rng_draw_count += 1;
+        &mut self.rng
+    }
+
+    /// Sample a `true`/`false` outcome with probability `pct` of `true`, via
+    /// `rand::distributions::Bernoulli` -- rather than thresholding an integer `gen_range`
+    /// draw the way this module used to (e.g. the old `gen_range(0..=1) < 1` idiom), which
+    /// silently clamps whatever probability constant it's compared against to an effective
+    /// 50/50, since a 2-outcome integer range can't represent anything finer. This is what
+    /// lets `choose_new_sig_pct`/`array_obj_number_elt_pct`/friends actually take effect at
+    /// their configured value instead of always behaving like a coin flip.
+    fn sample_bernoulli(&mut self, pct: f64) -> bool {
+        Bernoulli::new(pct).unwrap().sample(self.rng_mut())
+    }
+
+    /// Getter for the seed this generator's RNG was initialized with.
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Getter for the number of values drawn from the RNG so far (the checkpoint needed
+    /// to resume the exact same random stream, alongside `get_seed`).
+    pub fn get_rng_draw_count(&self) -> u64 {
+        self.rng_draw_count
+    }
+
+    /// Getter for the strategy this generator chooses extension types with, so a replayed
+    /// corpus case (see `record_corpus_case`) can be regenerated under a `TestGenDB` with
+    /// the same behaviour as the one that originally produced it.
+    pub fn get_ext_strategy(&self) -> ExtensionStrategy {
+        self.ext_strategy
+    }
+
+    /// Getter for the resolved per-package generation values this generator was built
+    /// with (see `Config`), e.g. so `legacy::discovery::run_discovery_phase` can read
+    /// `discovery_phase_testing_budget` without needing it threaded through as its own
+    /// parameter.
+    pub fn get_config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Persist `cur_test` -- generated by the `Test::extend` call that started at
+    /// `rng_draw_count_before` draws into this generator's RNG stream -- as an interesting
+    /// case for `lib_name`, appending it to the corpus file at `path` (creating it, and/or
+    /// `lib_name`'s entry in it, if this is the first case). See `CorpusCaseJSON` and
+    /// `crate::testgen::replay_corpus`.
+    pub fn record_corpus_case(
+        &self,
+        path: &PathBuf,
+        lib_name: &str,
+        cur_test: &Test,
+        ext_type: ExtensionType,
+        rng_draw_count_before: u64,
+    ) -> Result<(), DFError> {
+        let case = CorpusCaseJSON {
+            seed: self.seed,
+            rng_draw_count_before,
+            ext_type,
+            fingerprint: corpus_fingerprint(cur_test),
+        };
+
+        let mut corpus: HashMap<String, Vec<CorpusCaseJSON>> = if path.exists() {
+            let file_conts_string = std::fs::read_to_string(path)
+                .map_err(|_| DFError::ReadingCorpusError(path.to_string_lossy().to_string()))?;
+            serde_json::from_str(&file_conts_string)
+                .map_err(|_| DFError::ReadingCorpusError(path.to_string_lossy().to_string()))?
+        } else {
+            HashMap::new()
+        };
+        corpus.entry(lib_name.to_string()).or_insert_with(Vec::new).push(case);
+
+        let serialized = serde_json::to_string_pretty(&corpus)
+            .map_err(|_| DFError::WritingCorpusError(path.to_string_lossy().to_string()))?;
+        std::fs::write(path, serialized)
+            .map_err(|_| DFError::WritingCorpusError(path.to_string_lossy().to_string()))
+    }
+
+    /// Load the persisted corpus of interesting cases for `lib_name` from `path` (written
+    /// by `record_corpus_case`), e.g. to replay them before a fresh campaign begins (see
+    /// `crate::testgen::replay_corpus`). A `path` that doesn't exist yet isn't an error --
+    /// just an empty corpus, since the first run of a campaign with `--corpus-file` set
+    /// won't have written one yet.
+    pub fn load_corpus(path: &PathBuf, lib_name: &str) -> Result<Vec<CorpusCaseJSON>, DFError> {
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let file_conts_string = std::fs::read_to_string(path)
+            .map_err(|_| DFError::ReadingCorpusError(path.to_string_lossy().to_string()))?;
+        let corpus: HashMap<String, Vec<CorpusCaseJSON>> = serde_json::from_str(&file_conts_string)
+            .map_err(|_| DFError::ReadingCorpusError(path.to_string_lossy().to_string()))?;
+        Ok(corpus.get(lib_name).cloned().unwrap_or_default())
+    }
+
+    /// Choose an `ExtensionType` to extend a test with, via the centralized RNG, according
+    /// to `self.ext_strategy`.
+    pub fn gen_random_ext_type(&mut self) -> ExtensionType {
+        match self.ext_strategy {
+            ExtensionStrategy::FixedNested => ExtensionType::Nested,
+            ExtensionStrategy::FixedSequential => ExtensionType::Sequential,
+            ExtensionStrategy::UniformRandom => self.rng_mut().gen(),
+            ExtensionStrategy::Weighted => {
+                if self.rng_mut().gen_bool(self.nested_weight) {
+                    ExtensionType::Nested
+                } else {
+                    ExtensionType::Sequential
+                }
+            }
         }
     }
 
@@ -151,29 +739,74 @@ impl<'cxt> TestGenDB {
         self.toy_dir_base = toy_dir_base.to_owned();
     }
 
-    /// Choose random type for argument of type `arg_type`.
-    /// Note: can't have `allow_any` without `allow_cbs`.
-    pub fn choose_random_arg_type(&self, allow_cbs: bool, allow_any: bool) -> ArgType {
+    /// Choose a random `ArgType`, weighted by a previously-observed per-position
+    /// success-frequency distribution `freq` (see `PosTypeFreq`) instead of uniformly --
+    /// mirrors how `gen_new_sig_with_cb` already draws whole signatures from
+    /// `weighted_sigs`. `freq` must be non-empty.
+    pub fn choose_weighted_arg_type(&mut self, freq: &HashMap<ArgType, f64>) -> ArgType {
+        let entries = freq.iter().collect::<Vec<(&ArgType, &f64)>>();
+        let dist = WeightedIndex::new(entries.iter().map(|(_, weight)| **weight)).unwrap();
+        let chosen_index = dist.sample(self.rng_mut());
+        entries[chosen_index].0.clone()
+    }
+
+    /// Choose random type for argument of type `arg_type`, weighted by `arg_type_weights`
+    /// (a type missing from that map is weighted `1.0`, i.e. the old uniform behavior) --
+    /// see `apply_arg_type_weight_feedback` for how those weights adapt over a campaign.
+    /// Note: `AnyType` is only ever pushed onto `candidates` inside the `allow_cbs` branch
+    /// below, so `allow_cbs: false, allow_any: true` is accepted but never actually produces
+    /// `AnyType` -- it's `allow_cbs: true, allow_any: false` (the full candidate set minus
+    /// `AnyType`) that must stay valid; don't flip this assert without checking every
+    /// existing call site (see `gen_random_value_of_type`'s fallback below).
+    pub fn choose_random_arg_type(&mut self, allow_cbs: bool, allow_any: bool) -> ArgType {
         assert!(!(allow_cbs && !allow_any));
-        let num_arg_types = 4;
-        let max_arg_type_count = num_arg_types
-            + if allow_cbs {
-                if allow_any {
-                    3
-                } else {
-                    2
-                }
+        let mut candidates = vec![
+            ArgType::NumberType,
+            ArgType::StringType,
+            ArgType::ArrayType,
+            ArgType::ObjectType,
+        ];
+        if allow_cbs {
+            candidates.push(ArgType::CallbackType);
+            candidates.push(ArgType::LibFunctionType);
+            if allow_any {
+                candidates.push(ArgType::AnyType);
+            }
+        }
+        let weights: Vec<f64> = candidates
+            .iter()
+            .map(|arg_type| *self.arg_type_weights.get(arg_type).unwrap_or(&1.0))
+            .collect();
+        let dist = WeightedIndex::new(weights).unwrap();
+        let chosen_index = dist.sample(self.rng_mut());
+        candidates[chosen_index].clone()
+    }
+
+    /// Decay/boost `arg_type_weights` for every top-level argument type used by this test's
+    /// calls, based on whether each call's own result was a `FunctionCallResult::
+    /// ExecutionError` -- mirrors `apply_coverage_weight_feedback`'s blind decay-on-no-
+    /// progress/boost-on-progress shape, but keyed by `ArgType` rather than library
+    /// function/signature, so `choose_random_arg_type` learns to favor argument types a
+    /// given library actually accepts instead of wasting tests on the types it rejects.
+    fn apply_arg_type_weight_feedback(
+        &mut self,
+        test: &Test,
+        ext_point_results: &FxHashMap<ExtensionPointID, (FunctionCallResult, Option<String>)>,
+    ) {
+        let error_factor = self.config.arg_type_error_weight_factor;
+        let success_boost = 1.0 + self.config.arg_type_success_weight_boost_factor;
+        for (ext_id, (result, _)) in ext_point_results.iter() {
+            let Some(call) = test.get_fct_call_from_id(ext_id) else {
+                continue;
+            };
+            let boost = if matches!(result, FunctionCallResult::ExecutionError(_)) {
+                error_factor
             } else {
-                0
+                success_boost
             };
-        match thread_rng().gen_range(0..max_arg_type_count) {
-            0 => ArgType::NumberType,
-            1 => ArgType::StringType,
-            2 => ArgType::ArrayType,
-            3 => ArgType::ObjectType,
-            4 => ArgType::CallbackType,
-            5 => ArgType::LibFunctionType,
-            _ => ArgType::AnyType,
+            for arg_type in call.sig.get_abstract_sig() {
+                *self.arg_type_weights.entry(arg_type).or_insert(1.0) *= boost;
+            }
         }
     }
 
@@ -182,7 +815,7 @@ impl<'cxt> TestGenDB {
     /// You might be wondering why this is ever useful? It's not inherently, but
     /// just used for supporting old, deprecated versions of the test generator
     /// before we tracked the types of primitive arguments.
-    pub fn randomize_prim_arg_types(&self, abstract_sig: &Vec<ArgType>) -> Vec<ArgType> {
+    pub fn randomize_prim_arg_types(&mut self, abstract_sig: &Vec<ArgType>) -> Vec<ArgType> {
         let mut randomized_sig = Vec::with_capacity(abstract_sig.len());
         for arg_type in abstract_sig.iter() {
             randomized_sig.push(if arg_type.is_not_callback() {
@@ -204,7 +837,7 @@ impl<'cxt> TestGenDB {
     /// `mod_rep` is the representation of the API module that this generated value will be a part
     /// of testing: its functions are valid potential random values.
     pub fn gen_random_value_of_type(
-        &self,
+        &mut self,
         arg_type: ArgType,
         arg_pos: Option<usize>,
         ret_vals_pool: &Vec<ArgValAPTracked>,
@@ -212,93 +845,149 @@ impl<'cxt> TestGenDB {
         mod_rep: &NpmModule,
         test_gen_mode: &TestGenMode,
     ) -> ArgVal {
-        // gen AnyType? only if `ret_vals_pool` or `cb_arg_vals_pool` is non-empty
-        let arg_type = match (arg_type, (ret_vals_pool.len() + cb_arg_vals_pool.len()) > 0) {
-            (ArgType::AnyType, false) => {
-                self.choose_random_arg_type(true, false /* no AnyType */)
-            }
+        // gen AnyType? only if `ret_vals_pool` or `cb_arg_vals_pool` is non-empty -- these
+        // pools are already the live set of in-scope bindings at this extension point (see
+        // `tests::Test::get_ret_values_accessible_from_ext_point`/
+        // `get_cb_arg_values_accessible_from_ext_point`), so an empty pool means nothing is
+        // live here yet; fall back to a fresh concrete value instead of sampling `AnyType`
+        // with nothing to sample from -- `(false, false)`, not `(true, false)`: this is a
+        // fallback to a single *concrete* value, not an invitation to also pick a callback
+        let arg_type = match (&arg_type, (ret_vals_pool.len() + cb_arg_vals_pool.len()) > 0) {
+            (ArgType::AnyType, false) => self.choose_random_arg_type(false, false),
             (_, _) => arg_type,
         };
         match arg_type {
             ArgType::NullType => ArgVal::Null,
             ArgType::BoolType => self.gen_random_bool_val(),
             ArgType::NumberType => self.gen_random_number_val(),
+            ArgType::IntType => self.gen_random_int_val(),
+            ArgType::FloatType => self.gen_random_float_val(),
             ArgType::StringType => self.gen_random_string_val(true),
+            ArgType::StringEnum(vals) => {
+                let chosen = vals
+                    .iter()
+                    .choose(self.rng_mut())
+                    .cloned()
+                    .unwrap_or_default();
+                ArgVal::StringEnum("\"".to_owned() + &chosen + "\"", vals)
+            }
+            ArgType::ArrayOf(elt_type) => {
+                let max_generated_array_length = self.config.max_generated_array_length;
+                let num_elts = self.rng_mut().gen_range(0..=max_generated_array_length);
+                let mut gen_array: Vec<String> = Vec::with_capacity(num_elts);
+                for _ in 0..num_elts {
+                    gen_array.push(
+                        self.gen_random_value_of_type(
+                            (*elt_type).clone(),
+                            None,
+                            ret_vals_pool,
+                            cb_arg_vals_pool,
+                            mod_rep,
+                            test_gen_mode,
+                        )
+                        .get_string_rep(None, None, false, None),
+                    );
+                }
+                ArgVal::ArrayOf("[".to_owned() + &gen_array.join(", ") + "]", elt_type)
+            }
+            ArgType::Union(members) => {
+                let chosen_type = members
+                    .into_iter()
+                    .choose(self.rng_mut())
+                    .unwrap_or(ArgType::AnyType);
+                self.gen_random_value_of_type(
+                    chosen_type,
+                    arg_pos,
+                    ret_vals_pool,
+                    cb_arg_vals_pool,
+                    mod_rep,
+                    test_gen_mode,
+                )
+            }
             ArgType::ArrayType => {
                 // to keep things simple, we'll only have arrays of strings and/or numbers, like in the original lambdatester
                 // https://github.com/sola-da/LambdaTester/blob/master/utilities/randomGenerator.js#L90
-                let num_elts = thread_rng().gen_range(0..=MAX_GENERATED_ARRAY_LENGTH);
+                let max_generated_array_length = self.config.max_generated_array_length;
+                let num_elts = self.rng_mut().gen_range(0..=max_generated_array_length);
                 let mut gen_array: Vec<String> = Vec::with_capacity(num_elts);
-                let array_type = thread_rng().gen_range(0..=3);
+                let array_type = self.rng_mut().gen_range(0..=3);
                 for _ in 0..num_elts {
-                    gen_array.push(match (array_type, thread_rng().gen_range(0..=1) < 1) {
+                    let is_number_elt = self.sample_bernoulli(self.config.array_obj_number_elt_pct);
+                    gen_array.push(match (array_type, is_number_elt) {
                         (0, _) | (2, true) => self
                             .gen_random_number_val()
-                            .get_string_rep(None, None, false),
+                            .get_string_rep(None, None, false, None),
                         _ => self
                             .gen_random_string_val(true)
-                            .get_string_rep(None, None, false),
+                            .get_string_rep(None, None, false, None),
                     });
                 }
                 ArgVal::Array("[".to_owned() + &gen_array.join(", ") + "]")
             }
             ArgType::ObjectType => {
-                let num_elts = thread_rng().gen_range(0..=MAX_GENERATED_OBJ_LENGTH);
+                let max_generated_obj_length = self.config.max_generated_obj_length;
+                let num_elts = self.rng_mut().gen_range(0..=max_generated_obj_length);
                 let mut gen_obj: Vec<String> = Vec::with_capacity(num_elts);
                 for _ in 0..num_elts {
+                    let is_number_elt = self.sample_bernoulli(self.config.array_obj_number_elt_pct);
                     gen_obj.push(
                         self.gen_random_string_val(false)
-                            .get_string_rep(None, None, false)
+                            .get_string_rep(None, None, false, None)
                             + ": "
-                            + &match thread_rng().gen_range(0..=1) < 1 {
+                            + &match is_number_elt {
                                 true => self
                                     .gen_random_number_val()
-                                    .get_string_rep(None, None, false),
+                                    .get_string_rep(None, None, false, None),
                                 _ => self
                                     .gen_random_string_val(true)
-                                    .get_string_rep(None, None, false),
+                                    .get_string_rep(None, None, false, None),
                             },
                     );
                 }
                 ArgVal::Object("{".to_owned() + &gen_obj.join(", ") + "}")
             }
             ArgType::CallbackType => {
-                let num_args = thread_rng().gen_range(0..=DEFAULT_MAX_ARG_LENGTH);
+                let num_args = self.rng_mut().gen_range(0..=DEFAULT_MAX_ARG_LENGTH);
                 let cb_position = if num_args == 0 {
                     None
                 } else {
-                    Some(i32::try_from(thread_rng().gen_range(0..=(num_args * 2))).unwrap())
+                    Some(i32::try_from(self.rng_mut().gen_range(0..=(num_args * 2))).unwrap())
                     // x2 means there's a 50% chance of no callback (position never reached)
                     // NOTE: this is for the signature of the callback being generated -- a
                     // callback is always returned from this branch of the match
                 };
                 let sigs = HashMap::new();
-                let random_sig =
-                    gen_new_sig_with_cb(Some(num_args), &sigs, cb_position, self, test_gen_mode);
+                let random_sig = gen_new_sig_with_cb(
+                    Some(num_args),
+                    &sigs,
+                    cb_position,
+                    self,
+                    test_gen_mode,
+                    None,
+                );
                 self.gen_random_callback(Some(random_sig), arg_pos)
             }
             ArgType::LibFunctionType => {
                 // choose a random function in the API
                 let lib_name = mod_rep.get_mod_js_var_name();
-                ArgVal::LibFunction(
-                    lib_name.clone()
-                        + "."
-                        + mod_rep
-                            .get_fns()
-                            .keys()
-                            .filter(|(fct_acc_path, _)| {
-                                fct_acc_path == &AccessPathModuleCentred::RootPath(lib_name.clone())
-                            })
-                            .map(|(_, fct_name)| fct_name)
-                            .choose(&mut thread_rng())
-                            .unwrap(),
-                )
+                let fct_name = mod_rep
+                    .get_fns()
+                    .keys()
+                    .filter(|(fct_acc_path, _)| {
+                        fct_acc_path == &AccessPathModuleCentred::RootPath(lib_name.clone())
+                    })
+                    .map(|(_, fct_name)| fct_name)
+                    .choose(self.rng_mut())
+                    .unwrap()
+                    .clone();
+                ArgVal::LibFunction(lib_name + "." + &fct_name)
             }
             ArgType::AnyType => {
                 // choose a random value from the pool of available returns/args
                 // `AnyType` is only a valid random type if at least one of these lists is non-empty
-                let mut rand_index =
-                    thread_rng().gen_range(0..(ret_vals_pool.len() + cb_arg_vals_pool.len()));
+                let mut rand_index = self
+                    .rng_mut()
+                    .gen_range(0..(ret_vals_pool.len() + cb_arg_vals_pool.len()));
                 if rand_index < ret_vals_pool.len() {
                     ret_vals_pool
                         .iter()
@@ -316,26 +1005,53 @@ impl<'cxt> TestGenDB {
     }
 
     /// Generate a random number.
-    fn gen_random_number_val(&self) -> ArgVal {
-        ArgVal::Number((thread_rng().gen_range(-MAX_GENERATED_NUM..=MAX_GENERATED_NUM)).to_string())
+    fn gen_random_number_val(&mut self) -> ArgVal {
+        let max_generated_num = self.config.max_generated_num;
+        ArgVal::Number(
+            (self
+                .rng_mut()
+                .gen_range(-max_generated_num..=max_generated_num))
+            .to_string(),
+        )
+    }
+
+    /// Generate a random integer.
+    fn gen_random_int_val(&mut self) -> ArgVal {
+        let max_generated_num = self.config.max_generated_num as i64;
+        ArgVal::Int(
+            self.rng_mut()
+                .gen_range(-max_generated_num..=max_generated_num)
+                .to_string(),
+        )
+    }
+
+    /// Generate a random float (i.e., a number with a non-zero fractional part).
+    fn gen_random_float_val(&mut self) -> ArgVal {
+        let max_generated_num = self.config.max_generated_num;
+        ArgVal::Float(
+            (self
+                .rng_mut()
+                .gen_range(-max_generated_num..=max_generated_num))
+            .to_string(),
+        )
     }
 
     /// Generate a random boolean.
-    fn gen_random_bool_val(&self) -> ArgVal {
-        ArgVal::Bool((thread_rng().gen_range(1..=2) % 2 == 0).to_string())
+    fn gen_random_bool_val(&mut self) -> ArgVal {
+        ArgVal::Bool((self.rng_mut().gen_range(1..=2) % 2 == 0).to_string())
     }
 
     /// Generate a random string.
     /// Since we're possibly working with file system APIs, these strings can be configured to correspond
     /// to valid paths in the operating system with `include_fs_strings`.
-    fn gen_random_string_val(&self, include_fs_strings: bool) -> ArgVal {
+    fn gen_random_string_val(&mut self, include_fs_strings: bool) -> ArgVal {
         // if string, choose something from the self.fs_strings half the time
         // TODO if we're including fs strings, always choose an fs string
-        let string_choice = 0; // self.thread_rng().gen_range(0..=1);
+        let string_choice = 0; // self.rng_mut().gen_range(0..=1);
         ArgVal::String(match (string_choice, include_fs_strings) {
             (0, true) => {
                 // choose string from the list of valid files
-                let rand_index = thread_rng().gen_range(0..self.fs_strings.len());
+                let rand_index = self.rng_mut().gen_range(0..self.fs_strings.len());
                 "\"".to_owned()
                     // if there's an error in the generation of a file path, just return a random string
                     // ... this can happen when testing filesystem APIs, if a function deletes a file
@@ -344,8 +1060,8 @@ impl<'cxt> TestGenDB {
                         .into_os_string()
                         .into_string()
                         .unwrap(),
-                        Err(_) => self.toy_dir_base.clone() + "/" 
-                                + &self.gen_random_string_val(false).get_string_rep(None, None, false).replace('\"', ""),}
+                        Err(_) => self.toy_dir_base.clone() + "/"
+                                + &self.gen_random_string_val(false).get_string_rep(None, None, false, None).replace('\"', ""),}
                     + "\""
             }
             _ => {
@@ -354,11 +1070,14 @@ impl<'cxt> TestGenDB {
                 // THIS USED TO BE TRUE FOR DEBUGGING (uncomment the string concat to debug again)
                 // but make it start with the toy fs base just in case, to
                 // make sure if we're making new files with this random string it's fully contained in the toy_fs_dir
-                "\"".to_owned() 
+                let random_string_length = self.config.random_string_length;
+                let str_len = self.rng_mut().gen_range(1..=random_string_length);
+                "\"".to_owned()
                 // + self.toy_dir_base.clone()
-                    + &rand::thread_rng()
+                    + &self
+                        .rng_mut()
                         .sample_iter(&Alphanumeric)
-                        .take(thread_rng().gen_range(1..=RANDOM_STRING_LENGTH))
+                        .take(str_len)
                         .map(char::from)
                         .collect::<String>()
                     + "\""
@@ -370,7 +1089,7 @@ impl<'cxt> TestGenDB {
     /// `arg_pos` is an option to specify the position that this callback is in an arguments list
     /// e.g. if it's `cb` in `some_fct(x, y, cb)` then `arg_pos` would be 2.
     fn gen_random_callback(
-        &self,
+        &mut self,
         opt_sig: Option<FunctionSignature>,
         arg_pos: Option<usize>,
     ) -> ArgVal {
@@ -383,6 +1102,132 @@ impl<'cxt> TestGenDB {
         ArgVal::Callback(CallbackVal::RawCallback(cb))
     }
 
+    /// Generate a reference to one of `mod_rep`'s other functions, for use directly as a
+    /// callback argument (e.g. `arr.map(Number)`), optionally with a few leading arguments
+    /// already bound (a curried/partially-applied reference, e.g. `arr.forEach(cb.bind(null, x))`
+    /// rendered as a `bound_prefix`).
+    fn gen_random_fn_ref_callback(&mut self, mod_rep: &NpmModule) -> ArgVal {
+        let lib_name = mod_rep.get_mod_js_var_name();
+        let fct_name = mod_rep
+            .get_fns()
+            .keys()
+            .filter(|(fct_acc_path, _)| {
+                fct_acc_path == &AccessPathModuleCentred::RootPath(lib_name.clone())
+            })
+            .map(|(_, fct_name)| fct_name)
+            .choose(self.rng_mut())
+            .unwrap()
+            .clone();
+        let num_bound_args = self.rng_mut().gen_range(0..=MAX_FN_REF_BOUND_ARGS);
+        let array_obj_number_elt_pct = self.config.array_obj_number_elt_pct;
+        let bound_prefix = (0..num_bound_args)
+            .map(|_| match self.sample_bernoulli(array_obj_number_elt_pct) {
+                true => self.gen_random_number_val(),
+                false => self.gen_random_string_val(true),
+            })
+            .collect::<Vec<ArgVal>>();
+        ArgVal::Callback(CallbackVal::FnRef {
+            name: lib_name + "." + &fct_name,
+            bound_prefix,
+        })
+    }
+
+    /// Generate the `ArgVal` to fill a `CallbackType` position in the legacy discovery phase:
+    /// with `CHOOSE_FN_REF_CB_PCT` chance, a (possibly curried) reference to one of `mod_rep`'s
+    /// other functions (see `gen_random_fn_ref_callback`); otherwise the default bare named
+    /// callback, `cb`.
+    pub fn gen_discovery_cb_arg_val(&mut self, mod_rep: &NpmModule) -> ArgVal {
+        if self.rng_mut().gen_bool(CHOOSE_FN_REF_CB_PCT) {
+            self.gen_random_fn_ref_callback(mod_rep)
+        } else {
+            ArgVal::Callback(CallbackVal::Var("cb".to_string()))
+        }
+    }
+
+    /// Look up the `FunctionCallResult` previously recorded (via
+    /// `record_discovery_sig_result`) for `fct_name` called with the exact ordered
+    /// `arg_types` shape, so `legacy::discovery::run_discovery_phase` can reuse it instead of
+    /// executing an equivalent call again.
+    pub fn lookup_discovery_sig_cache(
+        &self,
+        fct_name: &str,
+        arg_types: &[ArgType],
+    ) -> Option<FunctionCallResult> {
+        self.discovery_sig_cache
+            .get(&calc_discovery_sig_hash(fct_name, arg_types))
+            .cloned()
+    }
+
+    /// Record the `FunctionCallResult` observed for `fct_name` called with `arg_types`, so a
+    /// later discovery attempt with the same (function, argument-type-shape) pair can be
+    /// answered from the cache instead of spending fresh testing budget on it.
+    pub fn record_discovery_sig_result(
+        &mut self,
+        fct_name: &str,
+        arg_types: &[ArgType],
+        result: FunctionCallResult,
+    ) {
+        self.discovery_sig_cache
+            .insert(calc_discovery_sig_hash(fct_name, arg_types), result);
+    }
+
+    /// Look up the `TestDiagnostics` previously recorded (via `record_test_result`) for a
+    /// test fingerprinted as `fingerprint` (see `calc_test_result_cache_hash`), so a caller
+    /// about to `cur_test.execute()` can reuse it instead of spawning another `node`
+    /// subprocess for an equivalent test. Always `None` when `result_cache_enabled` is
+    /// `false`.
+    pub fn lookup_result_cache(&self, fingerprint: u64) -> Option<TestDiagnostics> {
+        if !self.result_cache_enabled {
+            return None;
+        }
+        self.result_cache.get(&fingerprint).cloned()
+    }
+
+    /// Record `result` for a test fingerprinted as `fingerprint`, so a later equivalent test
+    /// this campaign can be answered from the cache instead of executing it. A no-op when
+    /// `result_cache_enabled` is `false`.
+    pub fn record_test_result(&mut self, fingerprint: u64, result: TestDiagnostics) {
+        if self.result_cache_enabled {
+            self.result_cache.insert(fingerprint, result);
+        }
+    }
+
+    /// Setter for whether the execution-result cache (see `result_cache`) is consulted and
+    /// populated at all. `--no-cache` turns this off for libraries whose calls aren't pure,
+    /// where identical generated code can legitimately yield different outcomes run to run.
+    pub fn set_result_cache_enabled(&mut self, enabled: bool) {
+        self.result_cache_enabled = enabled;
+    }
+
+    /// Record that test `test_id`, with top-to-bottom call names `call_names`, was
+    /// produced this campaign -- called by `Test::extend` for every test it creates (both
+    /// fresh tests and extensions of an existing one). See `ExtensionGraphNode`.
+    pub fn record_extension_graph_node(&mut self, test_id: usize, call_names: Vec<String>) {
+        self.extension_graph_nodes.push(ExtensionGraphNode {
+            test_id,
+            call_names,
+        });
+    }
+
+    /// Record that `edge.child_test_id` was produced by extending `edge.parent_test_id` --
+    /// called by `Test::extend` whenever it actually had a base test to extend (see
+    /// `ExtensionEdge`).
+    pub fn record_extension_edge(&mut self, edge: ExtensionEdge) {
+        self.extension_edges.push(edge);
+    }
+
+    /// Getter for every test node recorded this campaign (see `ExtensionGraphNode`), for
+    /// `crate::graphviz::write_extension_graph_dot`.
+    pub fn get_extension_graph_nodes(&self) -> &[ExtensionGraphNode] {
+        &self.extension_graph_nodes
+    }
+
+    /// Getter for every test-extension edge recorded this campaign (see `ExtensionEdge`),
+    /// for `crate::graphviz::write_extension_graph_dot`.
+    pub fn get_extension_edges(&self) -> &[ExtensionEdge] {
+        &self.extension_edges
+    }
+
     /// Generate a random function call, for module `mod_rep`.
     /// `ret_vals_pool` is the list of function return values in scope to be
     /// used in this call (with acc paths rep); `cb_arg_vals_pool` is the same for callback argument
@@ -406,32 +1251,58 @@ impl<'cxt> TestGenDB {
         // should we try and use mined data?
 
         // first, check mined data for nested extension
+        let use_mined_nesting_example = self.config.use_mined_nesting_example;
         if ext_type == ExtensionType::Nested
-            && (thread_rng().gen_range(0..=100) as f64) / 100. > USE_MINED_NESTING_EXAMPLE
+            && (self.rng_mut().gen_range(0..=100) as f64) / 100. > use_mined_nesting_example
         {
-            let possible_nested_exts = mined_seed_reps::get_rel_mined_data_nested_extensions(
+            // the lib this call is being generated for is always allowed (it's always
+            // `require`-able -- it's the module import already in scope), plus whichever
+            // other packages this campaign has separately registered as under test (see
+            // `pkgs_under_test`/`register_pkgs_under_test`, e.g. every library a multi-module
+            // `module_reps::ModuleRegistry` campaign registered) -- empty for an ordinary
+            // single-module campaign, so nesting there stays restricted to `lib_name` alone,
+            // same as before `pkgs_under_test` existed
+            let mut pkgs_under_test = self.pkgs_under_test.clone();
+            pkgs_under_test.insert(lib_name.clone());
+            let chosen_nested_ext = mined_seed_reps::choose_corresponding_mined_data(
                 ext_fct,
-                &lib_name,
-                &match self.lib_mined_data.get(&lib_name) {
-                    Some(lib_list) => lib_list.to_vec(),
-                    None => Vec::new(),
-                },
+                &pkgs_under_test,
+                &self.mined_nesting_index,
+                self.rng_mut(),
             );
-            if let Some(nested_ext) = possible_nested_exts.choose(&mut thread_rng()) {
+            if let Some(nested_ext) = chosen_nested_ext.as_ref() {
                 let ext_fct = ext_fct.unwrap(); // if we can nest, outer fct exists
                 let fct_name = nested_ext.fct_name.clone();
                 let fct_sig = nested_ext.sig.clone();
+                // the inner function may come from a different package than the one under
+                // test (e.g. `fs.realpath` nesting into `q.reject`) -- in that case, the
+                // access path is rooted in its own package rather than the module under
+                // test's, and the call's receiver is an inline `require(...)` of it, rather
+                // than the module-under-test variable `base_var_name` defaults to
+                let same_pkg = nested_ext.inner_pkg == lib_name;
                 let fct_acc_path_rep = AccessPathModuleCentred::FieldAccPath(
-                    Box::new(module_root_path),
+                    Box::new(if same_pkg {
+                        module_root_path
+                    } else {
+                        AccessPathModuleCentred::RootPath(nested_ext.inner_pkg.clone())
+                    }),
                     FieldNameType::StringField(fct_name.clone()),
                 );
+                let receiver = if same_pkg {
+                    None // the module import is the receiver by default
+                } else {
+                    Some(ArgVal::Variable(format!(
+                        "require(\"{}\")",
+                        nested_ext.inner_pkg
+                    )))
+                };
                 let mut ret_call = FunctionCall::new(
                     fct_name,
                     fct_sig,
                     None,                   /* position of arg in parent call of cb this is in */
                     None,                   /* parent call node ID */
                     Some(fct_acc_path_rep), /* access path rep of the call */
-                    None, /* receiver of the call -- it's the module import by default */
+                    receiver,
                 );
                 ret_call.init_args_with_random(
                     self,
@@ -441,23 +1312,33 @@ impl<'cxt> TestGenDB {
                     test_gen_mode,
                 )?;
                 let args = ret_call.sig.get_mut_args();
-                // let outer_sig = ext_fct.unwrap().sig;
-                // setup the dataflow
-                // THIS WILL CHANGE WHEN WE HAVE BETTER MINED DATA
-                // right now, the mined data assumes there is only one callback argument to the outer
-                // function, and that outer_pos is a valid argument position in this callback
-                if ext_fct.sig.get_callback_positions().len() == 1 {
-                    let outer_cb_args = ext_fct.sig.get_all_cb_args_vals(&ext_uniq_id);
-                    for (outer_pos, inner_pos) in nested_ext.outer_to_inner_dataflow.iter() {
-                        if *outer_pos < outer_cb_args.len() {
-                            args[*inner_pos] = FunctionArgument::new(
-                                ArgType::AnyType,
-                                Some(outer_cb_args[*outer_pos].clone()),
-                            );
+                // wire up the mined dataflow edges: `OuterCallbackParam` indexes into the
+                // flattened list of all the outer call's callback arguments' own parameters
+                // (see `FunctionSignature::get_all_cb_args_vals`), so this isn't restricted to
+                // an outer call with exactly one callback argument the way the dataflow used
+                // to be. `OuterReturn`/`OuterPromiseResolve`/`OuterPromiseReject` aren't wired
+                // up yet: there's no `ArgVal` tracking the outer call's own return value or
+                // promise settlement at the point a nested call is generated (only runtime
+                // instrumentation observes that), so those edges are mined and stored, but
+                // skipped here rather than guessed at.
+                let outer_cb_args = ext_fct.sig.get_all_cb_args_vals(&ext_uniq_id);
+                for edge in nested_ext.outer_to_inner_dataflow.iter() {
+                    let source_val = match edge.source {
+                        mined_seed_reps::DataflowSource::OuterArg(pos) => {
+                            ext_fct.sig.get_arg_list().get(pos).and_then(|a| a.get_arg_val().clone())
+                        }
+                        mined_seed_reps::DataflowSource::OuterCallbackParam(pos) => {
+                            outer_cb_args.get(pos).cloned()
                         }
+                        mined_seed_reps::DataflowSource::OuterReturn
+                        | mined_seed_reps::DataflowSource::OuterPromiseResolve
+                        | mined_seed_reps::DataflowSource::OuterPromiseReject => None,
+                    };
+                    if let Some(val) = source_val {
+                        args[edge.inner_arg_pos] = FunctionArgument::new(ArgType::AnyType, Some(val));
                     }
-                    return Ok(ret_call);
                 }
+                return Ok(ret_call);
             }
         }
         // not using mined nesting data...
@@ -493,69 +1374,75 @@ impl<'cxt> TestGenDB {
         );
 
         // let's first see if we should use mined API call data (we need the acc paths for this)
-        if (thread_rng().gen_range(0..=100) as f64) / 100. > USE_MINED_API_CALL_SIG {
-            let possible_calls = (match self.lib_mined_call_data.get(&lib_name) {
-                Some(lib_list) => lib_list.to_vec(),
-                None => Vec::new(),
-            })
-            .into_iter()
-            .filter(|mined_call| {
-                if let Some(base_path) = mined_call.get_acc_path().get_base_path() {
-                    return ap_receivers.contains_key(&base_path);
-                }
-                false
-            })
-            .collect::<Vec<MinedAPICall>>();
+        let use_mined_api_call_sig = self.config.use_mined_api_call_sig;
+        if (self.rng_mut().gen_range(0..=100) as f64) / 100. > use_mined_api_call_sig {
+            let possible_calls = self
+                .mined_api_call_index
+                .get_by_pkg(&lib_name)
+                .into_iter()
+                .filter(|mined_call| {
+                    if let Some(base_path) = mined_call.get_acc_path().get_base_path() {
+                        return ap_receivers.contains_key(&base_path);
+                    }
+                    false
+                })
+                .cloned()
+                .collect::<Vec<MinedAPICall>>();
             for call in possible_calls.into_iter() {
                 println!("BRO PLS: {:?}", call);
             }
         }
 
         // Build the weighted (by number of times previously tested -- if never tested,
-        // then the weight is 1) map of functions to test.
-        // We filter out the functions rooted in access paths that don't correspond to a
-        // variable (either previous return value or module import) that is in scope.
-        let lib_fcts_weights: Vec<(
-            (&AccessPathModuleCentred, &String, Vec<ArgVal>),
-            f64,
-            HashMap<Vec<ArgType>, f64>,
-        )> = self
+        // then the weight is 1) map of functions to test, grouped by receiver access path
+        // (built once per library, lazily, same as before).
+        let fct_weights_by_path = self
             .libs_fcts_weights
             .entry(lib_name.clone())
             .or_insert_with(|| {
+                let mut by_path: HashMap<
+                    AccessPathModuleCentred,
+                    Vec<(String, f64, HashMap<Vec<ArgType>, (f64, u32)>)>,
+                > = HashMap::new();
+                for ((fct_acc_path, fct_name), fct_obj) in mod_rep.get_fns().iter() {
+                    by_path.entry(fct_acc_path.clone()).or_insert_with(Vec::new).push((
+                        fct_name.clone(),
+                        1.0,
+                        fct_obj
+                            .get_sigs()
+                            .iter()
+                            .map(|sig| (sig.get_abstract_sig(), (1.0, 0)))
+                            .collect::<HashMap<Vec<ArgType>, (f64, u32)>>(),
+                    ));
+                }
+                by_path
+            });
+
+        // Only the access paths currently in scope (`ap_receivers`) can ever be chosen, so
+        // only those buckets need visiting -- via `NpmModule::fns_rooted_in`, this is time
+        // proportional to the number of in-scope receivers and the functions rooted at them,
+        // not every function the module has.
+        let lib_fcts_weights: Vec<(
+            (&AccessPathModuleCentred, &String, Vec<ArgVal>),
+            f64,
+            HashMap<Vec<ArgType>, (f64, u32)>,
+        )> = ap_receivers
+            .keys()
+            .flat_map(|receiver_acc_path| {
                 mod_rep
-                    .get_fns()
-                    .iter()
-                    .map(|((fct_acc_path, fct_name), fct_obj)| {
-                        (
-                            (fct_acc_path.clone(), fct_name.clone()),
-                            1.0,
-                            fct_obj
-                                .get_sigs()
-                                .iter()
-                                .map(|sig| (sig.get_abstract_sig(), 1.0))
-                                .collect::<HashMap<Vec<ArgType>, f64>>(),
-                        )
+                    .fns_rooted_in(receiver_acc_path)
+                    .filter_map(|(fct_acc_path, fct_name)| {
+                        fct_weights_by_path
+                            .get(fct_acc_path)?
+                            .iter()
+                            .find(|(name, _, _)| name == fct_name)
+                            .map(|(_, weight, fct_obj)| (fct_acc_path, fct_name, *weight, fct_obj))
                     })
-                    .collect()
             })
-            .iter()
-            .map(|((fct_acc_path, fct_name), weight, fct_obj)| {
-                // get the list of valid receivers with the acc path
-                // add this to the lib_fcts_weights. if it's empty change weight to zero
+            .map(|(fct_acc_path, fct_name, weight, fct_obj)| {
                 // note: the root import is always in ap_receivers
-                match ap_receivers.get(fct_acc_path) {
-                    Some(rec_list) => (
-                        (fct_acc_path, fct_name, rec_list.clone()),
-                        *weight,
-                        fct_obj.clone(),
-                    ),
-                    _ => (
-                        (fct_acc_path, fct_name, Vec::new()),
-                        f64::from(0), /* set weight to zero */
-                        fct_obj.clone(),
-                    ),
-                }
+                let rec_list = ap_receivers.get(fct_acc_path).cloned().unwrap_or_default();
+                ((fct_acc_path, fct_name, rec_list), weight, fct_obj.clone())
             })
             .collect();
 
@@ -563,10 +1450,10 @@ impl<'cxt> TestGenDB {
         // with non-zero weight
         let dist =
             WeightedIndex::new(lib_fcts_weights.iter().map(|(_, weight, _)| weight)).unwrap();
-        let rand_fct_index = dist.sample(&mut thread_rng());
+        let rand_fct_index = dist.sample(self.rng_mut());
         let ((fct_receiver_acc_path, fct_name, receivers), _, fct_sigs_weights) =
             lib_fcts_weights[rand_fct_index].clone();
-        let fct_call_receiver = receivers.choose(&mut rand::thread_rng());
+        let fct_call_receiver = receivers.choose(self.rng_mut());
         let fct_name = fct_name.clone();
         let fct_to_call = &mod_rep.get_fns()[&(fct_receiver_acc_path.clone(), fct_name.clone())];
         let fct_acc_path_rep = AccessPathModuleCentred::FieldAccPath(
@@ -577,21 +1464,23 @@ impl<'cxt> TestGenDB {
         let num_args = if let Some(api_args) = fct_to_call.get_num_api_args() {
             api_args
         } else {
-            thread_rng().gen_range(0..=DEFAULT_MAX_ARG_LENGTH)
+            self.rng_mut().gen_range(0..=DEFAULT_MAX_ARG_LENGTH)
         };
         let cb_position = if num_args == 0 {
             None
         } else {
-            Some(i32::try_from(thread_rng().gen_range(0..=(num_args * 2))).unwrap())
+            Some(i32::try_from(self.rng_mut().gen_range(0..=(num_args * 2))).unwrap())
             // x2 means there's a 50% chance of no callback (position doesnt correspond to valid arg pos)
         };
-        // choose a random signature -- either new, or an existing one (if theres some available)
+        // choose a random signature -- either new, or an existing one (if theres some available),
+        // excluding any that coverage feedback has retired (see `non_retired_sig_weights`)
         let random_sig = gen_new_sig_with_cb(
             fct_to_call.get_num_api_args(),
-            &fct_sigs_weights,
+            &Self::non_retired_sig_weights(&fct_sigs_weights),
             cb_position,
             self,
             test_gen_mode,
+            None,
         );
 
         // now update the weight of the function we just picked, and its signature
@@ -601,10 +1490,11 @@ impl<'cxt> TestGenDB {
             .unwrap()
             .get_mut(rand_fct_index)
         {
-            *cur_fct_weight *= RECHOOSE_LIB_FCT_WEIGHT_FACTOR;
-            *cur_fct_sig_weights
+            *cur_fct_weight *= self.config.rechoose_lib_fct_weight_factor;
+            cur_fct_sig_weights
                 .entry(random_sig.get_abstract_sig())
-                .or_insert(1.0) *= RECHOOSE_FCT_SIG_WEIGHT_FACTOR;
+                .or_insert((1.0, 0))
+                .0 *= self.config.rechoose_fct_sig_weight_factor;
         }
 
         let mut ret_call = FunctionCall::new(
@@ -628,23 +1518,34 @@ impl<'cxt> TestGenDB {
 
     /// Get a test that can be extended with the extension type specified.
     /// If there's no valid test that can be extended, return a new blank one.
+    /// Candidates are drawn with probability proportional to the coverage-derived weight
+    /// they were added with (see `add_extension_points_for_test`), so extension points from
+    /// tests that recently turned up new coverage are preferred over ones that didn't --
+    /// without ever fully excluding the latter, since weights are floored at
+    /// `MIN_EXTENSION_POINT_WEIGHT` rather than zeroed out.
     pub fn get_test_to_extend(
         &mut self,
         mod_rep: &'cxt NpmModule,
         ext_type: ExtensionType,
     ) -> (Test, Option<ExtensionPointID>, Option<String>) {
+        // collect owned copies first so we're not holding a borrow of `self` when
+        // we need to borrow `self.rng_mut()` below
         let rel_exts = self
             .possible_ext_points
             .iter()
-            .filter(|(et, _)| et == &ext_type)
-            .collect::<Vec<&(
-                ExtensionType,
-                (Test, Option<ExtensionPointID>, Option<String>),
-            )>>();
-        let rand_test = rel_exts.choose(&mut thread_rng());
+            .filter(|(et, _, _)| et == &ext_type)
+            .map(|(_, test_id, weight)| (test_id.clone(), *weight))
+            .collect::<Vec<((Test, Option<ExtensionPointID>, Option<String>), f64)>>();
+        let rand_test = if rel_exts.is_empty() {
+            None
+        } else {
+            let dist = WeightedIndex::new(rel_exts.iter().map(|(_, weight)| weight)).unwrap();
+            let rand_index = dist.sample(self.rng_mut());
+            Some(rel_exts[rand_index].0.clone())
+        };
         // if there's no valid test to extend yet, then we make a new blank one
         if let Some(test_with_id) = rand_test {
-            test_with_id.1.clone()
+            test_with_id
         } else {
             self.cur_test_index += 1;
             (
@@ -654,6 +1555,7 @@ impl<'cxt> TestGenDB {
                     self.test_dir_path.clone(),
                     self.test_file_prefix.clone(),
                     self.api_src_dir.clone(),
+                    self.sandbox_policy.clone(),
                 ),
                 None,
                 None,
@@ -670,51 +1572,284 @@ impl<'cxt> TestGenDB {
             self.test_dir_path.clone(),
             self.test_file_prefix.clone(),
             self.api_src_dir.clone(),
+            self.sandbox_policy.clone(),
         )
     }
 
     /// Set the current test index to `cur_test_index`; future tests will
     /// be generated with this index, which will then be incremented.
+    /// This is also the point at which a resumed campaign should persist `get_seed`
+    /// and `get_rng_draw_count`: together they checkpoint the RNG stream, so
+    /// `resume_from_checkpoint` can continue generation exactly where it left off.
     pub fn set_cur_test_index(&mut self, cur_test_index: usize) {
         self.cur_test_index = cur_test_index;
     }
 
+    /// Loosen (or tighten) the capability allow-list enforced around every test this
+    /// `TestGenDB` generates from here on (see `crate::sandbox::SandboxPolicy`); defaults
+    /// to deny-everything. Existing `Test`s already built keep whatever policy they were
+    /// constructed with -- this only affects `get_blank_test`/`get_test_to_extend` calls
+    /// made after this call.
+    pub fn set_sandbox_policy(&mut self, sandbox_policy: SandboxPolicy) {
+        self.sandbox_policy = sandbox_policy;
+    }
+
+    /// Record `pkgs` as also being under test this campaign (see `pkgs_under_test`), e.g.
+    /// every library a `module_reps::ModuleRegistry` has registered for a multi-module
+    /// campaign. Additive -- safe to call repeatedly (once per module registered, or once
+    /// with the whole registry) without losing packages recorded by an earlier call.
+    pub fn register_pkgs_under_test(&mut self, pkgs: impl IntoIterator<Item = String>) {
+        self.pkgs_under_test.extend(pkgs);
+    }
+
+    /// Getter for the current test index, e.g. to measure how many tests a `--rounds` of
+    /// generation produced.
+    pub fn get_cur_test_index(&self) -> usize {
+        self.cur_test_index
+    }
+
+    /// Nudge `nested_weight` based on whether `ext_point_results` found a callback to call:
+    /// raise it (more `Nested` extension) if any extension point reports
+    /// `CallbackCalledSync`/`CallbackCalledAsync`, lower it (more `Sequential` extension)
+    /// otherwise. No-op unless `ext_strategy` is `ExtensionStrategy::Weighted`.
+    fn update_ext_strategy_weights(
+        &mut self,
+        ext_point_results: &FxHashMap<ExtensionPointID, (FunctionCallResult, Option<String>)>,
+    ) {
+        if self.ext_strategy != ExtensionStrategy::Weighted {
+            return;
+        }
+        let found_callback = ext_point_results.values().any(|(res, _)| {
+            matches!(
+                res,
+                FunctionCallResult::SingleCallback(
+                    SingleCallCallbackTestResult::CallbackCalledSync
+                        | SingleCallCallbackTestResult::CallbackCalledAsync
+                )
+            ) || matches!(res, FunctionCallResult::MultiCallback(invocations) if invocations.iter().any(|cbi| cbi.count > 0))
+        });
+        self.nested_weight = if found_callback {
+            (self.nested_weight + NESTED_WEIGHT_ADAPTIVE_STEP).min(MAX_NESTED_WEIGHT)
+        } else {
+            (self.nested_weight - NESTED_WEIGHT_ADAPTIVE_STEP).max(MIN_NESTED_WEIGHT)
+        };
+    }
+
     /// Add an extension point to the list of valid extension points.
     /// Extension points are specified by their type `ext_type` and the
     /// test ID: a tuple of the test, an optional ID for the extension
     /// point this corresponds to, and an option of the position of a
     /// callback argument in this extension point (needed for nested extension).
+    /// `weight` is how strongly `get_test_to_extend` should favour this extension point
+    /// over others of the same type (see `add_extension_points_for_test`).
+    /// Like `set_cur_test_index`, this is part of the extension-point machinery whose
+    /// effect on later random choices (`get_test_to_extend` draws from this pool) is
+    /// only reproducible if the RNG checkpoint (`get_seed`/`get_rng_draw_count`) is
+    /// also persisted alongside it.
     fn add_extension_point(
         &mut self,
         ext_type: ExtensionType,
         test_id: (Test, Option<ExtensionPointID>, Option<String>),
+        weight: f64,
     ) {
-        self.possible_ext_points.push((ext_type, test_id));
+        self.possible_ext_points.push((ext_type, test_id, weight));
     }
 
     /// Add all valid extension points for test `test`, given the
     /// results at each of `test`'s extension points in `ext_point_results`.
+    /// `coverage`, if available, is the set of `(file, line)` pairs hit by each extension
+    /// point's call during this run (e.g. collected via `c8`/`nyc`); it's used to boost the
+    /// weight of functions/signatures that exercised previously-uncovered lines, and decay
+    /// the ones that didn't, via `apply_coverage_weight_feedback`.
+    /// `whole_test_coverage`, if available, is the whole-test V8 coverage collected by
+    /// `Test::execute` (see `Test::collect_v8_coverage`); it's used to derive the weight new
+    /// extension points from this test are added to `possible_ext_points` with, so
+    /// `get_test_to_extend` prioritizes extending tests that are still turning up new
+    /// coverage over ones that have gone dry (without ever fully excluding the latter).
     pub fn add_extension_points_for_test(
         &mut self,
         test: &Test,
-        ext_point_results: &HashMap<ExtensionPointID, (FunctionCallResult, Option<String>)>,
+        ext_point_results: &FxHashMap<ExtensionPointID, (FunctionCallResult, Option<String>)>,
+        coverage: Option<&CallCoverage>,
+        whole_test_coverage: Option<&[(String, u64)]>,
     ) {
-        // a test is only extensible if there are no execution errors
-        if ext_point_results
-            .values()
-            .any(|&(res, _)| res == FunctionCallResult::ExecutionError)
-        {
+        self.update_ext_strategy_weights(ext_point_results);
+        self.apply_arg_type_weight_feedback(test, ext_point_results);
+        if let Some(coverage) = coverage {
+            for (ext_id, hit_lines) in coverage.iter() {
+                self.apply_coverage_weight_feedback(test, *ext_id, hit_lines);
+            }
+        }
+        // a test is only extensible if it has no *hard* execution errors (a synchronous
+        // throw, or a timeout): a call whose only error was an unhandled rejection still
+        // returned a usable value, so it shouldn't disqualify the rest of the test from
+        // being extended -- individual extension points are still gated per-call by
+        // `can_be_extended` below
+        if ext_point_results.values().any(|(res, _)| {
+            matches!(res, FunctionCallResult::ExecutionError(info) if !info.is_rejection || info.timed_out)
+        }) {
             return;
         }
+        let ext_weight = match whole_test_coverage {
+            Some(whole_test_coverage) => {
+                let new_lines_count = whole_test_coverage
+                    .iter()
+                    .filter(|line| !self.seen_coverage_lines.contains(line))
+                    .count();
+                self.seen_coverage_lines
+                    .extend(whole_test_coverage.iter().cloned());
+                if new_lines_count > 0 {
+                    1.0 + COVERAGE_WEIGHT_BOOST_FACTOR * (new_lines_count as f64)
+                } else {
+                    MIN_EXTENSION_POINT_WEIGHT
+                }
+            }
+            None => 1.0,
+        };
         // for each of the extension points and their results, check if they
         // can be extended with each type of extension.
         for (ext_id, (res, cb_arg_pos)) in ext_point_results.iter() {
+            // a `MultiCallback` result doesn't have a single "the" callback argument
+            // position to nest into (unlike `cb_arg_pos`, which only ever names one) --
+            // every invoked callback argument of the call is its own nesting candidate
+            let multi_cb_arg_positions: Vec<Option<String>> = match res {
+                FunctionCallResult::MultiCallback(invocations) => invocations
+                    .iter()
+                    .filter(|cbi| cbi.count > 0)
+                    .map(|cbi| Some(cbi.arg_pos.to_string()))
+                    .collect(),
+                _ => vec![cb_arg_pos.clone()],
+            };
             for ext_type in ExtensionType::iter() {
                 if res.can_be_extended(ext_type) {
-                    self.add_extension_point(
-                        ext_type,
-                        (test.clone(), Some(*ext_id), cb_arg_pos.clone()),
-                    );
+                    for arg_pos in &multi_cb_arg_positions {
+                        self.add_extension_point(
+                            ext_type,
+                            (test.clone(), Some(*ext_id), arg_pos.clone()),
+                            ext_weight,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Signatures that `non_retired_sig_weights` would exclude from consideration: those
+    /// whose consecutive stale-coverage-round count has reached `MAX_STALE_COVERAGE_ATTEMPTS`.
+    /// Kept around (rather than deleted outright) so a later snapshot/resume still has their
+    /// weight and round count on record, in case `MAX_STALE_COVERAGE_ATTEMPTS` changes.
+    fn non_retired_sig_weights(
+        sig_weights: &HashMap<Vec<ArgType>, (f64, u32)>,
+    ) -> HashMap<Vec<ArgType>, f64> {
+        sig_weights
+            .iter()
+            .filter(|(_, (_, stale_rounds))| *stale_rounds < MAX_STALE_COVERAGE_ATTEMPTS)
+            .map(|(sig, (weight, _))| (sig.clone(), *weight))
+            .collect()
+    }
+
+    /// Update the weight of the function/signature that produced the call at `ext_id`,
+    /// based on whether it hit any lines not already in `seen_coverage_lines`: new coverage
+    /// boosts the weight by `1.0 + COVERAGE_WEIGHT_BOOST_FACTOR * new_lines` and resets its
+    /// stale-round count, while no new coverage decays the weight by the existing
+    /// `RECHOOSE_*_WEIGHT_FACTOR` and bumps the stale-round count -- once that count reaches
+    /// `MAX_STALE_COVERAGE_ATTEMPTS`, `non_retired_sig_weights` stops offering the signature
+    /// to `gen_new_sig_with_cb` at all. This turns the `WeightedIndex` sampler in
+    /// `gen_random_call` into a feedback-directed search that concentrates effort on fruitful
+    /// API/signature combinations, rather than blindly decaying (or endlessly retrying)
+    /// whatever it picks.
+    fn apply_coverage_weight_feedback(
+        &mut self,
+        test: &Test,
+        ext_id: ExtensionPointID,
+        hit_lines: &[(String, u64)],
+    ) {
+        let new_lines = hit_lines
+            .iter()
+            .filter(|line| !self.seen_coverage_lines.contains(line))
+            .count();
+        self.seen_coverage_lines.extend(hit_lines.iter().cloned());
+
+        let call = match test.get_fct_call_from_id(&ext_id) {
+            Some(call) => call,
+            None => return,
+        };
+        let fct_acc_path = match call.get_acc_path().as_ref() {
+            Some(fct_acc_path) => fct_acc_path,
+            None => return,
+        };
+        let fct_receiver_acc_path = match fct_acc_path.get_base_path() {
+            Some(fct_receiver_acc_path) => fct_receiver_acc_path,
+            None => return,
+        };
+        let lib_name = fct_receiver_acc_path.get_root_lib_name();
+        let fct_name = call.get_name().to_owned();
+        let abstract_sig = call.sig.get_abstract_sig();
+
+        if let Some(fct_weights_by_path) = self.libs_fcts_weights.get_mut(&lib_name) {
+            if let Some(entries) = fct_weights_by_path.get_mut(fct_receiver_acc_path) {
+                if let Some((_, cur_fct_weight, cur_fct_sig_weights)) =
+                    entries.iter_mut().find(|(name, _, _)| name == &fct_name)
+                {
+                    let sig_entry = cur_fct_sig_weights.entry(abstract_sig).or_insert((1.0, 0));
+                    if new_lines > 0 {
+                        let boost = 1.0 + COVERAGE_WEIGHT_BOOST_FACTOR * (new_lines as f64);
+                        *cur_fct_weight *= boost;
+                        sig_entry.0 *= boost;
+                        sig_entry.1 = 0;
+                    } else {
+                        *cur_fct_weight *= self.config.rechoose_lib_fct_weight_factor;
+                        sig_entry.0 *= self.config.rechoose_fct_sig_weight_factor;
+                        sig_entry.1 += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Apply an access-path rewrite produced by `NpmModule::canonicalize_paths` to this
+    /// generator's own per-path weight/coverage state, so an access path that got collapsed
+    /// into another on the `NpmModule` side doesn't silently orphan the history
+    /// `apply_coverage_weight_feedback` built up for it under the old key. When both the old
+    /// and new keys already have an entry, they're merged by taking the max weight (and, per
+    /// signature, the max weight / min stale-round count) rather than picking one side,
+    /// mirroring how `NpmModule::canonicalize_paths` unions `sigs` instead of dropping either.
+    ///
+    /// Already-generated `Test`s keep referencing whatever access path they were generated
+    /// with -- those are a historical record of what actually ran, not something retroactively
+    /// rewritten.
+    pub fn apply_path_canonicalization(
+        &mut self,
+        lib_name: &str,
+        rewrites: &HashMap<AccessPathModuleCentred, AccessPathModuleCentred>,
+    ) {
+        if rewrites.is_empty() {
+            return;
+        }
+        let Some(fct_weights_by_path) = self.libs_fcts_weights.get_mut(lib_name) else {
+            return;
+        };
+        let old_by_path = std::mem::take(fct_weights_by_path);
+        for (acc_path, entries) in old_by_path {
+            let canonical_path = rewrites.get(&acc_path).cloned().unwrap_or(acc_path);
+            let dest = fct_weights_by_path
+                .entry(canonical_path)
+                .or_insert_with(Vec::new);
+            for (name, weight, sig_weights) in entries {
+                if let Some(existing) = dest.iter_mut().find(|(n, _, _)| *n == name) {
+                    existing.1 = existing.1.max(weight);
+                    for (abstract_sig, (sig_weight, stale_rounds)) in sig_weights {
+                        existing
+                            .2
+                            .entry(abstract_sig)
+                            .and_modify(|(existing_weight, existing_stale_rounds)| {
+                                *existing_weight = existing_weight.max(sig_weight);
+                                *existing_stale_rounds = (*existing_stale_rounds).min(stale_rounds);
+                            })
+                            .or_insert((sig_weight, stale_rounds));
+                    }
+                } else {
+                    dest.push((name, weight, sig_weights));
                 }
             }
         }
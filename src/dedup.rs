@@ -0,0 +1,242 @@
+//! Post-generation pass over a finished test suite (invoked from
+//! `testgen::write_meta_test`, once per campaign, after every individual test file has
+//! been written out) that hoists verbatim-duplicated boilerplate into a single shared,
+//! `require`-able helper module (`TEST_HELPERS_FILE_NAME`) instead of leaving a copy
+//! inlined in every test file.
+//!
+//! This is deliberately *not* a general JS-aware content-addressing pass over arbitrary
+//! code blocks. Nearly everything `code_gen` emits embeds some per-test/per-call unique ID
+//! (`cur_call_uniq_id`, `ret_val_basename`, ...), so it's never actually byte-identical
+//! across two different calls -- and "hoisting" a block that references free variables
+//! from its surrounding scope, or declares a binding other code in the file relies on,
+//! would silently change behavior rather than preserve it. Two kinds of snippet *are*
+//! genuinely reproduced verbatim by construction, and self-contained enough to safely move:
+//! -- the module setup line (`get_js_for_module_import`): identical across every test in a
+//!    campaign (same module, same bound variable name), and introduces exactly one binding,
+//!    which the shared module can just re-export under the same name;
+//! -- the id-less `basic_callback()` body: a fixed `&'static str` with no per-test
+//!    variation at all (unlike `basic_callback_with_id`, which embeds a unique ID and so is
+//!    never actually duplicated).
+//! Everything else is left inlined; this pass hashes lines across the whole suite (as
+//! requested) to find recurring instances of exactly these two shapes, rather than
+//! hardcoding that a particular test's setup line is "the" one to hoist.
+
+use crate::code_gen;
+use crate::errors::DFError;
+
+use std::collections::HashMap;
+
+/// Name of the generated helper module, written alongside the per-test files.
+pub const TEST_HELPERS_FILE_NAME: &str = "test_helpers.js";
+
+/// Below this many occurrences across the suite, hoisting a snippet out costs more (an
+/// extra `require` line per occurrence, an extra file to read) than the duplication it
+/// would remove saves.
+const MIN_DUPLICATE_COUNT: usize = 2;
+
+/// Given a single line of generated setup code, return the variable name it binds if (and
+/// only if) it's one of the two shapes `get_js_for_module_import` emits for `Cjs`/`Esm`
+/// modules (`let <var> = require("...");` / `let <var> = await import("...");`) -- the only
+/// shapes whose single binding can be safely re-exported verbatim. `EsmStatic`'s
+/// `import * as <var> from "...";` form isn't matched here, since top-level `import`
+/// declarations can't be re-expressed as a `require()` of a generated helper.
+fn module_import_binding(line: &str) -> Option<&str> {
+    let rest = line.trim().strip_prefix("let ")?;
+    let (var, rest) = rest.split_once(" = ")?;
+    if rest.starts_with("require(\"") || rest.starts_with("await import(\"") {
+        Some(var)
+    } else {
+        None
+    }
+}
+
+/// Scan every file in `test_files`, and hoist out:
+/// -- whichever single module-import line (see `module_import_binding`) recurs, verbatim,
+///    in at least `MIN_DUPLICATE_COUNT` of them;
+/// -- the id-less `basic_callback()` body, if it appears at all (it's always identical, so
+///    a single extra occurrence already justifies sharing it).
+/// Each hoisted snippet is written once to `<test_dir>/test_helpers.js`, and every matching
+/// line in every test file is rewritten in place to reference it. Returns `Ok(None)` (and
+/// touches nothing) if nothing in the suite was duplicated enough to be worth hoisting --
+/// the common case for a small or one-off run.
+pub fn dedup_test_suite(test_dir: &str, test_files: &[String]) -> Result<Option<String>, DFError> {
+    let mut contents: Vec<(String, String)> = Vec::with_capacity(test_files.len());
+    for path in test_files {
+        if let Ok(src) = std::fs::read_to_string(path) {
+            contents.push((path.clone(), src));
+        }
+    }
+
+    // content-address every distinct line that appears in the suite, counting at most once
+    // per file (so a snippet repeated several times within one file doesn't look like it
+    // was shared across the suite)
+    let mut line_counts: HashMap<&str, usize> = HashMap::new();
+    for (_, src) in &contents {
+        let mut seen_this_file: std::collections::HashSet<&str> = std::collections::HashSet::new();
+        for line in src.lines() {
+            if seen_this_file.insert(line) {
+                *line_counts.entry(line).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let basic_cb_body = code_gen::basic_callback();
+    let hoist_basic_cb = line_counts.get(basic_cb_body).copied().unwrap_or(0) >= 1
+        && contents
+            .iter()
+            .filter(|(_, src)| src.contains(basic_cb_body))
+            .count()
+            >= MIN_DUPLICATE_COUNT;
+
+    let hoist_import = line_counts
+        .iter()
+        .find(|(line, count)| **count >= MIN_DUPLICATE_COUNT && module_import_binding(line).is_some())
+        .map(|(line, _)| line.to_string());
+
+    if !hoist_basic_cb && hoist_import.is_none() {
+        return Ok(None);
+    }
+
+    let mut helper_exports = Vec::new();
+    if hoist_basic_cb {
+        helper_exports.push(
+            [
+                "function basicCallback() {",
+                "\treturn function() { console.log({\"callback_exec\": true}); };",
+                "}",
+                "module.exports.basicCallback = basicCallback;",
+            ]
+            .join("\n"),
+        );
+    }
+    if let Some(ref import_line) = hoist_import {
+        let var = module_import_binding(import_line).unwrap();
+        helper_exports.push(format!("{}\nmodule.exports.{} = {};", import_line, var, var));
+    }
+
+    let helpers_path = [test_dir, "/", TEST_HELPERS_FILE_NAME].concat();
+    if std::fs::write(&helpers_path, helper_exports.join("\n\n") + "\n").is_err() {
+        return Err(DFError::WritingTestError(helpers_path));
+    }
+
+    for (path, src) in &contents {
+        let mut rewritten = src.clone();
+        let mut changed = false;
+        if hoist_basic_cb && rewritten.contains(basic_cb_body) {
+            rewritten = rewritten.replace(
+                basic_cb_body,
+                &format!(
+                    "let cb = require(\"./{}\").basicCallback();",
+                    TEST_HELPERS_FILE_NAME
+                ),
+            );
+            changed = true;
+        }
+        if let Some(ref import_line) = hoist_import {
+            if rewritten.contains(import_line.as_str()) {
+                let var = module_import_binding(import_line).unwrap();
+                rewritten = rewritten.replace(
+                    import_line.as_str(),
+                    &format!(
+                        "let {} = require(\"./{}\").{};",
+                        var, TEST_HELPERS_FILE_NAME, var
+                    ),
+                );
+                changed = true;
+            }
+        }
+        if changed && std::fs::write(path, rewritten).is_err() {
+            return Err(DFError::WritingTestError(path.clone()));
+        }
+    }
+
+    Ok(Some(helpers_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run `src` as a standalone node script (from `dir`, so a relative `require` resolves
+    /// against it) and return its stdout, for comparing the behavior of a test file before
+    /// and after hoisting -- hoisting is only actually safe if it doesn't change this output.
+    fn run_node(dir: &std::path::Path, src: &str) -> std::process::Output {
+        let script_path = dir.join("run_me.js");
+        std::fs::write(&script_path, src).unwrap();
+        std::process::Command::new("node")
+            .arg(&script_path)
+            .current_dir(dir)
+            .output()
+            .expect("node must be on PATH to run this test")
+    }
+
+    /// Hoisting the id-less `basic_callback()` body out to `test_helpers.js` must not change
+    /// what running the test actually does: calling the hoisted `cb` has to print the exact
+    /// same thing the inlined callback did.
+    #[test]
+    fn basic_callback_hoist_is_behaviorally_equivalent() {
+        let dir = std::env::temp_dir().join(format!("nessie_test_dedup_cb_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let body = format!("{}\ncb();\n", code_gen::basic_callback());
+        let file1 = dir.join("test0.js");
+        let file2 = dir.join("test1.js");
+        std::fs::write(&file1, &body).unwrap();
+        std::fs::write(&file2, &body).unwrap();
+
+        let inlined_output = run_node(&dir, &body);
+
+        let test_files = vec![
+            file1.to_str().unwrap().to_string(),
+            file2.to_str().unwrap().to_string(),
+        ];
+        let hoisted_path = dedup_test_suite(dir.to_str().unwrap(), &test_files)
+            .unwrap()
+            .expect("two identical callback bodies should be hoisted");
+        assert_eq!(hoisted_path, dir.join(TEST_HELPERS_FILE_NAME).to_str().unwrap());
+
+        let rewritten = std::fs::read_to_string(&file1).unwrap();
+        assert!(rewritten.contains(TEST_HELPERS_FILE_NAME));
+        let rewritten_output = run_node(&dir, &rewritten);
+
+        assert_eq!(inlined_output.stdout, rewritten_output.stdout);
+        assert!(rewritten_output.status.success());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    /// Hoisting a module-import binding out to `test_helpers.js` must not change what the
+    /// rest of the test sees when it uses the bound variable -- requiring it back out of the
+    /// helper has to yield the same thing `require`ing it directly did.
+    #[test]
+    fn module_import_hoist_is_behaviorally_equivalent() {
+        let dir = std::env::temp_dir().join(format!("nessie_test_dedup_import_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let body = "let lib = require(\"os\");\nconsole.log(typeof lib.platform);\n".to_string();
+        let file1 = dir.join("test0.js");
+        let file2 = dir.join("test1.js");
+        std::fs::write(&file1, &body).unwrap();
+        std::fs::write(&file2, &body).unwrap();
+
+        let inlined_output = run_node(&dir, &body);
+
+        let test_files = vec![
+            file1.to_str().unwrap().to_string(),
+            file2.to_str().unwrap().to_string(),
+        ];
+        let hoisted_path = dedup_test_suite(dir.to_str().unwrap(), &test_files)
+            .unwrap()
+            .expect("two identical import lines should be hoisted");
+        assert_eq!(hoisted_path, dir.join(TEST_HELPERS_FILE_NAME).to_str().unwrap());
+
+        let rewritten = std::fs::read_to_string(&file1).unwrap();
+        assert!(rewritten.contains(TEST_HELPERS_FILE_NAME));
+        let rewritten_output = run_node(&dir, &rewritten);
+
+        assert_eq!(inlined_output.stdout, rewritten_output.stdout);
+        assert!(rewritten_output.status.success());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,54 @@
+//! Pluggable code-emitter backend for extending a test at an `ExtensionPoint`. `dfs_print`
+//! (see `crate::code_gen`) already knows *where* in the call tree a call goes -- as a
+//! sequential sibling, or nested in a callback body -- from the tree shape itself; what it
+//! used to hard-code was *how* that placement gets turned into text. `Emitter` pulls that
+//! decision out into a trait dispatched on `ExtensionPoint::get_ext_type`, so a backend that
+//! emits something other than plain JS (TypeScript with type annotations, ESM `import`
+//! style, or a pure-AST/JSON backend for a non-text consumer) can be swapped in without
+//! touching `Test::extend`'s tree-manipulation logic at all -- only the two call sites in
+//! `crate::code_gen` that currently construct a `JsEmitter` would need to construct
+//! something else instead.
+
+use crate::tests::{ExtensionPoint, ExtensionType};
+
+/// Turns a single call's already-rendered code (`call_code`, produced by
+/// `get_function_call_code`) into the text spliced into the growing test body, depending
+/// on how this call extends the test (see `ExtensionType`).
+pub trait Emitter {
+    /// Emit a call that's a sequential sibling of the call it extends -- i.e. it runs
+    /// after, not inside, the extension point.
+    fn emit_sequential(&self, point: &ExtensionPoint, call_code: &str, out: &mut String);
+
+    /// Emit a call that's nested inside the callback body of the call it extends.
+    fn emit_nested(&self, point: &ExtensionPoint, call_code: &str, out: &mut String);
+
+    /// Dispatch to `emit_sequential`/`emit_nested` based on `point.get_ext_type()`,
+    /// mirroring how `Test::extend` itself matches on `ext_type` to decide whether to
+    /// append a sibling or a child node to the call tree.
+    fn emit_extension(&self, point: &ExtensionPoint, call_code: &str, out: &mut String) {
+        match point.get_ext_type() {
+            ExtensionType::Sequential => self.emit_sequential(point, call_code, out),
+            ExtensionType::Nested => self.emit_nested(point, call_code, out),
+        }
+    }
+}
+
+/// The default backend: plain JavaScript, matching the text `dfs_print` always produced
+/// before this trait existed -- `call_code` itself already carries any
+/// indentation/`await`/semicolon needed for its position (see `get_function_call_code`).
+/// Sequential siblings are appended back-to-back with no separator (each one's own
+/// leading `let ret_val_... ;` line is enough of a boundary); a nested call gets a
+/// trailing newline, since it's followed by the rest of its parent callback's body
+/// (the `console.log`/closing-brace lines `Callback::get_string_rep` appends next).
+pub struct JsEmitter;
+
+impl Emitter for JsEmitter {
+    fn emit_sequential(&self, _point: &ExtensionPoint, call_code: &str, out: &mut String) {
+        out.push_str(call_code);
+    }
+
+    fn emit_nested(&self, _point: &ExtensionPoint, call_code: &str, out: &mut String) {
+        out.push_str(call_code);
+        out.push('\n');
+    }
+}
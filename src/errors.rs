@@ -16,6 +16,12 @@ pub enum DFError {
     WritingTestError(String),
     /// error deleting test file (with file path)
     DeletingTestError(String),
+    /// error writing a generator weight-model snapshot (with file path)
+    WritingSnapshotError(String),
+    /// error reading a generator weight-model snapshot (with file path)
+    ReadingSnapshotError(String),
+    /// error writing a structured test-suite report (with file path; see `crate::reporters`)
+    ReportWritingError(String),
     /// error running test (could be a timeout)
     TestRunningError,
     /// error parsing test output
@@ -24,6 +30,45 @@ pub enum DFError {
     InvalidTestExtensionOption,
     /// error during test generation
     TestGenError(TestGenError),
+    /// error loading a `--config` file (malformed TOML/JSON, unreadable, or an `%include`
+    /// cycle) -- with a human-readable message (see `crate::config`)
+    ConfigFileError(String),
+    /// error writing a discovery JSON file (with file path; see
+    /// `module_reps::NpmModule::write_discovery_file`)
+    WritingDiscoveryFileError(String),
+    /// error reading a discovery JSON file (with file path; see
+    /// `module_reps::NpmModule::from_discovery_file`)
+    ReadingDiscoveryFileError(String),
+    /// a discovery file was read successfully but its `version` field doesn't match the
+    /// schema this build of nessie writes/expects (with the file's actual version)
+    UnsupportedDiscoveryFileVersion(u32),
+    /// a mined-data file's `spec_version` has a major version older than this build of the
+    /// generator requires (with the file's version, then the required version -- both
+    /// formatted as `"major.minor.patch"`; see `mined_seed_reps::SpecVersion`)
+    IncompatibleMinedDataVersion(String, String),
+    /// error writing a mined-data integrity sidecar (with the sidecar file path; see
+    /// `crate::integrity::write_sidecar`)
+    WritingIntegritySidecarError(String),
+    /// error reading a mined-data integrity sidecar (with the sidecar file path; see
+    /// `crate::integrity::verify_sidecar`)
+    ReadingIntegritySidecarError(String),
+    /// a mined-data file's recomputed canonical digest (or Ed25519 signature, if one was
+    /// required) didn't match its integrity sidecar (with the data file path; see
+    /// `crate::integrity::verify_sidecar`)
+    MinedDataIntegrityMismatch(String),
+    /// error parsing or evaluating an extension-point filter expression (with a
+    /// human-readable message; see `crate::ext_filter`)
+    ExtensionFilterError(String),
+    /// error writing a persisted corpus of interesting test cases (with file path; see
+    /// `decisions::TestGenDB::record_corpus_case`)
+    WritingCorpusError(String),
+    /// error reading a persisted corpus of interesting test cases (with file path; see
+    /// `decisions::TestGenDB::load_corpus`)
+    ReadingCorpusError(String),
+    /// a `[generation]` table in a `--config` file had a probability or weight-decay factor
+    /// outside `[0, 1]` (with a human-readable message naming the offending field; see
+    /// `consts::Config::validate`)
+    InvalidConfigValue(String),
 }
 
 /// Errors in the test generation.
@@ -55,14 +100,78 @@ pub enum SingleCallCallbackTestResult {
     NoCallbackCalled,
 }
 
-/// Possible results of one function execution.
+/// Whether (and how) the *first* invocation of one callback argument, in a call whose
+/// signature has more than one callback argument, happened relative to the function
+/// call's own completion. Mirrors `SingleCallCallbackTestResult`, but tracked per callback
+/// argument position rather than once for the whole call (see `CallbackInvocation`).
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize, Serialize, Hash)]
+pub enum CallbackInvocationTiming {
+    /// this callback argument was never invoked
+    NotCalled,
+    /// invoked at least once, and the call itself finished executing first
+    CalledAsync,
+    /// invoked at least once, before the call itself finished executing
+    CalledSync,
+}
+
+/// Invocation outcome for one callback argument position, as part of a `MultiCallback`
+/// result for a call whose signature has more than one callback argument.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize, Serialize, Hash)]
+pub struct CallbackInvocation {
+    /// position of this callback in the function's argument list
+    pub arg_pos: usize,
+    /// number of times this callback argument was invoked
+    pub count: u32,
+    /// timing of this callback's first invocation, relative to the call's completion
+    pub timing: CallbackInvocationTiming,
+    /// this callback's rank among all the callback arguments of the same call that were
+    /// invoked at least once, ordered by when each one was *first* invoked (`0` invoked
+    /// first); `None` if this callback was never invoked
+    pub order_rank: Option<usize>,
+}
+
+/// Structured classification of a function call's `FunctionCallResult::ExecutionError`,
+/// parsed from the `error_<id>` instrumentation event (see `code_gen::get_function_call_code`)
+/// instead of collapsing every failure into one opaque variant. `class_name`/`message`/`stack`
+/// mirror the thrown/rejected JS value's `.constructor.name`/`.message`/`.stack`, when it has
+/// them (e.g. a bare `throw "oops"` leaves all three `None`).
+#[derive(Debug, PartialEq, Eq, Clone, Default, Deserialize, Serialize, Hash)]
+pub struct ErrorInfo {
+    /// the thrown/rejected value's error class name, e.g. `"TypeError"`
+    pub class_name: Option<String>,
+    /// the thrown/rejected value's message, if it has one
+    pub message: Option<String>,
+    /// the thrown/rejected value's stack trace, if it has one
+    pub stack: Option<String>,
+    /// best-effort guess at which argument position `message` is complaining about,
+    /// e.g. `"The \"data\" argument must be..."` or `"argument 2 is invalid"` -- parsed out
+    /// by `nessieSerializeError` (see `code_gen::get_instrumented_header`) so the generator
+    /// can bias away from that position's current argument type on retry. `None` when no
+    /// such pattern is found in the message (most errors).
+    pub arg_hint: Option<usize>,
+    /// `true` if this came from an unhandled promise rejection (the call itself returned
+    /// normally; the error surfaced later), `false` if it was a synchronous throw caught
+    /// at the call site
+    pub is_rejection: bool,
+    /// `true` if the call's test process never finished within `TEST_TIMEOUT_SECONDS` and
+    /// was killed -- in this case `class_name`/`message`/`stack`/`arg_hint` are always
+    /// `None`, since nothing was caught to report on
+    pub timed_out: bool,
+}
+
+/// Possible results of one function execution. `MultiCallback`'s per-position outcomes and
+/// relative invocation order (see `CallbackInvocation::order_rank`) already cover
+/// multi-callback signatures end to end, so there's nothing further to do here for a
+/// signature with more than one callback argument.
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize, Hash)]
 pub enum FunctionCallResult {
     /// result WRT whether a callback argument was executed when this function is called
     SingleCallback(SingleCallCallbackTestResult),
-    /// there is an error in the execution of the function
-    ExecutionError,
-    // TODO MultiCallback
+    /// result for a call whose signature has more than one callback argument: one
+    /// `CallbackInvocation` per callback argument position
+    MultiCallback(Vec<CallbackInvocation>),
+    /// there is an error in the execution of the function, classified by `ErrorInfo`
+    ExecutionError(ErrorInfo),
 }
 
 impl FunctionCallResult {
@@ -70,15 +179,30 @@ impl FunctionCallResult {
     /// used as an extension point of type `ExtensionType`.
     pub fn can_be_extended(&self, ext_type: ExtensionType) -> bool {
         match (self, ext_type) {
-            // can never extend if there's an execution error
-            (Self::ExecutionError, _) => false,
+            // a synchronous throw, or a timeout, means the call never produced a usable
+            // return value (and definitely never ran a callback) -- can't extend at all
+            (Self::ExecutionError(info), _) if !info.is_rejection || info.timed_out => false,
+            // an unhandled rejection means the call itself returned fine -- only the
+            // promise it returned rejected later -- so its return value can still seed a
+            // sequential call, but there's no evidence any callback fired, so it can't be
+            // nested into
+            (Self::ExecutionError(_), ExtensionType::Nested) => false,
+            (Self::ExecutionError(_), ExtensionType::Sequential) => true,
             // can't nest if there's no callback
             (
                 Self::SingleCallback(SingleCallCallbackTestResult::NoCallbackCalled),
                 ExtensionType::Nested,
             ) => false,
+            // a multi-callback call can still be nested into as long as at least one of
+            // its callback arguments was invoked -- the specific position(s) that can be
+            // nested into are derived from `invocations` by the caller (see
+            // `TestGenDB::add_extension_points_for_test`); a callback argument that was
+            // never invoked simply contributes no nesting candidate of its own
+            (Self::MultiCallback(invocations), ExtensionType::Nested) => {
+                invocations.iter().any(|cbi| cbi.count > 0)
+            }
             // no-callback and sequential: true
-            // sync or async callback and either nested or sequential: true
+            // sync or async callback (single or multi) and either nested or sequential: true
             (_, _) => true,
         }
     }
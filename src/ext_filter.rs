@@ -0,0 +1,303 @@
+//! A small query language for narrowing the candidate pool of `ExtensionPoint`s before
+//! random selection, e.g. `ext_type = nested AND module = "fs"` or `NOT ext_type =
+//! sequential`. `parse_filter` turns the source text into a `FilterExpr` AST via a tiny
+//! recursive-descent parser (tokens: identifiers/string literals as operands, `=` for
+//! comparison, `AND`/`OR`/`NOT` with parentheses for boolean structure); `compile_filter`
+//! turns that AST into a predicate closure. Unknown fields or comparison values that don't
+//! match any known variant are a `DFError::ExtensionFilterError` at compile time, rather
+//! than a predicate that silently matches nothing.
+//!
+//! `ExtensionPoint` itself only carries a `node_id` and an `ext_type` -- it doesn't carry
+//! the module name or `AccessPathModuleCentred` a candidate sits on, since those depend on
+//! the `Test`/`NpmModule` the point was found in, not the point alone. So a compiled
+//! predicate here takes an `ExtPointEvalContext` alongside the `ExtensionPoint`, resolved
+//! once per candidate by the caller (see `ExtPointEvalContext::resolve`), rather than the
+//! point on its own.
+
+use crate::errors::DFError;
+use crate::module_reps::{AccessPathModuleCentred, NpmModule};
+use crate::tests::{ExtensionPoint, ExtensionType, Test};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Eq,
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, DFError> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = input.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            _ if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            '=' => {
+                tokens.push(Token::Eq);
+                i += 1;
+            }
+            '"' => {
+                let start = i + 1;
+                let mut end = start;
+                while end < chars.len() && chars[end] != '"' {
+                    end += 1;
+                }
+                if end >= chars.len() {
+                    return Err(DFError::ExtensionFilterError(
+                        "unterminated string literal".to_string(),
+                    ));
+                }
+                tokens.push(Token::Str(chars[start..end].iter().collect()));
+                i = end + 1;
+            }
+            _ if c.is_alphanumeric() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                tokens.push(match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Ident(word),
+                });
+            }
+            _ => {
+                return Err(DFError::ExtensionFilterError(format!(
+                    "unexpected character {:?} in filter expression",
+                    c
+                )))
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// AST for a parsed extension-point filter expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterExpr {
+    /// `field = value`, e.g. `ext_type = nested` or `module = "fs"`.
+    Compare(String, String),
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+    Not(Box<FilterExpr>),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let tok = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        tok
+    }
+
+    /// `or_expr := and_expr (OR and_expr)*`
+    fn parse_or(&mut self) -> Result<FilterExpr, DFError> {
+        let mut lhs = self.parse_and()?;
+        while matches!(self.peek(), Some(Token::Or)) {
+            self.advance();
+            let rhs = self.parse_and()?;
+            lhs = FilterExpr::Or(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    /// `and_expr := not_expr (AND not_expr)*`
+    fn parse_and(&mut self) -> Result<FilterExpr, DFError> {
+        let mut lhs = self.parse_not()?;
+        while matches!(self.peek(), Some(Token::And)) {
+            self.advance();
+            let rhs = self.parse_not()?;
+            lhs = FilterExpr::And(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    /// `not_expr := NOT not_expr | atom`
+    fn parse_not(&mut self) -> Result<FilterExpr, DFError> {
+        if matches!(self.peek(), Some(Token::Not)) {
+            self.advance();
+            let inner = self.parse_not()?;
+            return Ok(FilterExpr::Not(Box::new(inner)));
+        }
+        self.parse_atom()
+    }
+
+    /// `atom := "(" or_expr ")" | compare`
+    fn parse_atom(&mut self) -> Result<FilterExpr, DFError> {
+        match self.advance() {
+            Some(Token::LParen) => {
+                let inner = self.parse_or()?;
+                match self.advance() {
+                    Some(Token::RParen) => Ok(inner),
+                    _ => Err(DFError::ExtensionFilterError(
+                        "expected closing ')'".to_string(),
+                    )),
+                }
+            }
+            Some(Token::Ident(field)) => {
+                match self.advance() {
+                    Some(Token::Eq) => {}
+                    _ => {
+                        return Err(DFError::ExtensionFilterError(format!(
+                            "expected '=' after field {:?}",
+                            field
+                        )))
+                    }
+                }
+                match self.advance() {
+                    Some(Token::Ident(value)) | Some(Token::Str(value)) => {
+                        Ok(FilterExpr::Compare(field, value))
+                    }
+                    _ => Err(DFError::ExtensionFilterError(format!(
+                        "expected a value after '{} ='",
+                        field
+                    ))),
+                }
+            }
+            other => Err(DFError::ExtensionFilterError(format!(
+                "expected a field, '(', or 'NOT', got {:?}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Parse a filter expression, e.g. `ext_type = nested AND module = "fs"`, into a
+/// `FilterExpr` AST. Does not validate field names/values -- that's `compile_filter`'s job,
+/// so a syntactically valid but semantically meaningless filter (`color = blue`) is still
+/// reported as clearly as a malformed one.
+pub fn parse_filter(input: &str) -> Result<FilterExpr, DFError> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Err(DFError::ExtensionFilterError(
+            "empty filter expression".to_string(),
+        ));
+    }
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.parse_or()?;
+    if parser.pos != parser.tokens.len() {
+        return Err(DFError::ExtensionFilterError(format!(
+            "unexpected trailing tokens after {:?}",
+            expr
+        )));
+    }
+    Ok(expr)
+}
+
+/// The fields of a candidate `ExtensionPoint` a filter can query, resolved once per
+/// candidate against the `Test`/`NpmModule` it came from -- see `ExtensionPoint`'s own doc
+/// comment for why this can't just live on `ExtensionPoint` itself.
+pub struct ExtPointEvalContext {
+    /// The module this extension point's call belongs to (`NpmModule::lib`).
+    pub module: String,
+    /// The access path the call at this extension point sits on, if any.
+    pub acc_path: Option<AccessPathModuleCentred>,
+}
+
+impl ExtPointEvalContext {
+    /// Resolve the fields a filter can query for `point`, against `test`/`mod_rep`.
+    pub fn resolve(test: &Test, mod_rep: &NpmModule, point: &ExtensionPoint) -> Self {
+        let acc_path = test
+            .get_fct_call_from_id(&point.get_node_id())
+            .and_then(|fc| fc.get_acc_path().clone());
+        Self {
+            module: mod_rep.lib.clone(),
+            acc_path,
+        }
+    }
+}
+
+/// Compile a parsed filter into a predicate over `(candidate, its resolved context)`.
+/// Fails at compile time (rather than at evaluation time, once per candidate) on an
+/// unknown field name or a comparison value that doesn't match any known variant for that
+/// field, so a typo like `ext_tpye = nested` is reported once, clearly, instead of as a
+/// filter that silently rejects every candidate.
+pub fn compile_filter(
+    expr: &FilterExpr,
+) -> Result<Box<dyn Fn(&ExtensionPoint, &ExtPointEvalContext) -> bool>, DFError> {
+    match expr {
+        FilterExpr::Compare(field, value) => match field.as_str() {
+            "ext_type" => {
+                let want = match value.as_str() {
+                    "sequential" => ExtensionType::Sequential,
+                    "nested" => ExtensionType::Nested,
+                    other => {
+                        return Err(DFError::ExtensionFilterError(format!(
+                            "unknown ext_type value {:?} (expected 'sequential' or 'nested')",
+                            other
+                        )))
+                    }
+                };
+                Ok(Box::new(move |point: &ExtensionPoint, _ctx: &ExtPointEvalContext| {
+                    point.get_ext_type() == want
+                }))
+            }
+            "module" => {
+                let want = value.clone();
+                Ok(Box::new(move |_point: &ExtensionPoint, ctx: &ExtPointEvalContext| {
+                    ctx.module == want
+                }))
+            }
+            "path" => {
+                let want = value.clone();
+                Ok(Box::new(move |_point: &ExtensionPoint, ctx: &ExtPointEvalContext| {
+                    ctx.acc_path
+                        .as_ref()
+                        .map(|ap| ap.to_string())
+                        .as_deref()
+                        == Some(want.as_str())
+                }))
+            }
+            other => Err(DFError::ExtensionFilterError(format!(
+                "unknown filter field {:?} (expected 'ext_type', 'module', or 'path')",
+                other
+            ))),
+        },
+        FilterExpr::And(lhs, rhs) => {
+            let lhs = compile_filter(lhs)?;
+            let rhs = compile_filter(rhs)?;
+            Ok(Box::new(move |point, ctx| lhs(point, ctx) && rhs(point, ctx)))
+        }
+        FilterExpr::Or(lhs, rhs) => {
+            let lhs = compile_filter(lhs)?;
+            let rhs = compile_filter(rhs)?;
+            Ok(Box::new(move |point, ctx| lhs(point, ctx) || rhs(point, ctx)))
+        }
+        FilterExpr::Not(inner) => {
+            let inner = compile_filter(inner)?;
+            Ok(Box::new(move |point, ctx| !inner(point, ctx)))
+        }
+    }
+}
+
+/// Parse and compile a filter expression in one step -- the usual entry point for a
+/// user-supplied `--ext-filter` string.
+pub fn compile_filter_str(
+    input: &str,
+) -> Result<Box<dyn Fn(&ExtensionPoint, &ExtPointEvalContext) -> bool>, DFError> {
+    compile_filter(&parse_filter(input)?)
+}
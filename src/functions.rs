@@ -40,7 +40,7 @@ impl From<&Vec<ArgType>> for FunctionSignature {
         Self {
             arg_list: arg_types
                 .iter()
-                .map(|ty| FunctionArgument::new(*ty, None))
+                .map(|ty| FunctionArgument::new(ty.clone(), None))
                 .collect::<Vec<FunctionArgument>>(),
             call_test_result: None,
             is_spread_args: false,
@@ -99,6 +99,9 @@ impl FunctionSignature {
                 Some(ArgVal::Callback(CallbackVal::RawCallback(cb))) => {
                     Some(cb.get_all_cb_args_vals(context_uniq_id))
                 }
+                // `Var`/`FnRef` callbacks aren't generated by us (they're a name, or a name plus
+                // bound arguments, for an already-existing function), so we have no signature to
+                // draw scoped parameter names from -- nothing new comes into scope for them.
                 _ => None,
             })
             .flatten()
@@ -127,12 +130,43 @@ impl FunctionSignature {
 
     /// Getter for the result of calling the function with this signature.
     pub fn get_call_res(&self) -> Option<FunctionCallResult> {
-        self.call_test_result
+        self.call_test_result.clone()
     }
 
     pub fn set_call_res(&mut self, res: FunctionCallResult) {
         self.call_test_result = Some(res);
     }
+
+    /// Try to merge `self` and `other` into a single signature that's a generalization of
+    /// both: unifies position-by-position via `ArgType::unify`, returning `None` if any
+    /// position fails to unify. The two `arg_list`s may differ in length only if the
+    /// shorter one has `is_spread_args` set, in which case its missing trailing positions
+    /// widen to `AnyType` to line up with the longer signature's. The merged result's
+    /// `call_test_result` is dropped (it no longer corresponds to any single call tested).
+    pub fn try_merge(&self, other: &Self) -> Option<Self> {
+        let (longer, shorter) = if self.arg_list.len() >= other.arg_list.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        if longer.arg_list.len() != shorter.arg_list.len() && !shorter.is_spread_args {
+            return None;
+        }
+        let mut merged_args = Vec::with_capacity(longer.arg_list.len());
+        for (pos, longer_arg) in longer.arg_list.iter().enumerate() {
+            let merged_type = match shorter.arg_list.get(pos) {
+                Some(shorter_arg) => longer_arg.get_type().unify(&shorter_arg.get_type())?,
+                // shorter signature ran out of positions (only possible if it's spread args)
+                None => ArgType::AnyType,
+            };
+            merged_args.push(FunctionArgument::new(merged_type, None));
+        }
+        Some(Self {
+            arg_list: merged_args,
+            call_test_result: None,
+            is_spread_args: self.is_spread_args || other.is_spread_args,
+        })
+    }
 }
 
 /// Default signature is empty, with the spread argument, and untested.
@@ -173,17 +207,21 @@ impl FunctionArgument {
     /// then the `extra_body_code` is extra instrumentation code to be included
     /// in the body of the function, and `context_uniq_id` is the unique ID of the
     /// function this argument is being passed to, which is information needed
-    /// for the instrumentation).
+    /// for the instrumentation). `depth` is the nesting depth of the call this
+    /// callback is an argument to, logged alongside its own instrumentation events
+    /// (see `Callback::get_string_rep`); irrelevant when `print_instrumented` is false.
     pub fn get_string_rep_arg_val(
         &self,
         extra_body_code: Option<String>,
         context_uniq_id: Option<String>,
         print_instrumented: bool,
+        depth: Option<usize>,
     ) -> Option<String> {
         Some(self.arg_val.clone()?.get_string_rep(
             extra_body_code,
             context_uniq_id,
             print_instrumented,
+            depth,
         ))
     }
 
@@ -192,14 +230,14 @@ impl FunctionArgument {
     pub fn get_string_rep_arg_val_short(&self) -> Option<String> {
         match self.arg_type {
             ArgType::CallbackType => Some("\"[function]\"".to_string()),
-            _ => self.get_string_rep_arg_val(None, None, false),
+            _ => self.get_string_rep_arg_val(None, None, false, None),
         }
     }
 
     /// Setter for the value of this argument.
     /// Returns an error if the value `arg_val` is not compatible with the type of this arg.
     pub fn set_arg_val(&mut self, arg_val: ArgVal) -> Result<(), TestGenError> {
-        if !(arg_val.get_type().can_be_repd_as(self.arg_type)) {
+        if !(arg_val.get_type().can_be_repd_as(&self.arg_type)) {
             return Err(TestGenError::ArgTypeValMismatch);
         }
         self.arg_val = Some(arg_val);
@@ -218,7 +256,7 @@ impl FunctionArgument {
 
     /// Getter for the type of this argument.
     pub fn get_type(&self) -> ArgType {
-        self.arg_type
+        self.arg_type.clone()
     }
 
     /// Setter for the callback ID of this argument (this is a no-op if
@@ -239,20 +277,34 @@ impl FunctionArgument {
 /// Note: this can be modified for an arbitrary amount of granularity;
 /// so far we have mainly stuck to the default types available in JavaScript,
 /// with the added distinction between generated callbacks and API library functions.
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+/// `ArrayOf`, `StringEnum` and `Union` are refinements that narrow a plain `ArrayType`/
+/// `StringType`/`AnyType` to the shape a specific API actually expects (e.g. as inferred from
+/// a TypeScript declaration, see `crate::ts_spec`); `IntType`/`FloatType` do the same for
+/// `NumberType`. Not Copy, since `ArrayOf`/`Union` own their nested types.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 pub enum ArgType {
-    /// Number.
+    /// Number (unconstrained int-vs-float; used when that distinction isn't known).
     NumberType,
+    /// Number known to be an integer.
+    IntType,
+    /// Number known to have a fractional part.
+    FloatType,
     /// String.
     StringType,
+    /// String restricted to one of a fixed set of literal values.
+    StringEnum(Vec<String>),
     /// Array.
     ArrayType,
+    /// Array whose elements all have the given type.
+    ArrayOf(Box<ArgType>),
     /// Non-callback, non-array, object.
     ObjectType,
     /// Generated callback (TODO maybe more granularity here).
     CallbackType,
     /// API library function -- distinct from callbacks, since we're not building them.
     LibFunctionType,
+    /// One of a fixed set of alternative types (e.g. a TypeScript union type).
+    Union(Vec<ArgType>),
     /// The `any` dynamic type.
     AnyType,
 }
@@ -260,28 +312,80 @@ pub enum ArgType {
 impl ArgType {
     /// Return `true` if the receiver (`self`) can be represented
     /// by the other type `ot`.
-    pub fn can_be_repd_as(&self, ot: Self) -> bool {
-        *self == ot || ot == Self::AnyType
+    pub fn can_be_repd_as(&self, ot: &Self) -> bool {
+        if self == ot || *ot == Self::AnyType {
+            return true;
+        }
+        match (self, ot) {
+            // an int or a float is still a number
+            (Self::IntType | Self::FloatType, Self::NumberType) => true,
+            // a literal-constrained string is still a string
+            (Self::StringEnum(_), Self::StringType) => true,
+            // a literal set can narrow to a smaller literal set
+            (Self::StringEnum(self_vals), Self::StringEnum(ot_vals)) => {
+                self_vals.iter().all(|v| ot_vals.contains(v))
+            }
+            // a typed array is still an array, and narrows to a differently (but compatibly)
+            // typed array if its element type does
+            (Self::ArrayOf(_), Self::ArrayType) => true,
+            (Self::ArrayOf(self_elt), Self::ArrayOf(ot_elt)) => self_elt.can_be_repd_as(ot_elt),
+            // satisfies a union if it satisfies any one of its members
+            (_, Self::Union(members)) => members.iter().any(|m| self.can_be_repd_as(m)),
+            // a union can be repd as `ot` only if every one of its members can
+            (Self::Union(members), _) => members.iter().all(|m| m.can_be_repd_as(ot)),
+            _ => false,
+        }
+    }
+
+    /// Compute the least-upper-bound of `self` and `other`, if one exists: identical types
+    /// unify to themselves, and `AnyType` behaves as a placeholder/top that unifies with
+    /// anything, returning the more specific of the two. Anything else (in particular, a
+    /// `CallbackType`/`LibFunctionType` against a different, non-`AnyType` type) doesn't
+    /// unify, since widening it would change what kind of value is actually passed.
+    pub fn unify(&self, other: &Self) -> Option<Self> {
+        match (self, other) {
+            (a, b) if a == b => Some(a.clone()),
+            (Self::AnyType, other) | (other, Self::AnyType) => Some(other.clone()),
+            (Self::IntType | Self::FloatType, Self::NumberType)
+            | (Self::NumberType, Self::IntType | Self::FloatType) => Some(Self::NumberType),
+            (Self::ArrayOf(a_elt), Self::ArrayOf(b_elt)) => {
+                Some(Self::ArrayOf(Box::new(a_elt.unify(b_elt)?)))
+            }
+            (Self::ArrayOf(_), Self::ArrayType) | (Self::ArrayType, Self::ArrayOf(_)) => {
+                Some(Self::ArrayType)
+            }
+            _ => None,
+        }
     }
 
     /// Is this a primitive type?
     pub fn is_not_callback(&self) -> bool {
-        match *self {
-            ArgType::CallbackType | ArgType::LibFunctionType => false,
-            _ => true,
-        }
+        !matches!(self, ArgType::CallbackType | ArgType::LibFunctionType)
     }
 }
 
 impl std::fmt::Display for ArgType {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match *self {
+        match self {
             ArgType::NumberType => write!(f, "num"),
+            ArgType::IntType => write!(f, "int"),
+            ArgType::FloatType => write!(f, "float"),
             ArgType::StringType => write!(f, "string"),
+            ArgType::StringEnum(vals) => write!(f, "string-enum({})", vals.join(" | ")),
             ArgType::ArrayType => write!(f, "array"),
+            ArgType::ArrayOf(elt) => write!(f, "array-of({})", elt),
             ArgType::ObjectType => write!(f, "object"),
             ArgType::CallbackType => write!(f, "callback-function"),
             ArgType::LibFunctionType => write!(f, "lib-function"),
+            ArgType::Union(members) => write!(
+                f,
+                "union({})",
+                members
+                    .iter()
+                    .map(|m| m.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" | ")
+            ),
             ArgType::AnyType => write!(f, "any"),
         }
     }
@@ -292,10 +396,20 @@ impl std::fmt::Display for ArgType {
 pub enum ArgVal {
     /// Number.
     Number(String),
+    /// Number known to be an integer.
+    Int(String),
+    /// Number known to have a fractional part.
+    Float(String),
     /// String.
     String(String),
+    /// String drawn from a fixed set of literal values: the chosen string, plus the
+    /// full set it was restricted to (so `get_type` can report the original `StringEnum`).
+    StringEnum(String, Vec<String>),
     /// Array.
     Array(String),
+    /// Array whose elements all have a known type: the array's string rep, plus that
+    /// element type (so `get_type` can report the original `ArrayOf`).
+    ArrayOf(String, Box<ArgType>),
     /// Non-callback, non-array, object.
     Object(String),
     /// Generated callback.
@@ -315,21 +429,28 @@ pub struct ArgValAPTracked {
 impl ArgVal {
     /// Get the string representation of this argument value.
     /// Instrumentation code is passed in and used to instrument callback values.
+    /// `depth` is the nesting depth of the call this value is an argument to (see
+    /// `Callback::get_string_rep`); ignored for anything but a `Callback`.
     pub fn get_string_rep(
         &self,
         extra_body_code: Option<String>,
         context_uniq_id: Option<String>,
         print_instrumented: bool,
+        depth: Option<usize>,
     ) -> String {
         match self {
             Self::Number(s)
+            | Self::Int(s)
+            | Self::Float(s)
             | Self::String(s)
+            | Self::StringEnum(s, _)
             | Self::Array(s)
+            | Self::ArrayOf(s, _)
             | Self::Object(s)
             | Self::LibFunction(s)
             | Self::Variable(s) => s.clone(),
             Self::Callback(cbv) => {
-                cbv.get_string_rep(extra_body_code, context_uniq_id, print_instrumented)
+                cbv.get_string_rep(extra_body_code, context_uniq_id, print_instrumented, depth)
             }
         }
     }
@@ -343,8 +464,12 @@ impl ArgVal {
     pub fn get_type(&self) -> ArgType {
         match self {
             Self::Number(_) => ArgType::NumberType,
+            Self::Int(_) => ArgType::IntType,
+            Self::Float(_) => ArgType::FloatType,
             Self::String(_) => ArgType::StringType,
+            Self::StringEnum(_, vals) => ArgType::StringEnum(vals.clone()),
             Self::Array(_) => ArgType::ArrayType,
+            Self::ArrayOf(_, elt) => ArgType::ArrayOf(elt.clone()),
             Self::Object(_) => ArgType::ObjectType,
             Self::Callback(_) => ArgType::CallbackType,
             Self::LibFunction(_) => ArgType::LibFunctionType,
@@ -372,21 +497,46 @@ pub enum CallbackVal {
     Var(String),
     /// Anonymous callback, represented as the raw signature/function-body.
     RawCallback(Callback),
+    /// Reference to an existing (library) function, optionally with some leading arguments
+    /// already bound -- e.g. passing `Number` directly as a callback (`arr.map(Number)`), or a
+    /// curried/partially-applied version of a library function (mirroring function-pointer
+    /// currying, where a callable is stored together with its pre-bound arguments).
+    FnRef {
+        /// Name of the referenced function.
+        name: String,
+        /// Leading arguments already bound; empty for a bare function reference.
+        bound_prefix: Vec<ArgVal>,
+    },
 }
 
 impl CallbackVal {
     /// Get the string representation of this callback value.
     /// Instrumentation code is passed in and used to instrument raw callback values.
+    /// `depth` is forwarded to `Callback::get_string_rep`; a bound-prefix `FnRef` argument
+    /// has no callback body of its own to log a depth for, so it's dropped on that path.
     pub fn get_string_rep(
         &self,
         extra_body_code: Option<String>,
         context_uniq_id: Option<String>,
         print_instrumented: bool,
+        depth: Option<usize>,
     ) -> String {
         match self {
             Self::Var(vs) => vs.clone(),
             Self::RawCallback(cb) => {
-                cb.get_string_rep(extra_body_code, context_uniq_id, print_instrumented)
+                cb.get_string_rep(extra_body_code, context_uniq_id, print_instrumented, depth)
+            }
+            Self::FnRef {
+                name,
+                bound_prefix,
+            } if bound_prefix.is_empty() => name.clone(),
+            Self::FnRef { name, bound_prefix } => {
+                let bound_args = bound_prefix
+                    .iter()
+                    .map(|arg_val| arg_val.get_string_rep(None, None, print_instrumented, None))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                format!("(...rest) => {}({}, ...rest)", name, bound_args)
             }
         }
     }
@@ -0,0 +1,75 @@
+//! GraphViz DOT export of a campaign's test-extension graph: one node per generated
+//! `Test` and a directed edge from a parent test to each test derived by extending it,
+//! built from `decisions::TestGenDB`'s `ExtensionGraphNode`/`ExtensionEdge` records (see
+//! `decisions::TestGenDB::record_extension_graph_node`/`record_extension_edge`). Lets a
+//! user render the campaign's exploration tree and see which functions/extension points
+//! dominate generation.
+
+use crate::decisions::TestGenDB;
+use crate::errors::DFError;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+fn open_writer(path: &str) -> Result<BufWriter<File>, DFError> {
+    File::create(path)
+        .map(BufWriter::new)
+        .map_err(|_| DFError::ReportWritingError(path.to_string()))
+}
+
+fn write_all(writer: &mut BufWriter<File>, path: &str, s: &str) -> Result<(), DFError> {
+    writer
+        .write_all(s.as_bytes())
+        .map_err(|_| DFError::ReportWritingError(path.to_string()))
+}
+
+/// Escape a string for use inside a quoted DOT identifier/label.
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// DOT node id for test `test_id` -- `indextree`'s `ExtensionPointID` is per-test, so the
+/// test id alone is already unique across the whole graph.
+fn node_id(test_id: usize) -> String {
+    format!("test_{}", test_id)
+}
+
+/// Render `testgen_db`'s recorded test-extension graph (see module docs) as a GraphViz
+/// `digraph` to `report_path`.
+pub fn write_extension_graph_dot(testgen_db: &TestGenDB, report_path: &str) -> Result<(), DFError> {
+    let mut writer = open_writer(report_path)?;
+    write_all(&mut writer, report_path, "digraph extension_graph {\n")?;
+
+    for node in testgen_db.get_extension_graph_nodes() {
+        let label = format!(
+            "Test {}\\n{}",
+            node.test_id,
+            dot_escape(&node.call_names.join(", "))
+        );
+        write_all(
+            &mut writer,
+            report_path,
+            &format!("  \"{}\" [label=\"{}\"];\n", node_id(node.test_id), label),
+        )?;
+    }
+
+    for edge in testgen_db.get_extension_edges() {
+        let mut label = format!("{:?} @ {:?}", edge.ext_type, edge.parent_ext_id);
+        if let Some(cb_arg_pos) = &edge.cb_arg_pos {
+            label += &format!(" (cb arg: {})", dot_escape(cb_arg_pos));
+        }
+        write_all(
+            &mut writer,
+            report_path,
+            &format!(
+                "  \"{}\" -> \"{}\" [label=\"{}\"];\n",
+                node_id(edge.parent_test_id),
+                node_id(edge.child_test_id),
+                label
+            ),
+        )?;
+    }
+
+    write_all(&mut writer, report_path, "}\n")?;
+    Ok(())
+}
@@ -0,0 +1,129 @@
+//! Integrity verification for mined-data seed files (see `crate::mined_seed_reps`), via a
+//! canonical JSON encoding, a SHA-512 digest, and an optional detached Ed25519 signature,
+//! stored alongside the data file in a sidecar `.sig` file.
+//!
+//! Mined corpora are large, externally-produced artifacts that get shared and reused as
+//! generator seeds -- there's no guarantee a given copy wasn't truncated in transit, edited by
+//! hand, or swapped out entirely. Canonicalizing before hashing (sorting object keys, and
+//! dropping the insignificant whitespace a pretty-printer would otherwise vary) makes the
+//! digest reproducible for the same logical data on any machine, independent of exactly how
+//! it was serialized to disk.
+
+use crate::errors::DFError;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha512};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// On-disk sidecar content: a hex-encoded SHA-512 digest of the canonical JSON encoding of the
+/// data file it accompanies, plus an optional hex-encoded detached Ed25519 signature over that
+/// digest.
+#[derive(Debug, Serialize, Deserialize)]
+struct IntegritySidecarJSON {
+    sha512_digest: String,
+    ed25519_signature: Option<String>,
+}
+
+/// Recursively re-encode a `serde_json::Value` with object keys in sorted order, so that two
+/// semantically-equal values serialize to byte-identical output regardless of the original
+/// field order.
+fn canonicalize(value: Value) -> Value {
+    match value {
+        Value::Array(items) => Value::Array(items.into_iter().map(canonicalize).collect()),
+        Value::Object(map) => {
+            let sorted: BTreeMap<String, Value> =
+                map.into_iter().map(|(k, v)| (k, canonicalize(v))).collect();
+            Value::Object(sorted.into_iter().collect())
+        }
+        other => other,
+    }
+}
+
+/// Serialize `data` to its canonical JSON byte encoding: keys sorted, no insignificant
+/// whitespace. This is only ever used to compute a digest -- the data file itself keeps using
+/// the repo's usual pretty-printed `serde_json::to_writer_pretty`.
+fn canonical_json_bytes<T: Serialize>(data: &T) -> Result<Vec<u8>, DFError> {
+    let value = serde_json::to_value(data).map_err(|_| {
+        DFError::InvalidMinedData("could not canonicalize data for integrity check".to_string())
+    })?;
+    serde_json::to_vec(&canonicalize(value)).map_err(|_| {
+        DFError::InvalidMinedData("could not canonicalize data for integrity check".to_string())
+    })
+}
+
+fn sha512_hex(bytes: &[u8]) -> String {
+    let mut hasher = Sha512::new();
+    hasher.update(bytes);
+    hex::encode(hasher.finalize())
+}
+
+/// Path of the sidecar signature file for a given mined-data file path: `<path>.sig`.
+pub fn sidecar_path(data_path: &PathBuf) -> PathBuf {
+    let mut sig_path = data_path.clone().into_os_string();
+    sig_path.push(".sig");
+    PathBuf::from(sig_path)
+}
+
+/// Write a `.sig` sidecar next to `data_path`, covering the canonical encoding of `data`. Signs
+/// the digest with `signing_key` if provided -- otherwise the sidecar just carries the digest
+/// (tamper-evident, but not attributable to a specific signer).
+pub fn write_sidecar<T: Serialize>(
+    data: &T,
+    data_path: &PathBuf,
+    signing_key: Option<&SigningKey>,
+) -> Result<(), DFError> {
+    let digest = sha512_hex(&canonical_json_bytes(data)?);
+    let ed25519_signature =
+        signing_key.map(|key| hex::encode(key.sign(digest.as_bytes()).to_bytes()));
+
+    let sig_path = sidecar_path(data_path);
+    let sidecar = IntegritySidecarJSON {
+        sha512_digest: digest,
+        ed25519_signature,
+    };
+    let sig_file = std::fs::File::create(&sig_path).map_err(|_| {
+        DFError::WritingIntegritySidecarError(sig_path.to_string_lossy().to_string())
+    })?;
+    serde_json::to_writer_pretty(sig_file, &sidecar).map_err(|_| {
+        DFError::WritingIntegritySidecarError(sig_path.to_string_lossy().to_string())
+    })
+}
+
+/// Recompute the canonical digest of `data` and check it against the `.sig` sidecar next to
+/// `data_path`. If `verifying_key` is given and the sidecar carries a signature, the signature
+/// is checked too; a sidecar with no signature is accepted as long as the digest matches (the
+/// signature is an additional layer on top of the digest, not a requirement of every sidecar).
+pub fn verify_sidecar<T: Serialize>(
+    data: &T,
+    data_path: &PathBuf,
+    verifying_key: Option<&VerifyingKey>,
+) -> Result<(), DFError> {
+    let sig_path = sidecar_path(data_path);
+    let mismatch = || DFError::MinedDataIntegrityMismatch(data_path.to_string_lossy().to_string());
+
+    let sig_conts = std::fs::read_to_string(&sig_path).map_err(|_| {
+        DFError::ReadingIntegritySidecarError(sig_path.to_string_lossy().to_string())
+    })?;
+    let sidecar: IntegritySidecarJSON = serde_json::from_str(&sig_conts).map_err(|_| {
+        DFError::ReadingIntegritySidecarError(sig_path.to_string_lossy().to_string())
+    })?;
+
+    let digest = sha512_hex(&canonical_json_bytes(data)?);
+    if digest != sidecar.sha512_digest {
+        return Err(mismatch());
+    }
+
+    if let Some(verifying_key) = verifying_key {
+        let sig_hex = sidecar.ed25519_signature.as_ref().ok_or_else(mismatch)?;
+        let sig_bytes = hex::decode(sig_hex).map_err(|_| mismatch())?;
+        let signature = Signature::from_slice(&sig_bytes).map_err(|_| mismatch())?;
+        verifying_key
+            .verify(digest.as_bytes(), &signature)
+            .map_err(|_| mismatch())?;
+    }
+
+    Ok(())
+}
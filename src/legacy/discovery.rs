@@ -9,6 +9,7 @@ use crate::errors::*;
 use crate::functions::*;
 use crate::module_reps::*;
 use crate::tests::*;
+use crate::FxHashMap;
 use crate::TestGenMode;
 
 use std::collections::HashMap;
@@ -32,19 +33,45 @@ pub fn run_discovery_phase(
     // results of test executions
     let mut test_res_pairs: Vec<(
         Test,
-        HashMap<ExtensionPointID, (FunctionCallResult, Option<String>)>,
+        FxHashMap<ExtensionPointID, (FunctionCallResult, Option<String>)>,
     )> = Vec::new();
 
     for ((_, func_name), func_desc) in fcts.iter_mut() {
         let mut cur_cb_position = 1;
-        for _ in 0..consts::DISCOVERY_PHASE_TESTING_BUDGET {
+        // how many times each abstract signature has produced a non-error result so far
+        // this function, and the observed per-position type-success distribution -- fed
+        // back into `gen_args_for_fct_with_cb` so the testing budget concentrates on
+        // plausible argument shapes instead of re-sampling ones that already failed
+        let mut sig_success_counts: HashMap<Vec<ArgType>, f64> = HashMap::new();
+        let mut pos_type_freq: decisions::PosTypeFreq = Vec::new();
+        for _ in 0..testgen_db.get_config().discovery_phase_testing_budget {
             let args = gen_args_for_fct_with_cb(
                 &func_desc,
                 Some(cur_cb_position - 1),
-                &testgen_db,
+                &mut testgen_db,
                 &mod_rep,
                 test_gen_mode,
+                &sig_success_counts,
+                &pos_type_freq,
             )?;
+
+            // this exact (function name, ordered argument types) shape may already have been
+            // executed -- on this access path, or an aliased one reaching the same function
+            // under a different (path, name) key in `fcts` -- so check the discovery cache
+            // before spawning node again for an answer we already know (see
+            // `decisions::calc_discovery_sig_hash`).
+            let abstract_sig: Vec<ArgType> = args.iter().map(|arg| arg.get_type()).collect();
+            if let Some(cached_result) =
+                testgen_db.lookup_discovery_sig_cache(func_name, &abstract_sig)
+            {
+                if !matches!(cached_result, FunctionCallResult::ExecutionError(_)) {
+                    func_desc.add_sig(FunctionSignature::try_from((&args, cached_result)).unwrap());
+                    record_successful_shape(&args, &mut sig_success_counts, &mut pos_type_freq);
+                }
+                cur_cb_position = advance_cb_position(cur_cb_position, args.len());
+                continue;
+            }
+
             let fct_call = FunctionCall::new(
                 func_name.clone(),
                 FunctionSignature::new(&args, None),
@@ -62,6 +89,7 @@ pub fn run_discovery_phase(
                 testgen_db.test_dir_path.clone(),
                 testgen_db.test_file_prefix.clone(),
                 testgen_db.api_src_dir.clone(),
+                testgen_db.sandbox_policy.clone(),
             );
 
             let test_results = match cur_test.execute() {
@@ -71,19 +99,31 @@ pub fn run_discovery_phase(
             cur_test.delete_file()?;
 
             let (fct_result, _cb_arg_pos) = test_results.get(&cur_fct_id).unwrap();
+            testgen_db.record_discovery_sig_result(func_name, &abstract_sig, *fct_result);
             // if there was no execution error, then the generated signature is valid
-            if fct_result != &FunctionCallResult::ExecutionError {
+            if !matches!(fct_result, FunctionCallResult::ExecutionError(_)) {
                 func_desc.add_sig(FunctionSignature::try_from((&args, *fct_result)).unwrap());
+                record_successful_shape(&args, &mut sig_success_counts, &mut pos_type_freq);
+            } else if let Some(repaired_sig) = try_repair_failing_call(
+                func_name,
+                &args,
+                func_desc.get_sigs(),
+                &mut testgen_db,
+                &mod_rep,
+                test_gen_mode,
+                cur_test_id,
+            ) {
+                record_successful_shape(
+                    repaired_sig.get_arg_list(),
+                    &mut sig_success_counts,
+                    &mut pos_type_freq,
+                );
+                func_desc.add_sig(repaired_sig);
             }
 
             // if we haven't tested the current position with no callbacks, do that
             // else, move to the next position in the arg list and try with a callback arg
-            if cur_cb_position < 0 && args.len() > 0 {
-                cur_cb_position =
-                    (((cur_cb_position * (-1)) + 1) % i32::try_from(args.len()).unwrap()) + 1
-            } else {
-                cur_cb_position *= -1
-            }
+            cur_cb_position = advance_cb_position(cur_cb_position, args.len());
             cur_test_id += 1;
             test_res_pairs.push((cur_test, test_results));
         }
@@ -92,35 +132,128 @@ pub fn run_discovery_phase(
     for (cur_test, test_results) in test_res_pairs.iter() {
         testgen_db.add_extension_points_for_test(cur_test, test_results);
     }
+    // consolidate the (many, near-duplicate) signatures discovery accumulated per function
+    // into a minimal canonical set, widening conflicting positions to `AnyType`.
+    for (_, func_desc) in fcts.iter_mut() {
+        func_desc.consolidate_sigs();
+    }
     mod_rep.set_fns(fcts);
     Ok((mod_rep, testgen_db))
 }
 
+/// Run discovery across every module in `registry`, round-robin: each registered module gets
+/// its own full discovery pass, one at a time, via the existing single-module
+/// `run_discovery_phase` -- so a multi-module campaign's API surface is discovered the same
+/// way a single-module one always has been, module by module, accumulating into the one
+/// shared `testgen_db` (see `decisions::TestGenDB`) as it goes.
+///
+/// NOTE: this spends a full per-function discovery budget (`TestGenDB::get_config`'s
+/// `discovery_phase_testing_budget` each) on every module in turn, rather than dividing one
+/// shared budget across them -- the
+/// simplest reading of "round-robin... discovery budget across modules" that doesn't require
+/// inventing a new cross-module budgeting scheme. A registry with very many large modules
+/// would want a real weighted split instead; that's future work, not something this change
+/// needs to solve to make multi-module discovery usable.
+pub fn run_discovery_phase_multi(
+    registry: ModuleRegistry,
+    testgen_db: TestGenDB,
+    test_gen_mode: &TestGenMode,
+) -> Result<(ModuleRegistry, TestGenDB), DFError> {
+    let mut registry = registry;
+    let mut testgen_db = testgen_db;
+    let libs: Vec<String> = registry.iter().map(|(lib, _)| lib.clone()).collect();
+    // every registered module is "under test" for the rest of the campaign, so mined
+    // nested extensions crossing between two of them (see `decisions::TestGenDB::
+    // register_pkgs_under_test`) are considered rather than discarded
+    testgen_db.register_pkgs_under_test(libs.iter().cloned());
+    for lib in libs {
+        let mod_rep = match registry.take(&lib) {
+            Some(mod_rep) => mod_rep,
+            None => continue,
+        };
+        let (mod_rep, new_testgen_db) = run_discovery_phase(mod_rep, testgen_db, test_gen_mode)?;
+        testgen_db = new_testgen_db;
+        registry.register(mod_rep);
+    }
+    Ok((registry, testgen_db))
+}
+
+/// Advance `cur_cb_position` to the next position `run_discovery_phase` should try a
+/// callback at: if the current position is "no callback yet" (negative), flip to trying a
+/// callback at the next position in the arg list (wrapping back to position 1 past the end);
+/// otherwise, go back to trying the same position with no callback.
+fn advance_cb_position(cur_cb_position: i32, num_args: usize) -> i32 {
+    if cur_cb_position < 0 && num_args > 0 {
+        (((cur_cb_position * (-1)) + 1) % i32::try_from(num_args).unwrap()) + 1
+    } else {
+        cur_cb_position * -1
+    }
+}
+
+/// Record that `args` (an abstract signature that either produced a non-error result, or was
+/// accepted as a repair) succeeded: bump its whole-shape count in `sig_success_counts`, and bump
+/// the per-position type count in `pos_type_freq` for each of its argument types.
+fn record_successful_shape(
+    args: &[FunctionArgument],
+    sig_success_counts: &mut HashMap<Vec<ArgType>, f64>,
+    pos_type_freq: &mut decisions::PosTypeFreq,
+) {
+    let abstract_sig = args.iter().map(|arg| arg.get_type()).collect::<Vec<ArgType>>();
+    *sig_success_counts.entry(abstract_sig).or_insert(0.0) += 1.0;
+
+    for (i, arg) in args.iter().enumerate() {
+        if pos_type_freq.len() <= i {
+            pos_type_freq.resize(i + 1, HashMap::new());
+        }
+        *pos_type_freq[i].entry(arg.get_type()).or_insert(0.0) += 1.0;
+    }
+}
+
 /// Generate arguments for a function with a callback at specified position `cb_position`.
 /// If the position specified is invalid (i.e., if it's not in the range of valid indices)
 /// then there is no callback argument included.
+///
+/// `sig_success_counts` and `pos_type_freq` are the feedback accumulated so far this function
+/// by `run_discovery_phase` (see `record_successful_shape`): they bias the freshly-generated
+/// signature towards abstract shapes, and per-position types, that have already worked.
+#[allow(clippy::too_many_arguments)]
 fn gen_args_for_fct_with_cb(
     mod_fct: &ModuleFunction,
     cb_position: Option<i32>,
-    testgen_db: &TestGenDB,
+    testgen_db: &mut TestGenDB,
     mod_rep: &NpmModule,
     test_gen_mode: &TestGenMode,
+    sig_success_counts: &HashMap<Vec<ArgType>, f64>,
+    pos_type_freq: &decisions::PosTypeFreq,
 ) -> Result<Vec<FunctionArgument>, TestGenError> {
     let num_args = mod_fct.get_num_api_args();
-    // TODO in the improved version of the discovery phase, this information will be used
-    // to inform the new signatures generated
+    // weight each already-discovered abstract shape by how often it has actually succeeded
+    // so far this function, rather than treating them all as equally promising
     let sigs = mod_fct
         .get_sigs()
         .iter()
-        .map(|sig| (sig.get_abstract_sig(), 1.0))
+        .map(|sig| {
+            let abstract_sig = sig.get_abstract_sig();
+            let weight = *sig_success_counts.get(&abstract_sig).unwrap_or(&1.0);
+            (abstract_sig, weight)
+        })
         .collect::<HashMap<Vec<ArgType>, f64>>();
 
-    let mut cur_sig =
-        decisions::gen_new_sig_with_cb(num_args, &sigs, cb_position, testgen_db, test_gen_mode);
+    let mut cur_sig = decisions::gen_new_sig_with_cb(
+        num_args,
+        &sigs,
+        cb_position,
+        testgen_db,
+        test_gen_mode,
+        Some(pos_type_freq),
+    );
     for (i, arg) in cur_sig.get_mut_args().iter_mut().enumerate() {
         let arg_type = arg.get_type();
         arg.set_arg_val(match arg_type {
-            ArgType::CallbackType => ArgVal::Callback(CallbackVal::Var("cb".to_string())),
+            // occasionally pass a reference to one of the module's other functions directly as
+            // the callback (optionally curried), to exercise call patterns like `arr.map(Number)`
+            // alongside the default freshly-generated named callback
+            ArgType::CallbackType => testgen_db.gen_discovery_cb_arg_val(&mod_rep),
             _ => testgen_db.gen_random_value_of_type(
                 arg_type,
                 Some(i),
@@ -133,3 +266,227 @@ fn gen_args_for_fct_with_cb(
     }
     Ok(cur_sig.get_arg_list().to_vec())
 }
+
+/// Try to recover a passing signature for a call whose generated `args` errored out, by
+/// reasoning about argument placement instead of discarding the attempt: `repair_candidates`
+/// proposes alternative argument shapes (at most `consts::MAX_REPAIR_CANDIDATES_TRIED` of them,
+/// see its doc comment for how), and each is rebuilt into a fresh call and executed once.
+/// The first candidate whose rebuilt call no longer errors is returned as a (now-valid)
+/// `FunctionSignature`; `None` if every candidate still errors (or none were found).
+#[allow(clippy::too_many_arguments)]
+fn try_repair_failing_call(
+    fct_name: &str,
+    args: &[FunctionArgument],
+    known_good_sigs: &std::collections::HashSet<FunctionSignature>,
+    testgen_db: &mut TestGenDB,
+    mod_rep: &NpmModule,
+    test_gen_mode: &TestGenMode,
+    cur_test_id: usize,
+) -> Option<FunctionSignature> {
+    let candidates = repair_candidates(args, known_good_sigs, testgen_db, mod_rep, test_gen_mode);
+    for candidate_args in candidates
+        .into_iter()
+        .take(consts::MAX_REPAIR_CANDIDATES_TRIED)
+    {
+        let fct_call = FunctionCall::new(
+            fct_name.to_string(),
+            FunctionSignature::new(&candidate_args, None),
+            None,
+            None,
+            None, // no access path specified (none needed for this legacy code)
+            None, // default receiver (the module import)
+        );
+        let (cur_fct_id, mut cur_test) = Test::test_one_call(
+            mod_rep,
+            fct_call,
+            true, /* include basic callback */
+            cur_test_id,
+            testgen_db.test_dir_path.clone(),
+            testgen_db.test_file_prefix.clone(),
+            testgen_db.api_src_dir.clone(),
+            testgen_db.sandbox_policy.clone(),
+        );
+        let test_results = match cur_test.execute() {
+            Ok(res) => res.0,
+            Err(_) => continue,
+        };
+        let _ = cur_test.delete_file();
+        let (fct_result, _cb_arg_pos) = match test_results.get(&cur_fct_id) {
+            Some(res) => res,
+            None => continue,
+        };
+        if !matches!(fct_result, FunctionCallResult::ExecutionError(_)) {
+            return FunctionSignature::try_from((&candidate_args, *fct_result)).ok();
+        }
+    }
+    None
+}
+
+/// Propose alternative argument shapes for a failing call `args`, by comparing it against
+/// each of `known_good_sigs` (signatures already confirmed to work for this function) via
+/// an N-by-N-ish boolean compatibility matrix: entry `(provided_i, position_j)` is true if
+/// `args[provided_i]`'s type `can_be_repd_as` the type `known_good_sig` expects at position
+/// `position_j`. A minimal-cost edit is then searched for, bounded to at most 2 edits total
+/// (a reordering permutation, counted as one edit if the provided and target arities
+/// already match; plus, if the arities differ by exactly one, a single drop or insert):
+/// - same arity: a permutation of `args` onto the target's positions, found via bipartite
+///   matching on the matrix (`bipartite_perfect_match`);
+/// - one surplus argument: drop each candidate surplus argument in turn, then bipartite-match
+///   the rest;
+/// - one missing argument: for each candidate missing position, generate a fresh value of
+///   the type expected there, then bipartite-match the rest.
+/// Callback positions can only ever match other callback positions, since `can_be_repd_as`
+/// never returns `true` across a `CallbackType`/non-`CallbackType` pair (barring `AnyType`).
+fn repair_candidates(
+    args: &[FunctionArgument],
+    known_good_sigs: &std::collections::HashSet<FunctionSignature>,
+    testgen_db: &mut TestGenDB,
+    mod_rep: &NpmModule,
+    test_gen_mode: &TestGenMode,
+) -> Vec<Vec<FunctionArgument>> {
+    let provided_types: Vec<ArgType> = args.iter().map(|a| a.get_type()).collect();
+    let mut candidates = Vec::new();
+
+    for target_sig in known_good_sigs.iter() {
+        let expected = target_sig.get_abstract_sig();
+        let (provided_n, expected_n) = (provided_types.len(), expected.len());
+
+        if provided_n == expected_n {
+            let compat = compat_matrix(&provided_types, &expected);
+            if let Some(assignment) = bipartite_perfect_match(&compat) {
+                candidates.push(apply_assignment(args, &assignment, expected_n));
+            }
+        } else if provided_n == expected_n + 1 {
+            // one surplus argument: try dropping each one in turn
+            for dropped in 0..provided_n {
+                let remaining_args: Vec<FunctionArgument> = args
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != dropped)
+                    .map(|(_, a)| a.clone())
+                    .collect();
+                let remaining_types: Vec<ArgType> =
+                    remaining_args.iter().map(|a| a.get_type()).collect();
+                let compat = compat_matrix(&remaining_types, &expected);
+                if let Some(assignment) = bipartite_perfect_match(&compat) {
+                    candidates.push(apply_assignment(&remaining_args, &assignment, expected_n));
+                }
+            }
+        } else if expected_n == provided_n + 1 {
+            // one missing argument: try inserting a fresh value at each candidate position
+            for missing_pos in 0..expected_n {
+                let remaining_expected: Vec<ArgType> = expected
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != missing_pos)
+                    .map(|(_, et)| *et)
+                    .collect();
+                let compat = compat_matrix(&provided_types, &remaining_expected);
+                if let Some(assignment) = bipartite_perfect_match(&compat) {
+                    // `assignment[i]` indexes into `remaining_expected`; translate back to
+                    // the real position in `expected` (which skips over `missing_pos`)
+                    let expected_positions: Vec<usize> =
+                        (0..expected_n).filter(|&j| j != missing_pos).collect();
+                    let mut repaired: Vec<Option<FunctionArgument>> = vec![None; expected_n];
+                    for (i, &col) in assignment.iter().enumerate() {
+                        repaired[expected_positions[col]] = Some(args[i].clone());
+                    }
+                    let missing_type = expected[missing_pos];
+                    let mut missing_arg = FunctionArgument::new(missing_type, None);
+                    let missing_val = match missing_type {
+                        ArgType::CallbackType => {
+                            ArgVal::Callback(CallbackVal::Var("cb".to_string()))
+                        }
+                        _ => testgen_db.gen_random_value_of_type(
+                            missing_type,
+                            Some(missing_pos),
+                            &Vec::new(),
+                            &Vec::new(),
+                            mod_rep,
+                            test_gen_mode,
+                        ),
+                    };
+                    if missing_arg.set_arg_val(missing_val).is_ok() {
+                        repaired[missing_pos] = Some(missing_arg);
+                        if repaired.iter().all(Option::is_some) {
+                            candidates.push(
+                                repaired.into_iter().map(|a| a.unwrap()).collect::<Vec<_>>(),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+    candidates
+}
+
+/// Boolean compatibility matrix: `matrix[i][j]` is true if `provided[i]` `can_be_repd_as`
+/// `expected[j]` (see `ArgType::can_be_repd_as`). Callback positions only ever match other
+/// callback positions (or `AnyType`), since a `CallbackType` can't represent -- or be
+/// represented by -- any other concrete type.
+fn compat_matrix(provided: &[ArgType], expected: &[ArgType]) -> Vec<Vec<bool>> {
+    provided
+        .iter()
+        .map(|pt| expected.iter().map(|et| pt.can_be_repd_as(et)).collect())
+        .collect()
+}
+
+/// Rebuild an argument list of length `num_positions` by placing `args[i]` at
+/// `assignment[i]` for each `i`, per a `bipartite_perfect_match` result.
+fn apply_assignment(
+    args: &[FunctionArgument],
+    assignment: &[usize],
+    num_positions: usize,
+) -> Vec<FunctionArgument> {
+    let mut repaired: Vec<Option<FunctionArgument>> = vec![None; num_positions];
+    for (i, &pos) in assignment.iter().enumerate() {
+        repaired[pos] = Some(args[i].clone());
+    }
+    repaired.into_iter().map(|a| a.unwrap()).collect()
+}
+
+/// Find a perfect matching between `compat`'s rows and columns via Kuhn's augmenting-path
+/// algorithm: an assignment of every row to a distinct column with `compat[row][col]` true.
+/// Returns, for each row, the column it's assigned to; `None` if `compat` isn't square or
+/// no such assignment exists.
+fn bipartite_perfect_match(compat: &[Vec<bool>]) -> Option<Vec<usize>> {
+    let n = compat.len();
+    if n == 0 || compat.iter().any(|row| row.len() != n) {
+        return if n == 0 { Some(Vec::new()) } else { None };
+    }
+
+    fn try_assign(
+        row: usize,
+        compat: &[Vec<bool>],
+        visited: &mut [bool],
+        match_for_col: &mut [Option<usize>],
+    ) -> bool {
+        for (col, &compatible) in compat[row].iter().enumerate() {
+            if compatible && !visited[col] {
+                visited[col] = true;
+                if match_for_col[col].is_none()
+                    || try_assign(match_for_col[col].unwrap(), compat, visited, match_for_col)
+                {
+                    match_for_col[col] = Some(row);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    let mut match_for_col: Vec<Option<usize>> = vec![None; n];
+    for row in 0..n {
+        let mut visited = vec![false; n];
+        if !try_assign(row, compat, &mut visited, &mut match_for_col) {
+            return None;
+        }
+    }
+
+    let mut match_for_row = vec![0usize; n];
+    for (col, row) in match_for_col.into_iter().enumerate() {
+        match_for_row[row.unwrap()] = col;
+    }
+    Some(match_for_row)
+}
@@ -5,19 +5,40 @@
 //! Data and feedback directed automated test generator for JavaScript libraries.
 
 pub mod code_gen;
+pub mod config;
 pub mod consts;
 pub mod decisions;
+pub mod dedup;
+pub mod emitter;
 pub mod errors;
+pub mod ext_filter;
 pub mod functions;
+pub mod graphviz;
+pub mod integrity;
 pub mod legacy;
 pub mod mined_seed_reps;
+pub mod minimize;
 pub mod module_reps;
+pub mod reporters;
+pub mod runner;
+pub mod sandbox;
+pub mod serialization;
 pub mod testgen;
 pub mod tests;
+pub mod ts_spec;
 
 #[macro_use]
 extern crate rand_derive;
 
+/// Drop-in replacement for `std::collections::HashMap` using `rustc_hash`'s FxHash instead of
+/// the default SipHash: SipHash is DoS-resistant (an attacker who controls the keys can't force
+/// worst-case collisions), which is wasted cost on the hot access-path/extension-point maps --
+/// built from our own mining/instrumentation output, never from untrusted input -- that can
+/// hold tens of thousands of entries for a large library. Key/value semantics are identical to
+/// `HashMap`; only construction differs (`FxHashMap::default()` in place of `HashMap::new()`,
+/// since the inherent `::new()` is only defined for the default `RandomState` hasher).
+pub type FxHashMap<K, V> = std::collections::HashMap<K, V, rustc_hash::FxBuildHasher>;
+
 #[derive(Debug, Eq, PartialEq)]
 pub enum TestGenMode {
     /// Current head of the current -- most up-to-date version (the default option)
@@ -1,23 +1,44 @@
-use std::io::Write;
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::str::FromStr;
 use structopt::StructOpt;
 
+use nessie::config;
 use nessie::consts;
 use nessie::decisions;
+use nessie::graphviz;
 use nessie::legacy;
 use nessie::mined_seed_reps::MinedNestingPairJSON;
 use nessie::module_reps::*; // all the representation structs
+use nessie::reporters::{self, ReportFormat};
+use nessie::testgen;
 use nessie::testgen::run_testgen_phase;
 use nessie::TestGenMode;
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "nessie_args", about = "Arguments for the test generator")]
 struct Opt {
-    /// Name of the library/module to generate tests for.
+    /// Path to a TOML (or, with a `.json` extension, JSON) config file whose top-level keys
+    /// mirror this struct's fields, so a reproducible per-library generation recipe can be
+    /// kept under version control instead of a long command line. A top-level `%include`
+    /// key names another config file (resolved relative to this one) to merge in as a
+    /// lower-precedence base -- see `nessie::config`. Precedence: explicit CLI flags >
+    /// this file's own keys > keys it pulled in via `%include`.
+    ///
+    /// A top-level `[generation]` table, if present, is parsed into `consts::Config` (see
+    /// `resolve_generation_config`) instead of mirroring an `Opt` field: weight factors,
+    /// choice probabilities, and the toy filesystem layout the hardcoded `consts::*`
+    /// equivalents used to provide, now tunable per-package without recompiling. Any key
+    /// left out of `[generation]` keeps `consts::Config::default()`'s value.
+    #[structopt(long, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Name of the library/module to generate tests for. Required, but may come from
+    /// `--config` instead of the CLI if given there.
     #[structopt(long)]
-    lib_name: String,
+    lib_name: Option<String>,
 
     /// Directory containing source code for the library.
     /// Note: this needs to be the root such that if we `require(lib_src_dir)` we
@@ -35,9 +56,21 @@ struct Opt {
     #[structopt(long, parse(from_os_str))]
     module_import_code: Option<PathBuf>,
 
-    /// Number of tests to generate.
+    /// How to load the module under test in generated tests: `Cjs` (default, `require(...)`),
+    /// `Esm` (dynamic `await import(...)` from a `.js` test file, for ESM-only or
+    /// dual-published packages that throw when `require`d), `EsmStatic` (a static
+    /// `import * as ...` declaration written to a `.mjs` test file, for packages that need
+    /// to be loaded as a real ES module rather than interop-loaded from CommonJS), or
+    /// `TypeScript` (same static import, written to a `.ts` test file, for packages with no
+    /// compiled entry point at all -- running the suite needs a `ts-node`-compatible loader;
+    /// see `code_gen::get_meta_test_code`).
+    #[structopt(long)]
+    module_load_mode: Option<String>,
+
+    /// Number of tests to generate. Required, but may come from `--config` instead of the
+    /// CLI if given there.
     #[structopt(long)]
-    num_tests: i32,
+    num_tests: Option<i32>,
 
     /// Redo the API discovery?
     /// Default: no if there is an existing discovery output file.
@@ -56,13 +89,196 @@ struct Opt {
     /// Mode to run the test generator in.
     /// Default: the current head of this repo.
     test_gen_mode: Option<String>,
+
+    /// Seed for the test generator's RNG. If not specified, a random seed is drawn
+    /// from entropy (and printed, so the run can still be replayed later).
+    /// Pinning this lets a generation campaign be replayed bit-for-bit, which is useful
+    /// to deterministically regenerate a given test suite or bisect a flaky test.
+    #[structopt(long)]
+    seed: Option<u64>,
+
+    /// Optional path to a previous weight-model snapshot (written via `--snapshot-out`
+    /// by an earlier run) to resume this campaign from, or to warm-start a new run with
+    /// a pre-trained weight model for this module.
+    #[structopt(long, parse(from_os_str))]
+    snapshot_in: Option<PathBuf>,
+
+    /// Optional path to write a snapshot of the generator's learned weight model and
+    /// progress counters to once test generation finishes, so the campaign can be
+    /// resumed or shared later.
+    #[structopt(long, parse(from_os_str))]
+    snapshot_out: Option<PathBuf>,
+
+    /// Use the persistent-runner execution backend (a single resident `node` worker
+    /// fed test files one at a time, see `nessie::runner::PersistentRunner`) instead of
+    /// spawning a fresh `node` process per test. Trades away both per-test whole-test V8
+    /// coverage collection and the parallel worker-pool execution added for the default
+    /// backend (the two aren't composed: a resident worker is inherently single-threaded)
+    /// for lower per-test interpreter/module-load startup cost.
+    #[structopt(long)]
+    persistent_runner: bool,
+
+    /// Format to write a structured, streaming report of the generated test suite's
+    /// outcomes in (see `nessie::reporters`). Also selects the format of the per-library-
+    /// function signature-discovery/outcome summary written to `<report_path>.functions`
+    /// once the campaign finishes (see `nessie::reporters::write_function_summary_report`).
+    /// Default: no report is written.
+    #[structopt(long)]
+    report_format: Option<String>,
+
+    /// Path to write the structured test-suite report to. Required if `--report-format`
+    /// is given; ignored otherwise. The per-library-function summary (see
+    /// `report_format` above) is written alongside it, at `<report_path>.functions`.
+    #[structopt(long, parse(from_os_str))]
+    report_path: Option<PathBuf>,
+
+    /// Path to write a GraphViz DOT rendering of the campaign's test-extension graph to
+    /// once test generation finishes -- one node per generated test, and an edge from a
+    /// parent test to each test derived by extending it (see `nessie::graphviz`).
+    /// Default: no graph is written.
+    #[structopt(long, parse(from_os_str))]
+    extension_graph_path: Option<PathBuf>,
+
+    /// Strategy used to choose between nested and sequential extension when generating a
+    /// new test (see `nessie::decisions::ExtensionStrategy`).
+    /// Default: `UniformRandom`, matching the generator's previous (fixed) behaviour.
+    #[structopt(long)]
+    ext_strategy: Option<String>,
+
+    /// Path to a package's bundled TypeScript declaration file (`.d.ts`). If given, instead
+    /// of running the `api_info` phase's JS-based listing script, function signatures are
+    /// pre-seeded from the declared parameter types (see `nessie::module_reps::NpmModule::
+    /// from_typescript_spec`), shrinking the random search space discovery has to cover.
+    #[structopt(long, parse(from_os_str))]
+    ts_declarations: Option<PathBuf>,
+
+    /// Number of discovery+testgen rounds to run. Default: 1 (the previous, single-shot
+    /// behaviour). Each round after the first re-runs the discovery phase against the
+    /// `mod_rep`/`testgen_db` state left by the previous round (so newly-discovered
+    /// signatures and access paths feed back in), then generates `num_tests` more tests --
+    /// whose extension-point weights are already biased towards under-covered code by the
+    /// per-test V8 coverage feedback in `TestGenDB::add_extension_points_for_test` (see
+    /// `--coverage-guided` below). Ignored if `--skip-testgen` is given.
+    #[structopt(long)]
+    rounds: Option<u32>,
+
+    /// Print a per-round summary of how much of the discovered API surface has been
+    /// exercised by at least one successful call, so coverage-driven progress across
+    /// `--rounds` is visible. Coverage-guided extension-point weighting itself happens
+    /// unconditionally whenever tests are run via the worker-pool backend (see
+    /// `Test::execute`/`collect_v8_coverage`) -- this flag only controls whether that
+    /// progress is summarized. Has no effect with `--persistent-runner`, which doesn't
+    /// collect per-test V8 coverage at all.
+    #[structopt(long)]
+    coverage_guided: bool,
+
+    /// Disable `decisions::TestGenDB`'s execution-result cache (see `TestGenDB::
+    /// set_result_cache_enabled`), which by default skips re-executing a freshly-generated
+    /// test that's structurally (and value-for-value) identical to one already run this
+    /// campaign. Use this for libraries whose calls aren't pure (e.g. anything
+    /// time-/randomness-/IO-dependent), where identical generated code can legitimately
+    /// yield different outcomes run to run.
+    #[structopt(long)]
+    no_cache: bool,
+
+    /// Keep running: after finishing a discovery+testgen pass, watch `lib_src_dir` for
+    /// source file changes (polling, debounced -- see `wait_for_source_change`) and, once
+    /// one is seen, re-run discovery and testgen again rather than exiting. Requires
+    /// `--lib-src-dir` (there's nothing to watch for an installed npm package). The toy
+    /// filesystem set up once at startup (`setup_toy_fs`) is reused across iterations
+    /// rather than recreated.
+    #[structopt(long)]
+    watch: bool,
+
+    /// Path to a persisted corpus of interesting test cases (an execution error, or a novel
+    /// signature/extension point) for `lib_name`, modeled on proptest's
+    /// `failure_persistence` (see `nessie::testgen::replay_corpus`). If given: before any
+    /// random generation this run, every case already persisted for `lib_name` is
+    /// regenerated and re-executed first, so a regression is caught immediately; and every
+    /// interesting test generated this run is appended to it for a later run to replay.
+    /// The file is created on first use and is shared across every `lib_name` that's ever
+    /// passed it, keyed by name. Ignored if `--skip-testgen` is given.
+    #[structopt(long, parse(from_os_str))]
+    corpus_file: Option<PathBuf>,
+}
+
+/// Recursively collect `(path, modified time)` for every file under `dir`. Missing/
+/// unreadable entries are skipped rather than bailing out, since a file can legitimately
+/// disappear between the directory listing and the `metadata` call (e.g. an editor's
+/// atomic-save temp file).
+fn collect_source_mtimes(dir: &Path) -> HashMap<PathBuf, std::time::SystemTime> {
+    let mut mtimes = HashMap::new();
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return mtimes,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            mtimes.extend(collect_source_mtimes(&path));
+        } else if let Ok(metadata) = entry.metadata() {
+            if let Ok(modified) = metadata.modified() {
+                mtimes.insert(path, modified);
+            }
+        }
+    }
+    mtimes
+}
+
+/// Block (polling every `WATCH_POLL_INTERVAL`) until a file under `lib_src_dir` is added,
+/// removed, or has a newer modification time than it did in `prev_mtimes`, debounced by
+/// `WATCH_DEBOUNCE` of quiet so a multi-file save (or a build tool rewriting several files
+/// in sequence) is only reported once `lib_src_dir` settles. Returns the new snapshot of
+/// mtimes, to diff against on the next call.
+fn wait_for_source_change(
+    lib_src_dir: &Path,
+    prev_mtimes: &HashMap<PathBuf, std::time::SystemTime>,
+) -> HashMap<PathBuf, std::time::SystemTime> {
+    const WATCH_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+    const WATCH_DEBOUNCE: std::time::Duration = std::time::Duration::from_secs(1);
+
+    let mut changed_mtimes;
+    loop {
+        std::thread::sleep(WATCH_POLL_INTERVAL);
+        changed_mtimes = collect_source_mtimes(lib_src_dir);
+        if changed_mtimes != *prev_mtimes {
+            break;
+        }
+    }
+    // debounce: keep polling until the tree is quiet for a full `WATCH_DEBOUNCE`
+    loop {
+        std::thread::sleep(WATCH_DEBOUNCE);
+        let settled_mtimes = collect_source_mtimes(lib_src_dir);
+        if settled_mtimes == changed_mtimes {
+            return settled_mtimes;
+        }
+        changed_mtimes = settled_mtimes;
+    }
+}
+
+/// Fraction of `mod_rep`'s discovered functions that have at least one signature with a
+/// recorded call result (i.e., that testgen has actually exercised), as `(num_exercised,
+/// num_total)`. Used to report `--coverage-guided` progress across `--rounds`.
+fn count_exercised_fcts(mod_rep: &NpmModule) -> (usize, usize) {
+    let fcts = mod_rep.get_fns();
+    let num_exercised = fcts
+        .values()
+        .filter(|fct| fct.get_sigs().iter().any(|sig| sig.get_call_res().is_some()))
+        .count();
+    (num_exercised, fcts.len())
 }
 
 /// Function to set up a toy filesystem that the generated tests can interact with.
-fn setup_toy_fs(path_start: &str) -> Result<Vec<PathBuf>, std::io::Error> {
+/// `toy_fs_dirs`/`toy_fs_files` come from the resolved `consts::Config` (see
+/// `resolve_generation_config`), defaulting to `consts::setup::TOY_FS_DIRS`/`TOY_FS_FILES`.
+fn setup_toy_fs(
+    path_start: &str,
+    toy_fs_dirs: &[String],
+    toy_fs_files: &[String],
+) -> Result<Vec<PathBuf>, std::io::Error> {
     let mut toy_fs_paths: Vec<PathBuf> = Vec::new();
 
-    for dir in &consts::setup::TOY_FS_DIRS {
+    for dir in toy_fs_dirs {
         let cur_path = PathBuf::from(path_start.to_owned() + "/" + dir);
         toy_fs_paths.push(cur_path.clone());
         if Path::new(&(cur_path)).exists() {
@@ -73,7 +289,7 @@ fn setup_toy_fs(path_start: &str) -> Result<Vec<PathBuf>, std::io::Error> {
             .create(&cur_path)?;
     }
 
-    for file in &consts::setup::TOY_FS_FILES {
+    for file in toy_fs_files {
         let cur_path = PathBuf::from(path_start.to_owned() + "/" + file);
         toy_fs_paths.push(cur_path.clone());
         if Path::new(&(cur_path)).is_file() {
@@ -85,8 +301,117 @@ fn setup_toy_fs(path_start: &str) -> Result<Vec<PathBuf>, std::io::Error> {
     Ok(toy_fs_paths)
 }
 
+/// Read a string-valued key out of a parsed `--config` table.
+fn config_str(table: &toml::value::Table, key: &str) -> Option<String> {
+    table.get(key)?.as_str().map(|s| s.to_owned())
+}
+
+/// Read a path-valued key (stored as a string in the config file) out of a parsed
+/// `--config` table.
+fn config_path(table: &toml::value::Table, key: &str) -> Option<PathBuf> {
+    config_str(table, key).map(PathBuf::from)
+}
+
+/// Read a boolean-flag key out of a parsed `--config` table. Missing or non-boolean is
+/// treated as `false`, not an error, since every `Opt` flag already defaults to `false`.
+fn config_bool(table: &toml::value::Table, key: &str) -> bool {
+    table.get(key).and_then(|v| v.as_bool()).unwrap_or(false)
+}
+
+/// Read an integer-valued key out of a parsed `--config` table.
+fn config_i64(table: &toml::value::Table, key: &str) -> Option<i64> {
+    table.get(key)?.as_integer()
+}
+
+/// Apply `table` (a config file loaded via `nessie::config::load_config_with_includes`) as
+/// fallback values for every field of `opt` that wasn't already given on the CLI, so CLI
+/// flags always take precedence over the config file (see `Opt::config`'s doc comment).
+/// Boolean flags are OR'd together instead, since `structopt` has no way to tell "flag not
+/// passed" from "flag explicitly false" once parsing is done.
+fn apply_config_defaults(opt: &mut Opt, table: &toml::value::Table) {
+    opt.lib_name = opt.lib_name.take().or_else(|| config_str(table, "lib_name"));
+    opt.lib_src_dir = opt.lib_src_dir.take().or_else(|| config_path(table, "lib_src_dir"));
+    opt.testing_dir = opt.testing_dir.take().or_else(|| config_path(table, "testing_dir"));
+    opt.module_import_code = opt
+        .module_import_code
+        .take()
+        .or_else(|| config_path(table, "module_import_code"));
+    opt.module_load_mode = opt
+        .module_load_mode
+        .take()
+        .or_else(|| config_str(table, "module_load_mode"));
+    opt.num_tests = opt
+        .num_tests
+        .take()
+        .or_else(|| config_i64(table, "num_tests").map(|n| n as i32));
+    opt.redo_discovery = opt.redo_discovery || config_bool(table, "redo_discovery");
+    opt.skip_testgen = opt.skip_testgen || config_bool(table, "skip_testgen");
+    opt.mined_data = opt.mined_data.take().or_else(|| config_path(table, "mined_data"));
+    opt.test_gen_mode = opt.test_gen_mode.take().or_else(|| config_str(table, "test_gen_mode"));
+    opt.seed = opt
+        .seed
+        .take()
+        .or_else(|| config_i64(table, "seed").map(|n| n as u64));
+    opt.snapshot_in = opt.snapshot_in.take().or_else(|| config_path(table, "snapshot_in"));
+    opt.snapshot_out = opt.snapshot_out.take().or_else(|| config_path(table, "snapshot_out"));
+    opt.persistent_runner = opt.persistent_runner || config_bool(table, "persistent_runner");
+    opt.report_format = opt.report_format.take().or_else(|| config_str(table, "report_format"));
+    opt.report_path = opt.report_path.take().or_else(|| config_path(table, "report_path"));
+    opt.extension_graph_path = opt
+        .extension_graph_path
+        .take()
+        .or_else(|| config_path(table, "extension_graph_path"));
+    opt.ext_strategy = opt.ext_strategy.take().or_else(|| config_str(table, "ext_strategy"));
+    opt.ts_declarations = opt
+        .ts_declarations
+        .take()
+        .or_else(|| config_path(table, "ts_declarations"));
+    opt.rounds = opt
+        .rounds
+        .take()
+        .or_else(|| config_i64(table, "rounds").map(|n| n as u32));
+    opt.coverage_guided = opt.coverage_guided || config_bool(table, "coverage_guided");
+    opt.watch = opt.watch || config_bool(table, "watch");
+    opt.corpus_file = opt.corpus_file.take().or_else(|| config_path(table, "corpus_file"));
+    opt.no_cache = opt.no_cache || config_bool(table, "no_cache");
+}
+
+/// Parse the optional `[generation]` sub-table of a `--config` file (see `Opt::config`)
+/// into `consts::Config`, the per-package-tunable generation values (weight factors,
+/// choice probabilities, toy filesystem layout) that replace the hardcoded `consts::*`
+/// equivalents -- keys left out of `[generation]` entirely keep `Config::default()`'s
+/// value. Validated via `Config::validate` here, so an out-of-range probability fails
+/// fast at startup instead of silently misbehaving for an entire campaign.
+fn resolve_generation_config(table: &toml::value::Table) -> consts::Config {
+    let config: consts::Config = match table.get("generation") {
+        Some(value) => consts::Config::deserialize(value.clone())
+            .unwrap_or_else(|e| panic!("invalid [generation] table in --config file: {:?}", e)),
+        None => consts::Config::default(),
+    };
+    config
+        .validate()
+        .unwrap_or_else(|e| panic!("invalid [generation] table in --config file: {:?}", e));
+    config
+}
+
 fn main() {
-    let opt = Opt::from_args();
+    let mut opt = Opt::from_args();
+
+    let mut generation_config = consts::Config::default();
+    if let Some(ref config_file) = opt.config.clone() {
+        let config_table = config::load_config_with_includes(config_file)
+            .unwrap_or_else(|e| panic!("failed to load --config {:?}: {:?}", config_file, e));
+        apply_config_defaults(&mut opt, &config_table);
+        generation_config = resolve_generation_config(&config_table);
+    }
+
+    let lib_name = opt
+        .lib_name
+        .clone()
+        .unwrap_or_else(|| panic!("--lib-name is required (via CLI flag or --config)"));
+    let num_tests = opt
+        .num_tests
+        .unwrap_or_else(|| panic!("--num-tests is required (via CLI flag or --config)"));
 
     let test_gen_mode = match opt.test_gen_mode {
         Some(ref mode_str) => TestGenMode::from_str(&mode_str)
@@ -94,9 +419,21 @@ fn main() {
         None => TestGenMode::Head, // default is the current newest version
     };
 
+    let ext_strategy = match opt.ext_strategy {
+        Some(ref strategy_str) => decisions::ExtensionStrategy::from_str(strategy_str)
+            .unwrap_or_else(|_| panic!("invalid extension strategy provided")),
+        None => decisions::ExtensionStrategy::UniformRandom, // previous, fixed behaviour
+    };
+
+    let module_load_mode = match opt.module_load_mode {
+        Some(ref mode_str) => ModuleLoadMode::from_str(mode_str)
+            .unwrap_or_else(|_| panic!("invalid module load mode provided")),
+        None => ModuleLoadMode::default(), // previous, fixed behaviour: `require`
+    };
+
     // different kinds of discovery files depending on the testgen mode we're using
     let discovery_filename =
-        "js_tools/".to_owned() + &opt.lib_name + "_discovery" + &test_gen_mode.label() + ".json";
+        "js_tools/".to_owned() + &lib_name + "_discovery" + &test_gen_mode.label() + ".json";
 
     let testing_dir = match &opt.testing_dir {
         Some(ref dir) => dir.clone().into_os_string().into_string().unwrap(),
@@ -106,8 +443,12 @@ fn main() {
     let test_dir_path = consts::setup::TEST_DIR_PATH;
 
     let toy_dir_base = &(testing_dir + "/" + test_dir_path + "/toy_fs_dir");
-    let toy_fs_paths =
-        setup_toy_fs(toy_dir_base).expect("Error creating toy filesystem for tests; bailing out.");
+    let toy_fs_paths = setup_toy_fs(
+        toy_dir_base,
+        &generation_config.toy_fs_dirs,
+        &generation_config.toy_fs_files,
+    )
+    .expect("Error creating toy filesystem for tests; bailing out.");
 
     let mined_data: Option<Vec<MinedNestingPairJSON>> =
         opt.mined_data.as_ref().map(|mined_data_file| {
@@ -121,7 +462,8 @@ fn main() {
     let mut testgen_db = decisions::TestGenDB::new(
         test_dir_path.to_string(),
         test_file_prefix.to_string(),
-        mined_data,
+        mined_data.clone(),
+        None, // TODO thread mined API call data through from the CLI once it's plumbed in
         opt.lib_src_dir.as_ref().map(|dir| {
             std::fs::canonicalize(dir.clone())
                 .unwrap_or_else(|_| panic!("invalid directory {:?} for api source code", dir))
@@ -129,100 +471,282 @@ fn main() {
                 .into_string()
                 .unwrap()
         }),
+        opt.seed,
+        ext_strategy,
+        generation_config.clone(),
     );
-    testgen_db.set_fs_strings(toy_fs_paths, toy_dir_base);
+    println!("Using test generation seed: {:?}", testgen_db.get_seed());
+    testgen_db.set_fs_strings(toy_fs_paths.clone(), toy_dir_base);
+    testgen_db.set_result_cache_enabled(!opt.no_cache);
 
     // if we don't have the source code of the api, install it so it can be `require`d
     if opt.lib_src_dir.is_none()
-        && !Path::new(&("node_modules/".to_owned() + &opt.lib_name)).exists()
+        && !Path::new(&("node_modules/".to_owned() + &lib_name)).exists()
     {
         Command::new("npm")
             .arg("install")
-            .arg(&opt.lib_name)
+            .arg(&lib_name)
             .output()
-            .unwrap_or_else(|_| panic!("failed to install {:?} to test", &opt.lib_name));
+            .unwrap_or_else(|_| panic!("failed to install {:?} to test", &lib_name));
     }
 
-    // if discovery file doesn't already exist
-    let (mut mod_rep, mut testgen_db) =
-        if (!Path::new(&discovery_filename).exists()) || opt.redo_discovery {
-            // is the api spec file already there? if so, don't run
-            let api_spec_filename = "js_tools/".to_owned() + &opt.lib_name + "_output.json";
-            let mut api_spec_args = vec!["lib_name=".to_owned() + &opt.lib_name];
-            if let Some(ref dir) = opt.lib_src_dir {
-                let lib_src_dir_name = dir.clone().into_os_string().into_string().unwrap();
-                api_spec_args.push("lib_src_dir=".to_owned() + &lib_src_dir_name);
-            }
-            if let Some(ref import_file) = opt.module_import_code {
-                let import_file_name = import_file.clone().into_os_string().into_string().unwrap();
-                api_spec_args.push("import_code_file=".to_owned() + &import_file_name);
-            }
+    if opt.watch && opt.lib_src_dir.is_none() {
+        panic!("--watch requires --lib-src-dir (nothing to watch for an installed npm package)");
+    }
+    let mut prev_src_mtimes = opt
+        .lib_src_dir
+        .as_ref()
+        .map(|dir| collect_source_mtimes(dir))
+        .unwrap_or_default();
+
+    let mut watch_iteration: u64 = 0;
+    loop {
+        watch_iteration += 1;
+        let iteration_start = std::time::Instant::now();
+        // only the first iteration respects `--redo-discovery` as given; every iteration
+        // `--watch` loops back to after that is, by definition, in response to a source
+        // change, so it always needs a fresh discovery pass
+        let force_redo_discovery = opt.watch && watch_iteration > 1;
 
-            if !Path::new(&api_spec_filename).exists() {
-                Command::new("./get_api_specs.sh")
-                    .args(api_spec_args)
-                    .output()
-                    .unwrap_or_else(|_| {
-                        panic!(
-                            "failed to execute API info gathering process for {:?}",
-                            &opt.lib_name
-                        )
-                    });
-                println!("Generating API spec");
+        // if discovery file doesn't already exist
+        //
+        // note: this reassigns (rather than shadows) the `testgen_db` declared before the
+        // watch loop, so its learned weights/progress persist across `--watch` iterations;
+        // `mod_rep` is always rebuilt fresh each iteration instead, since that's the whole
+        // point of re-running discovery after a source change.
+        let mut mod_rep;
+        (mod_rep, testgen_db) = if (!Path::new(&discovery_filename).exists())
+            || opt.redo_discovery
+            || force_redo_discovery
+        {
+            // if we got to this point, we successfully got the API and can construct the
+            // module object -- either from TypeScript declarations, if given (skipping the
+            // `api_info` phase's JS-based listing script entirely), or from that script's
+            // output as before
+            let mut mod_rep = if let Some(ref ts_declarations) = opt.ts_declarations {
+                match NpmModule::from_typescript_spec(
+                    ts_declarations.clone(),
+                    lib_name.clone(),
+                    opt.module_import_code.clone(),
+                    module_load_mode,
+                ) {
+                    Ok(mod_rep) => mod_rep,
+                    _ => panic!("Error reading the module spec from the TypeScript declarations"),
+                }
             } else {
-                println!(
-                    "API spec file exists, reading from {:?}",
-                    &api_spec_filename
-                );
-            }
+                // is the api spec file already there? if so, don't run
+                let api_spec_filename = "js_tools/".to_owned() + &lib_name + "_output.json";
+                let mut api_spec_args = vec!["lib_name=".to_owned() + &lib_name];
+                if let Some(ref dir) = opt.lib_src_dir {
+                    let lib_src_dir_name = dir.clone().into_os_string().into_string().unwrap();
+                    api_spec_args.push("lib_src_dir=".to_owned() + &lib_src_dir_name);
+                }
+                if let Some(ref import_file) = opt.module_import_code {
+                    let import_file_name =
+                        import_file.clone().into_os_string().into_string().unwrap();
+                    api_spec_args.push("import_code_file=".to_owned() + &import_file_name);
+                }
+
+                // a `--watch` re-discovery pass always needs a fresh API spec, since it's
+                // the whole point of re-running -- a cached one from before the source
+                // change would just reproduce the stale `mod_rep` we already have
+                if !Path::new(&api_spec_filename).exists() || force_redo_discovery {
+                    Command::new("./get_api_specs.sh")
+                        .args(api_spec_args)
+                        .output()
+                        .unwrap_or_else(|_| {
+                            panic!(
+                                "failed to execute API info gathering process for {:?}",
+                                &lib_name
+                            )
+                        });
+                    println!("Generating API spec");
+                } else {
+                    println!(
+                        "API spec file exists, reading from {:?}",
+                        &api_spec_filename
+                    );
+                }
 
-            // if we got to this point, we successfully got the API and can construct the module object
-            let mut mod_rep = match NpmModule::from_api_spec(
-                PathBuf::from(&api_spec_filename),
-                opt.lib_name.clone(),
-                opt.module_import_code,
-            ) {
-                Ok(mod_rep) => mod_rep,
-                _ => panic!("Error reading the module spec from the api_info file"),
+                match NpmModule::from_api_spec(
+                    PathBuf::from(&api_spec_filename),
+                    lib_name.clone(),
+                    opt.module_import_code.clone(),
+                    module_load_mode,
+                ) {
+                    Ok(mod_rep) => mod_rep,
+                    _ => panic!("Error reading the module spec from the api_info file"),
+                }
             };
             if test_gen_mode.has_discovery() {
                 (mod_rep, testgen_db) = legacy::discovery::run_discovery_phase(mod_rep, testgen_db)
                     .expect("Error running discovery phase: {:?}");
-                let mut disc_file = std::fs::File::create(&discovery_filename)
-                    .expect("Error creating discovery JSON file");
-                // print discovery to a file
-                disc_file
-                    .write_all(format!("{:?}", mod_rep).as_bytes())
+                mod_rep
+                    .write_discovery_file(&PathBuf::from(&discovery_filename))
                     .expect("Error writing to discovery JSON file");
             }
             (mod_rep, testgen_db)
         } else {
             (
-                NpmModule::from_api_spec(
-                    PathBuf::from(&discovery_filename),
-                    opt.lib_name.clone(),
-                    opt.module_import_code,
-                )
-                .expect("Error reading the discovery info file"),
+                NpmModule::from_discovery_file(&PathBuf::from(&discovery_filename))
+                    .expect("Error reading the discovery info file"),
                 testgen_db,
             )
         };
 
-    // at this point, the mod_rep has the results from the API listing phase, or
-    // a previously run's API discovery if applicable
+        // at this point, the mod_rep has the results from the API listing phase, or
+        // a previously run's API discovery if applicable
 
-    let num_tests = opt.num_tests;
-    if !opt.skip_testgen {
-        run_testgen_phase(&mut mod_rep, &mut testgen_db, num_tests, test_gen_mode)
-            .expect("Error running test generation phase: {:?}");
-    } else {
-        println!("`skip-testgen` specified: Skipping test generation phase.")
-    }
+        // if a previous snapshot was specified, warm-start the generator from it instead of
+        // the blank state set up above (now that `mod_rep` is available to validate it against)
+        if let Some(ref snapshot_path) = opt.snapshot_in {
+            testgen_db = decisions::TestGenDB::from_snapshot(
+                snapshot_path,
+                &mod_rep,
+                test_dir_path.to_string(),
+                test_file_prefix.to_string(),
+                None, // mined data isn't part of the snapshot; re-supply via --mined-data if needed
+                None,
+                opt.lib_src_dir.as_ref().map(|dir| {
+                    std::fs::canonicalize(dir.clone())
+                        .unwrap_or_else(|_| panic!("invalid directory {:?} for api source code", dir))
+                        .into_os_string()
+                        .into_string()
+                        .unwrap()
+                }),
+                ext_strategy,
+                generation_config.clone(),
+            )
+            .unwrap_or_else(|_| panic!("failed to load generator snapshot from {:?}", snapshot_path));
+            testgen_db.set_fs_strings(toy_fs_paths.clone(), toy_dir_base);
+            testgen_db.set_result_cache_enabled(!opt.no_cache);
+        }
 
-    let mut disc_file =
-        std::fs::File::create(&discovery_filename).expect("Error creating API discovery JSON file");
-    // print discovery to a file
-    disc_file
-        .write_all(format!("{:?}", mod_rep).as_bytes())
-        .expect("Error writing to API discovery JSON file");
+        let mut reporter: Box<dyn reporters::TestSuiteReporter> =
+            match (&opt.report_format, &opt.report_path) {
+                (Some(format_str), Some(path)) => {
+                    let format = ReportFormat::from_str(format_str)
+                        .unwrap_or_else(|_| panic!("invalid report format provided"));
+                    reporters::build_reporter(
+                        format,
+                        &path.clone().into_os_string().into_string().unwrap(),
+                        lib_name.clone(),
+                    )
+                    .unwrap_or_else(|_| panic!("failed to create {:?} reporter at {:?}", format, path))
+                }
+                (None, None) => Box::new(reporters::NoopReporter),
+                _ => panic!("--report-format and --report-path must be given together"),
+            };
+
+        if !opt.skip_testgen {
+            if let Some(ref corpus_file) = opt.corpus_file {
+                testgen::replay_corpus(
+                    &mut mod_rep,
+                    &lib_name,
+                    corpus_file,
+                    test_dir_path.to_string(),
+                    test_file_prefix.to_string(),
+                    mined_data.clone(),
+                    None, // TODO thread mined API call data through once it's plumbed in, see above
+                    opt.lib_src_dir.as_ref().map(|dir| {
+                        std::fs::canonicalize(dir.clone())
+                            .unwrap_or_else(|_| panic!("invalid directory {:?} for api source code", dir))
+                            .into_os_string()
+                            .into_string()
+                            .unwrap()
+                    }),
+                    ext_strategy,
+                    testgen_db.get_config().clone(),
+                    &test_gen_mode,
+                    reporter.as_mut(),
+                )
+                .unwrap_or_else(|e| panic!("Error replaying corpus file {:?}: {:?}", corpus_file, e));
+            }
+
+            let rounds = opt.rounds.unwrap_or(1).max(1);
+            for round in 1..=rounds {
+                let round_start = std::time::Instant::now();
+                // re-run discovery before every round after the first, so signatures/access
+                // paths the previous round's testgen found feed back into this round's search
+                if round > 1 && test_gen_mode.has_discovery() {
+                    (mod_rep, testgen_db) = legacy::discovery::run_discovery_phase(mod_rep, testgen_db)
+                        .expect("Error running discovery phase: {:?}");
+                }
+                let tests_before = testgen_db.get_cur_test_index();
+                run_testgen_phase(
+                    &mut mod_rep,
+                    &mut testgen_db,
+                    num_tests,
+                    &test_gen_mode,
+                    opt.persistent_runner,
+                    opt.corpus_file.as_ref().map(|path| (path, lib_name.as_str())),
+                    reporter.as_mut(),
+                )
+                    .expect("Error running test generation phase: {:?}");
+                let new_tests = testgen_db.get_cur_test_index() - tests_before;
+                println!(
+                    "Round {:?}/{:?}: generated {:?} new tests in {:?}",
+                    round,
+                    rounds,
+                    new_tests,
+                    round_start.elapsed()
+                );
+                if opt.coverage_guided {
+                    let (num_exercised, num_total) = count_exercised_fcts(&mod_rep);
+                    println!(
+                        "Round {:?}/{:?}: {:?}/{:?} discovered functions exercised so far",
+                        round, rounds, num_exercised, num_total
+                    );
+                }
+            }
+        } else {
+            println!("`skip-testgen` specified: Skipping test generation phase.")
+        }
+        reporter
+            .finish()
+            .unwrap_or_else(|_| panic!("failed to finalize test-suite report"));
+
+        if let (Some(format_str), Some(path)) = (&opt.report_format, &opt.report_path) {
+            let format = ReportFormat::from_str(format_str)
+                .unwrap_or_else(|_| panic!("invalid report format provided"));
+            let function_summary_path =
+                path.clone().into_os_string().into_string().unwrap() + ".functions";
+            reporters::write_function_summary_report(&mod_rep, format, &function_summary_path)
+                .unwrap_or_else(|_| {
+                    panic!(
+                        "failed to write function-summary report to {:?}",
+                        function_summary_path
+                    )
+                });
+        }
+
+        if let Some(ref graph_path) = opt.extension_graph_path {
+            let graph_path_str = graph_path.clone().into_os_string().into_string().unwrap();
+            graphviz::write_extension_graph_dot(&testgen_db, &graph_path_str).unwrap_or_else(|_| {
+                panic!("failed to write extension graph to {:?}", graph_path_str)
+            });
+        }
+
+        if let Some(ref snapshot_path) = opt.snapshot_out {
+            testgen_db
+                .write_snapshot(snapshot_path)
+                .unwrap_or_else(|_| panic!("failed to write generator snapshot to {:?}", snapshot_path));
+        }
+
+        mod_rep
+            .write_discovery_file(&PathBuf::from(&discovery_filename))
+            .expect("Error writing to API discovery JSON file");
+
+        println!(
+            "Watch iteration {:?} finished in {:?}",
+            watch_iteration,
+            iteration_start.elapsed()
+        );
+        if !opt.watch {
+            break;
+        }
+        let lib_src_dir = opt.lib_src_dir.as_ref().unwrap();
+        println!("--watch: waiting for changes under {:?}...", lib_src_dir);
+        prev_src_mtimes = wait_for_source_change(lib_src_dir, &prev_src_mtimes);
+    }
 }
@@ -1,17 +1,15 @@
 //! Representation and use of mined data (used as a seed for the test generator).
-//!
-//! TODO: in the improved version of the test generator, we're going to mine
-//! much more information -- the current struct representing a mined data point
-//! only represents nesting relationships.
-//! This is going to get totally overhauled.
 
 use crate::errors::*;
 use crate::functions::{ArgType, ArgVal, FunctionArgument, FunctionSignature};
 use crate::module_reps::{AccessPathModuleCentred, FieldNameType};
 use crate::tests::FunctionCall;
 
+use rand::distributions::WeightedIndex;
+use rand::prelude::Distribution;
+use rand::rngs::SmallRng;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::path::PathBuf;
 use std::str::FromStr;
@@ -44,12 +42,181 @@ use std::str::FromStr;
             }
         ]
     },
+
+    Newer mined data adds an explicit "dataflow_edges" list alongside "inner_params", e.g.
+    `"dataflow_edges": [{"source": {"OuterArg": 0}, "inner_arg_pos": 1}]` for the pair above,
+    or `{"source": "OuterPromiseResolve", "inner_arg_pos": 0}` for a value flowing in from the
+    outer call's settled promise rather than a plain argument. Pairs mined before this field
+    existed simply omit it -- see `get_rel_mined_data_nested_extensions`.
+
+    The whole file can also be wrapped with a `spec_version` header instead of being a bare
+    top-level array, e.g. `{"spec_version": "0.1.0", "data": [ ...the pairs above... ]}` -- see
+    `SpecVersion`. Files with no header (a bare array, as above) are read as `SpecVersion::LEGACY`.
 */
+/// A value produced somewhere in or around the outer call, that a `DataflowEdge` can source
+/// from. Generalizes the old convention of an `inner_params` entry's `ident` happening to be
+/// named `outer_arg_N` (which could only ever mean "the outer call's Nth argument, forwarded
+/// unchanged") into an explicit, typed origin -- including origins, like a callback's own
+/// parameters or a settled promise, that string convention had no way to express at all.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum DataflowSource {
+    /// the outer call's own argument at this position.
+    OuterArg(usize),
+    /// the outer call's return value.
+    OuterReturn,
+    /// one of the outer call's callback argument(s)' own parameters, indexed into the flat
+    /// list of all parameters across however many callback arguments the outer call has (see
+    /// `FunctionSignature::get_all_cb_args_vals`) -- this isn't tied to a single callback
+    /// position, so it covers an outer call with more than one callback argument just as well
+    /// as the single-callback case.
+    OuterCallbackParam(usize),
+    /// the resolved value of a promise the outer call returned (`promise.then(val => ...)`).
+    OuterPromiseResolve,
+    /// the rejection value of a promise the outer call returned (`promise.catch(err => ...)`).
+    OuterPromiseReject,
+}
+
+/// One dataflow edge mined from a nesting example: a value produced at `source` (relative to
+/// the outer call) is passed as the inner call's argument at `inner_arg_pos`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct DataflowEdge {
+    pub source: DataflowSource,
+    pub inner_arg_pos: usize,
+}
+
+/// Semantic version (`major.minor.patch`) for the schema of a mined-data file, serialized as
+/// a plain `"1.0.0"`-style string. Mined data is produced by a separate (external) mining
+/// pipeline, so the generator has no control over when a corpus was produced -- this lets it
+/// tell, at load time, whether it actually understands a given file's shape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SpecVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl SpecVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Version assumed for mined-data files written before `spec_version` existed at all (see
+    /// `MinedDataFileJSON`'s `#[serde(default)]`), so they keep loading unchanged.
+    pub const LEGACY: Self = Self::new(0, 1, 0);
+
+    /// Is a file declaring this version safe to read, given the generator `required`s some
+    /// version? Only the major version gates compatibility: a minor/patch difference is
+    /// assumed to be an additive, backwards-compatible change (e.g. `dataflow_edges` was added
+    /// to `MinedNestingPairJSON` behind a `#[serde(default)]`), while a major bump signals an
+    /// actual breaking schema change.
+    pub fn is_compatible(&self, required: &Self) -> bool {
+        self.major >= required.major
+    }
+}
+
+impl std::fmt::Display for SpecVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl FromStr for SpecVersion {
+    type Err = DFError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || DFError::InvalidMinedData(format!("invalid spec_version {:?}", s));
+        let parts: Vec<&str> = s.split('.').collect();
+        if let [major, minor, patch] = parts[..] {
+            Ok(Self::new(
+                major.parse().map_err(|_| invalid())?,
+                minor.parse().map_err(|_| invalid())?,
+                patch.parse().map_err(|_| invalid())?,
+            ))
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+impl Serialize for SpecVersion {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for SpecVersion {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Self::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// A mined-data file, as read off disk: either the current format (a `spec_version` header
+/// alongside the `data` array), or a legacy file that's just a bare JSON array of `T` (in
+/// which case its version defaults to `SpecVersion::LEGACY`). Untagged so both shapes parse
+/// with the same `serde_json::from_str` call.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum MinedDataFileJSON<T> {
+    Versioned {
+        #[serde(default = "default_spec_version")]
+        spec_version: SpecVersion,
+        data: Vec<T>,
+    },
+    Legacy(Vec<T>),
+}
+
+fn default_spec_version() -> SpecVersion {
+    SpecVersion::LEGACY
+}
+
+/// Read a mined-data file of `T`s, enforcing `SpecVersion` compatibility: parses either the
+/// versioned `{"spec_version": ..., "data": [...]}` shape or a legacy bare array (defaulting
+/// to `SpecVersion::LEGACY`), and rejects the file if its major version is older than
+/// `required`.
+fn read_versioned_mined_data<T: for<'de> Deserialize<'de>>(
+    path: &PathBuf,
+    required: &SpecVersion,
+) -> Result<Vec<T>, DFError> {
+    let file_conts_string =
+        std::fs::read_to_string(path).map_err(|_| DFError::MinedDataFileError)?;
+
+    let mined_data_file: MinedDataFileJSON<T> =
+        serde_json::from_str(&file_conts_string).map_err(|_| DFError::MinedDataFileError)?;
+
+    let (version, data) = match mined_data_file {
+        MinedDataFileJSON::Versioned { spec_version, data } => (spec_version, data),
+        MinedDataFileJSON::Legacy(data) => (SpecVersion::LEGACY, data),
+    };
+
+    if !version.is_compatible(required) {
+        return Err(DFError::IncompatibleMinedDataVersion(
+            version.to_string(),
+            required.to_string(),
+        ));
+    }
+
+    Ok(data)
+}
+
+/// `spec_version` this build of the generator requires of mined nesting-pair data (see
+/// `MinedNestingPairJSON::list_from_file`).
+const REQUIRED_NESTING_PAIR_SPEC_VERSION: SpecVersion = SpecVersion::LEGACY;
+
+/// `spec_version` this build of the generator requires of mined API-call data (see
+/// `MinedAPICallJSON::list_from_file`).
+const REQUIRED_API_CALL_SPEC_VERSION: SpecVersion = SpecVersion::LEGACY;
+
 /// Representation of a mined nesting pair.
 /// Currently the only information represented is the package and names of the
 /// functions in the nesting, limited information on the types of the arguments,
-/// and any dataflow between other arguments to the outer function, and arguments
-/// to the inner call (nested in the callback).
+/// and the dataflow edges between the outer call (its arguments, return value, callback
+/// parameters, or settled promise) and the arguments of the inner call (nested in the
+/// callback).
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MinedNestingPairJSON {
     /// Name of the module the outer function belongs to.
@@ -64,6 +231,13 @@ pub struct MinedNestingPairJSON {
     inner_fct: String,
     /// Arguments to the inner function.
     inner_params: Vec<MinedParam>,
+    /// Explicit dataflow edges from the outer call into the inner call's arguments. Absent
+    /// (or empty) in mined data written before this field existed -- in that case,
+    /// `get_rel_mined_data_nested_extensions` falls back to deriving the same direct
+    /// outer-arg-to-inner-arg edges from `inner_params`' old `ident: "outer_arg_N"`
+    /// convention, so older mined-data files keep working unchanged.
+    #[serde(default)]
+    dataflow_edges: Vec<DataflowEdge>,
 }
 
 /// Database of mined data, indexed by the library associated with the outer function in the nested pair.
@@ -72,23 +246,126 @@ pub type LibMinedData = HashMap<String, Vec<MinedNestingPairJSON>>;
 /// Database of mined call data, indexed by the library associated with the function being called.
 pub type LibMinedCallData = HashMap<String, Vec<MinedAPICall>>;
 
+/// Composite key a `MinedNestingIndex` is keyed on: the outer package, the outer function
+/// name, and the outer function's arity.
+type NestingIndexKey = (String, String, usize);
+
+/// Index over mined nesting pairs, keyed by `(outer_pkg, outer_fct, outer_arity)` rather than
+/// just `outer_pkg` (as `LibMinedData` is), so `get_rel_mined_data_nested_extensions` doesn't
+/// have to linearly re-filter every pair mined for a package on every single outer call --
+/// just the (typically much smaller) bucket that could plausibly nest under this exact
+/// function.
+#[derive(Debug, Default, Clone)]
+pub struct MinedNestingIndex {
+    by_outer: HashMap<NestingIndexKey, Vec<MinedNestingPairJSON>>,
+}
+
+impl MinedNestingIndex {
+    /// Build an index from a flat list of mined nesting pairs (e.g. the output of
+    /// `MinedNestingPairJSON::list_from_file`).
+    pub fn from_list(pairs: Vec<MinedNestingPairJSON>) -> Self {
+        let mut by_outer: HashMap<NestingIndexKey, Vec<MinedNestingPairJSON>> = HashMap::new();
+        for pair in pairs {
+            let key = (
+                pair.get_outer_pkg(),
+                pair.outer_fct.clone(),
+                pair.outer_params.len(),
+            );
+            by_outer.entry(key).or_insert_with(Vec::new).push(pair);
+        }
+        Self { by_outer }
+    }
+
+    /// Fully-specified lookup: pairs whose outer package, function name, and arity all match.
+    pub fn get(&self, outer_pkg: &str, outer_fct: &str, outer_arity: usize) -> &[MinedNestingPairJSON] {
+        self.by_outer
+            .get(&(outer_pkg.to_string(), outer_fct.to_string(), outer_arity))
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    /// Partial lookup ignoring arity: every pair whose outer package and function name match,
+    /// regardless of how many arguments the outer call has.
+    pub fn get_by_fct(&self, outer_pkg: &str, outer_fct: &str) -> Vec<&MinedNestingPairJSON> {
+        self.by_outer
+            .iter()
+            .filter(|((pkg, fct, _), _)| pkg == outer_pkg && fct == outer_fct)
+            .flat_map(|(_, pairs)| pairs.iter())
+            .collect()
+    }
+
+    /// Wildcard lookup: every pair whose outer package matches, regardless of function name or
+    /// arity.
+    pub fn get_by_pkg(&self, outer_pkg: &str) -> Vec<&MinedNestingPairJSON> {
+        self.by_outer
+            .iter()
+            .filter(|((pkg, _, _), _)| pkg == outer_pkg)
+            .flat_map(|(_, pairs)| pairs.iter())
+            .collect()
+    }
+}
+
+/// Composite key a `MinedAPICallIndex` is keyed on: the package and function name (see
+/// `MinedAPICall::get_fct_name`).
+type ApiCallIndexKey = (String, String);
+
+/// Index over mined API calls, keyed by `(pkg, fct_name)` rather than just `pkg` (as
+/// `LibMinedCallData` is), so looking up mined call signatures for a specific function doesn't
+/// require scanning every call mined for the whole library.
+#[derive(Debug, Default, Clone)]
+pub struct MinedAPICallIndex {
+    by_fct: HashMap<ApiCallIndexKey, Vec<MinedAPICall>>,
+}
+
+impl MinedAPICallIndex {
+    /// Build an index from a flat list of mined API calls (e.g. the output of
+    /// `MinedAPICall::list_from_file`).
+    pub fn from_list(calls: Vec<MinedAPICall>) -> Self {
+        let mut by_fct: HashMap<ApiCallIndexKey, Vec<MinedAPICall>> = HashMap::new();
+        for call in calls {
+            let key = (call.get_pkg(), call.get_fct_name());
+            by_fct.entry(key).or_insert_with(Vec::new).push(call);
+        }
+        Self { by_fct }
+    }
+
+    /// Fully-specified lookup: calls whose package and function name both match.
+    pub fn get(&self, pkg: &str, fct_name: &str) -> &[MinedAPICall] {
+        self.by_fct
+            .get(&(pkg.to_string(), fct_name.to_string()))
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    /// Wildcard lookup: every call whose package matches, regardless of function name.
+    pub fn get_by_pkg(&self, pkg: &str) -> Vec<&MinedAPICall> {
+        self.by_fct
+            .iter()
+            .filter(|((p, _), _)| p == pkg)
+            .flat_map(|(_, calls)| calls.iter())
+            .collect()
+    }
+}
+
 impl MinedNestingPairJSON {
     /// Read a file (output from the data mining), that has a list of JSON representations
     /// of mined nesting pairs.
     /// Return the corresponding list, or an error if the file is malformed.
     pub fn list_from_file(path: &PathBuf) -> Result<Vec<Self>, DFError> {
-        let file_conts = std::fs::read_to_string(path);
-        let file_conts_string = match file_conts {
-            Ok(fcs) => fcs,
-            _ => return Err(DFError::MinedDataFileError),
-        };
-
-        let mined_data_rep: Vec<Self> = match serde_json::from_str(&file_conts_string) {
-            Ok(rep) => rep,
-            Err(_) => return Err(DFError::MinedDataFileError),
-        };
+        read_versioned_mined_data(path, &REQUIRED_NESTING_PAIR_SPEC_VERSION)
+    }
 
-        Ok(mined_data_rep)
+    /// Like `list_from_file`, but additionally checks the file against its `.sig` integrity
+    /// sidecar (see `crate::integrity`) before returning -- fails if the sidecar is missing,
+    /// the recomputed canonical digest doesn't match, or (when `verifying_key` is given) the
+    /// sidecar's signature doesn't check out.
+    pub fn list_from_file_verified(
+        path: &PathBuf,
+        verifying_key: Option<&ed25519_dalek::VerifyingKey>,
+    ) -> Result<Vec<Self>, DFError> {
+        let data = Self::list_from_file(path)?;
+        crate::integrity::verify_sidecar(&data, path, verifying_key)?;
+        Ok(data)
     }
 
     /// Turn a list of mined nesting pairs into a map of lists indexed by the library
@@ -203,10 +480,14 @@ impl std::fmt::Display for MinedParam {
 pub struct MinedDataNestedExtension {
     /// Name of the inner function.
     pub fct_name: String,
+    /// Name of the package the inner function originates from -- may differ from the outer
+    /// function's package (e.g. a `fs.realpath` nesting into `q.reject`), so the generator
+    /// knows whether it needs to `require`/import a different package for this call.
+    pub inner_pkg: String,
     /// Signature of the inner function.
     pub sig: FunctionSignature,
-    /// List of pairs of: position of argument in outer function call, passed to position in inner call.
-    pub outer_to_inner_dataflow: Vec<(usize, usize)>,
+    /// Dataflow edges from the outer function call into this inner call's arguments.
+    pub outer_to_inner_dataflow: Vec<DataflowEdge>,
 }
 
 impl TryFrom<&MinedParam> for FunctionArgument {
@@ -237,10 +518,20 @@ impl TryFrom<&Vec<MinedParam>> for FunctionSignature {
 /// Given a list of mined data pairs and an outer function call to extend,
 /// return a list of all valid nested extensions from the mined data
 /// (empty list if none are valid).
+///
+/// `pkgs_under_test` is the set of packages a nested call is allowed to originate from (both
+/// the outer and, potentially different, inner function) -- not just the single package the
+/// outer call belongs to, so a cross-package nesting like `fs.realpath` calling `q.reject` is
+/// kept rather than discarded, as long as both `fs` and `q` are in the set.
+///
+/// `mined_data` is looked up by `(outer_pkg, outer_fct, outer_arity)` via `MinedNestingIndex`
+/// rather than linearly scanned, since the outer function's package, name, and arity are all
+/// already known here -- only the remaining per-candidate checks (inner package membership,
+/// inner signature parsing) still need to run per candidate.
 pub fn get_rel_mined_data_nested_extensions(
     outer_fct: Option<&FunctionCall>,
-    pkg_name: &String,
-    mined_data: &[MinedNestingPairJSON],
+    pkgs_under_test: &HashSet<String>,
+    mined_data: &MinedNestingIndex,
 ) -> Vec<MinedDataNestedExtension> {
     if outer_fct.is_none() {
         return Vec::new();
@@ -253,43 +544,44 @@ pub fn get_rel_mined_data_nested_extensions(
     let outer_arg_len = outer_fct.sig.get_arg_list().len();
     let outer_fct_name = outer_fct.get_name();
 
-    mined_data
+    pkgs_under_test
         .iter()
+        .flat_map(|outer_pkg| mined_data.get(outer_pkg, &outer_fct_name, outer_arg_len).iter())
         .filter_map(|mined_pair| {
             let inner_fct_sig = FunctionSignature::try_from(&mined_pair.inner_params);
-            // note: right now we only support nestings from functions from the same package
-            // for a nesting to be a valid for extending the `outer_fct`:
-            // -- outer package matches origin package of function to be nested extended
-            // -- inner package matches origin package of function to be nested extended
-            // -- outer function matches the function being nested extended
-            // -- outer function signature has compatible signature (i.e., same number of arguments)
-            //    as the function being nested extended
-            // -- inner function signature is properly parsed from the mined data
-            if &mined_pair.get_outer_pkg() == pkg_name
-                && &mined_pair.get_inner_pkg() == pkg_name
-                && mined_pair.outer_fct == outer_fct_name
-                && mined_pair.outer_params.len() == outer_arg_len
-                && inner_fct_sig.is_ok()
-            {
+            // the outer package/function/arity already matched to land in this bucket --
+            // all that's left to check is that the inner package is also under test, and
+            // that the inner function signature is properly parsed from the mined data
+            if pkgs_under_test.contains(&mined_pair.get_inner_pkg()) && inner_fct_sig.is_ok() {
                 let inner_fct_name = mined_pair.inner_fct.clone();
                 let inner_fct_sig = inner_fct_sig.unwrap();
 
-                let outer_to_inner_dataflow = mined_pair.inner_params
-                    .iter()
-                    .enumerate()
-                    .filter_map(|(pos, inner_param)| {
-                        if let Some(var_name) = &inner_param.ident && var_name.starts_with("outer_arg_") {
-                            // get the string after the last _ and convert to a usize
-                            let (_, outer_pos) = var_name.rsplit_once('_').unwrap();
-                            Some((outer_pos.parse::<usize>().unwrap(), pos))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<Vec<(usize, usize)>>();
+                let outer_to_inner_dataflow = if !mined_pair.dataflow_edges.is_empty() {
+                    mined_pair.dataflow_edges.clone()
+                } else {
+                    // older mined data with no `dataflow_edges`: fall back to deriving the
+                    // same direct-forwarding edges from the `ident: "outer_arg_N"` convention
+                    mined_pair.inner_params
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(pos, inner_param)| {
+                            if let Some(var_name) = &inner_param.ident && var_name.starts_with("outer_arg_") {
+                                // get the string after the last _ and convert to a usize
+                                let (_, outer_pos) = var_name.rsplit_once('_').unwrap();
+                                Some(DataflowEdge {
+                                    source: DataflowSource::OuterArg(outer_pos.parse::<usize>().unwrap()),
+                                    inner_arg_pos: pos,
+                                })
+                            } else {
+                                None
+                            }
+                        })
+                        .collect::<Vec<DataflowEdge>>()
+                };
 
                 Some(MinedDataNestedExtension {
                     fct_name: inner_fct_name,
+                    inner_pkg: mined_pair.get_inner_pkg(),
                     sig: inner_fct_sig,
                     outer_to_inner_dataflow,
                 })
@@ -300,6 +592,36 @@ pub fn get_rel_mined_data_nested_extensions(
         .collect::<Vec<MinedDataNestedExtension>>()
 }
 
+/// Choose a single mined nested extension to generate next, out of all the ones valid for
+/// `outer_fct` (see `get_rel_mined_data_nested_extensions`). Rather than picking uniformly
+/// at random among the candidates, weight the choice by how often each inner function name
+/// recurs among them, so an inner function mined with many different nesting examples is
+/// more likely to be picked than a one-off pair -- without needing a separate pass to dedup
+/// or rank function names ourselves.
+/// Returns `None` if there are no valid candidates (including whenever
+/// `get_rel_mined_data_nested_extensions` itself would, e.g. no mined data for this package,
+/// or an outer function with no callback argument to nest in).
+pub fn choose_corresponding_mined_data(
+    outer_fct: Option<&FunctionCall>,
+    pkgs_under_test: &HashSet<String>,
+    mined_data: &MinedNestingIndex,
+    rng: &mut SmallRng,
+) -> Option<MinedDataNestedExtension> {
+    let candidates = get_rel_mined_data_nested_extensions(outer_fct, pkgs_under_test, mined_data);
+    if candidates.is_empty() {
+        return None;
+    }
+    let mut fct_name_freq: HashMap<&str, usize> = HashMap::new();
+    for candidate in &candidates {
+        *fct_name_freq.entry(candidate.fct_name.as_str()).or_insert(0) += 1;
+    }
+    let weights = candidates
+        .iter()
+        .map(|candidate| fct_name_freq[candidate.fct_name.as_str()]);
+    let dist = WeightedIndex::new(weights).ok()?;
+    Some(candidates[dist.sample(rng)].clone())
+}
+
 /*
     Example mined data for single API call w/ at least one statically available argument.
 
@@ -329,21 +651,85 @@ impl MinedAPICallJSON {
     /// of mined API calls.
     /// Return the corresponding list, or an error if the file is malformed.
     pub fn list_from_file(path: &PathBuf) -> Result<Vec<Self>, DFError> {
-        let file_conts = std::fs::read_to_string(path);
-        let file_conts_string = match file_conts {
-            Ok(fcs) => fcs,
-            _ => return Err(DFError::MinedDataFileError),
-        };
-
-        let mined_data_rep: Vec<Self> = match serde_json::from_str(&file_conts_string) {
-            Ok(rep) => rep,
-            Err(_) => {
-                return Err(DFError::MinedDataFileError);
-            }
-        };
+        read_versioned_mined_data(path, &REQUIRED_API_CALL_SPEC_VERSION)
+    }
+
+    /// Like `list_from_file`, but additionally checks the file against its `.sig` integrity
+    /// sidecar (see `crate::integrity`) before returning -- fails if the sidecar is missing,
+    /// the recomputed canonical digest doesn't match, or (when `verifying_key` is given) the
+    /// sidecar's signature doesn't check out.
+    pub fn list_from_file_verified(
+        path: &PathBuf,
+        verifying_key: Option<&ed25519_dalek::VerifyingKey>,
+    ) -> Result<Vec<Self>, DFError> {
+        let data = Self::list_from_file(path)?;
+        crate::integrity::verify_sidecar(&data, path, verifying_key)?;
+        Ok(data)
+    }
+}
 
-        Ok(mined_data_rep)
+/// Split a mined `"(arg0,arg1,...)"` signature string (as found in `sig_with_types`/
+/// `sig_with_values`) into its top-level comma-separated argument slices. Unlike a naive
+/// `split(',')`, this walks the string character by character, tracking nesting depth for
+/// `(`/`[`/`{` and single/double quote state, so a comma inside a nested object literal
+/// (`{a:1,b:2}`), array (`[1,2,3]`), or quoted string (`'a,b'`) doesn't get mistaken for an
+/// argument separator. Returns `DFError::InvalidMinedData` if the signature isn't
+/// parenthesized, or its brackets/quotes are unbalanced.
+fn split_mined_sig_args(sig: &str) -> Result<Vec<String>, DFError> {
+    let inner = sig
+        .trim()
+        .strip_prefix('(')
+        .and_then(|s| s.strip_suffix(')'))
+        .ok_or_else(|| DFError::InvalidMinedData(format!("signature {:?} is not parenthesized", sig)))?;
+
+    if inner.is_empty() {
+        return Ok(Vec::new());
     }
+
+    let mut args = Vec::new();
+    let mut cur = String::new();
+    let mut depth: i32 = 0;
+    let mut in_single_quote = false;
+    let mut in_double_quote = false;
+    for ch in inner.chars() {
+        match ch {
+            '\'' if !in_double_quote => {
+                in_single_quote = !in_single_quote;
+                cur.push(ch);
+            }
+            '"' if !in_single_quote => {
+                in_double_quote = !in_double_quote;
+                cur.push(ch);
+            }
+            '(' | '[' | '{' if !in_single_quote && !in_double_quote => {
+                depth += 1;
+                cur.push(ch);
+            }
+            ')' | ']' | '}' if !in_single_quote && !in_double_quote => {
+                depth -= 1;
+                if depth < 0 {
+                    return Err(DFError::InvalidMinedData(format!(
+                        "unbalanced brackets in signature {:?}",
+                        sig
+                    )));
+                }
+                cur.push(ch);
+            }
+            ',' if depth == 0 && !in_single_quote && !in_double_quote => {
+                args.push(cur.trim().to_string());
+                cur = String::new();
+            }
+            _ => cur.push(ch),
+        }
+    }
+    if depth != 0 || in_single_quote || in_double_quote {
+        return Err(DFError::InvalidMinedData(format!(
+            "unbalanced brackets or quotes in signature {:?}",
+            sig
+        )));
+    }
+    args.push(cur.trim().to_string());
+    Ok(args)
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -377,12 +763,10 @@ impl MinedAPICall {
         for api_call in json_vec.into_iter() {
             let mut sig_with_types: Vec<Option<ArgType>> = Vec::new();
             let mut sig_with_values: Vec<Option<ArgVal>> = Vec::new();
-            for (ty, val) in api_call
-                .sig_with_types
-                .split(',')
-                .zip(api_call.sig_with_values.split(','))
-            {
-                let opt_ty = match ty {
+            let ty_args = split_mined_sig_args(&api_call.sig_with_types)?;
+            let val_args = split_mined_sig_args(&api_call.sig_with_values)?;
+            for (ty, val) in ty_args.iter().zip(val_args.iter()) {
+                let opt_ty = match ty.as_str() {
                     "Object" => Some(ArgType::ObjectType),
                     "string" => Some(ArgType::StringType),
                     "bool" => Some(ArgType::BoolType),
@@ -393,7 +777,7 @@ impl MinedAPICall {
                     "_FUNCTION_" => Some(ArgType::CallbackType),
                     _ => None,
                 };
-                let opt_val = match (val, opt_ty) {
+                let opt_val = match (val.as_str(), opt_ty) {
                     (s, Some(ArgType::ObjectType)) => Some(ArgVal::Object(s.to_string())),
                     (s, Some(ArgType::StringType)) => Some(ArgVal::String(s.to_string())),
                     (s, Some(ArgType::BoolType)) => Some(ArgVal::Bool(s.to_string())),
@@ -0,0 +1,330 @@
+//! Delta-debugging minimization for "interesting" generated tests.
+//! Given a `Test` whose execution produced some outcome worth keeping a small reproducer
+//! for -- an execution error by default, but any `FunctionCallResult` shape a caller wants
+//! to chase (e.g. a callback that ran asynchronously where a sync one was expected) -- reduce
+//! it (ddmin-style) to the smallest subsequence of its top-level calls that still reproduces
+//! that outcome.
+
+use crate::errors::*;
+use crate::tests::*;
+
+use std::collections::HashMap;
+
+/// Minimum number of chunks ddmin starts partitioning the call sequence into.
+const MIN_GRANULARITY: usize = 2;
+
+/// Run the test and check whether it still reproduces an outcome `is_interesting` accepts.
+/// We only ask whether *any* call's result matches (not, across re-executions, whether it's
+/// still the *same* call -- top-level calls get renumbered as chunks are dropped), since
+/// that's the same granularity ddmin already reduces at (a chunk, not a single call).
+fn still_interesting(test: &mut Test, is_interesting: &dyn Fn(&FunctionCallResult) -> bool) -> bool {
+    match test.execute() {
+        Ok((ext_point_results, _, _, _)) => ext_point_results
+            .values()
+            .any(|(res, _)| is_interesting(res)),
+        // timeouts/parse errors etc. are themselves a (different) kind of fault --
+        // still worth treating as "still interesting" so minimization keeps shrinking
+        // towards it rather than giving up
+        Err(_) => true,
+    }
+}
+
+/// The default, and original, notion of "interesting": some call in the test raised an
+/// execution error.
+pub fn is_execution_error(res: &FunctionCallResult) -> bool {
+    matches!(res, FunctionCallResult::ExecutionError(_))
+}
+
+/// Does the serialized form of `call` reference the return value of the call with
+/// uniq ID `uniq_id`? This is a conservative, string-based check over `call`'s whole
+/// (possibly deeply-nested, e.g. in array/object/callback arguments) signature, since
+/// `ArgVal` doesn't expose a structured "all variables referenced" walk.
+fn call_references_uniq_id(call: &FunctionCall, ret_val_var_name: &str) -> bool {
+    serde_json::to_string(call)
+        .map(|serialized| serialized.contains(ret_val_var_name))
+        .unwrap_or(false)
+}
+
+/// Is it safe to drop the chunk `removed` while keeping `surviving`? Only true if no
+/// surviving call (or any of its nested descendants) uses a removed call's return value
+/// as a receiver or as an argument -- i.e., we preserve the dataflow invariant that a call
+/// can only be dropped if nothing left in the test depends on it.
+fn is_valid_removal(
+    test: &Test,
+    surviving: &[ExtensionPointID],
+    removed: &[ExtensionPointID],
+) -> bool {
+    let fct_tree = test.get_fct_tree();
+    let removed_ret_val_names: Vec<String> = removed
+        .iter()
+        .map(|&id| {
+            format!(
+                "ret_val_{}_{}",
+                test.mod_js_var_name,
+                test.get_uniq_id_for_call(&fct_tree[id])
+            )
+        })
+        .collect();
+    surviving.iter().all(|&id| {
+        id.descendants(fct_tree).all(|desc_id| {
+            let call = fct_tree[desc_id].get();
+            removed_ret_val_names
+                .iter()
+                .all(|ret_val_name| !call_references_uniq_id(call, ret_val_name))
+        })
+    })
+}
+
+/// Clone the subtree rooted at `node_id` (in `src`) into `dst`, preserving structure,
+/// return the new root's ID in `dst`, and record every old->new `NodeId` pair cloned
+/// (including `node_id` itself) into `id_map`, so `rebuild_test` can remap any frozen
+/// reference to an old id afterwards.
+fn clone_subtree(
+    node_id: ExtensionPointID,
+    src: &indextree::Arena<FunctionCall>,
+    dst: &mut indextree::Arena<FunctionCall>,
+    id_map: &mut HashMap<ExtensionPointID, ExtensionPointID>,
+) -> ExtensionPointID {
+    let new_id = dst.new_node(src[node_id].get().clone());
+    id_map.insert(node_id, new_id);
+    for child_id in node_id.children(src).collect::<Vec<ExtensionPointID>>() {
+        let new_child_id = clone_subtree(child_id, src, dst, id_map);
+        new_id.append(new_child_id, dst);
+    }
+    new_id
+}
+
+/// Build the candidate `Test` containing only the top-level calls (and their nested
+/// descendants) in `keep_top_level`, in order.
+///
+/// A fresh arena assigns its own `NodeId`s in insertion order, which won't match the
+/// originals once an earlier chunk has been dropped. Two things embed the old ids and
+/// would otherwise go stale:
+/// - `parent_call_id`, set once at nesting time (`Test::extend`'s `Nested` arm sets it to
+///   the same `ext_id` the node is also structurally appended under), so it can just be
+///   re-derived from the rebuilt tree's actual structural parent instead.
+/// - a surviving call's *frozen* `ret_val_<mod>_<uniq_id>`/`cb_<uniq_id>_arg_<n>`
+///   references (baked in at extension time by `get_ret_values_accessible_from_ext_point`/
+///   `get_cb_arg_values_accessible_from_ext_point`), which still name the old id while the
+///   producer re-derives and re-emits its *new* one at codegen time -- left unfixed, the
+///   reduced test would reference an undefined variable: a `ReferenceError` unrelated to
+///   the original fault that `still_interesting` would wrongly accept as "still reproduces".
+fn rebuild_test(test: &Test, keep_top_level: &[ExtensionPointID]) -> Test {
+    let mut new_fct_tree = indextree::Arena::new();
+    let src_fct_tree = test.get_fct_tree();
+    let mut id_map: HashMap<ExtensionPointID, ExtensionPointID> = HashMap::new();
+    for &id in keep_top_level {
+        clone_subtree(id, src_fct_tree, &mut new_fct_tree, &mut id_map);
+    }
+
+    for &new_id in id_map.values() {
+        let new_parent = new_fct_tree[new_id].parent();
+        new_fct_tree
+            .get_mut(new_id)
+            .unwrap()
+            .get_mut()
+            .set_parent_call_id(new_parent);
+    }
+
+    let mut uniq_id_remap: Vec<(String, String)> = Vec::new();
+    for (&old_id, &new_id) in &id_map {
+        let old_uniq_id = uniq_id_for_node(src_fct_tree, old_id);
+        let new_uniq_id = uniq_id_for_node(&new_fct_tree, new_id);
+        if old_uniq_id != new_uniq_id {
+            uniq_id_remap.push((old_uniq_id, new_uniq_id));
+        }
+    }
+
+    if !uniq_id_remap.is_empty() {
+        // longest-old-id-first, so remapping e.g. id "1" can't also accidentally clobber
+        // a reference that actually names id "10"
+        uniq_id_remap.sort_by_key(|(old, _)| std::cmp::Reverse(old.len()));
+        let mod_js_var_name = test.mod_js_var_name.clone();
+        for &new_id in id_map.values() {
+            let node = new_fct_tree.get_mut(new_id).unwrap();
+            let mut serialized =
+                serde_json::to_string(node.get()).expect("FunctionCall always serializes");
+            for (old_uniq_id, new_uniq_id) in &uniq_id_remap {
+                serialized = serialized
+                    .replace(
+                        &format!("ret_val_{}_{}", mod_js_var_name, old_uniq_id),
+                        &format!("ret_val_{}_{}", mod_js_var_name, new_uniq_id),
+                    )
+                    .replace(&format!("cb_{}", old_uniq_id), &format!("cb_{}", new_uniq_id));
+            }
+            *node.get_mut() = serde_json::from_str(&serialized)
+                .expect("FunctionCall always deserializes back what was just serialized");
+        }
+    }
+
+    test.with_fct_tree(new_fct_tree)
+}
+
+/// Minimize `test` via ddmin (Zeller & Hildebrandt): repeatedly partition its ordered
+/// top-level calls into `n` chunks, try dropping each chunk (skipping any whose removal
+/// would break a dataflow dependency), and keep the smallest subsequence that still makes
+/// some call's result satisfy `is_interesting`. Granularity doubles whenever a full pass
+/// removes nothing, and minimization stops once granularity exceeds the remaining length.
+/// Returns the (possibly unreduced) minimized `Test`; it's the caller's job to write it
+/// out via the normal test-writing path (`Test::write_test_to_file`).
+pub fn minimize_test_matching(
+    test: &Test,
+    is_interesting: &dyn Fn(&FunctionCallResult) -> bool,
+) -> Result<Test, DFError> {
+    let mut current = test.get_top_level_call_ids();
+    let mut n_chunks = MIN_GRANULARITY;
+
+    while n_chunks <= current.len().max(1) {
+        let chunk_size = (current.len() + n_chunks - 1) / n_chunks;
+        if chunk_size == 0 {
+            break;
+        }
+
+        let mut reduced_this_pass = false;
+        let mut chunk_start = 0;
+        while chunk_start < current.len() {
+            let chunk_end = (chunk_start + chunk_size).min(current.len());
+            let removed = &current[chunk_start..chunk_end];
+            let candidate: Vec<ExtensionPointID> = current[..chunk_start]
+                .iter()
+                .chain(current[chunk_end..].iter())
+                .cloned()
+                .collect();
+
+            if !candidate.is_empty() && is_valid_removal(test, &candidate, removed) {
+                let mut candidate_test = rebuild_test(test, &candidate);
+                if still_interesting(&mut candidate_test, is_interesting) {
+                    current = candidate;
+                    n_chunks = MIN_GRANULARITY.max(n_chunks - 1);
+                    reduced_this_pass = true;
+                    break; // re-scan chunks from the top of the shrunk sequence
+                }
+            }
+            chunk_start = chunk_end;
+        }
+
+        if !reduced_this_pass {
+            if n_chunks >= current.len() {
+                break;
+            }
+            n_chunks = (n_chunks * 2).min(current.len());
+        }
+    }
+
+    Ok(rebuild_test(test, &current))
+}
+
+/// Minimize a `test` that produced an execution error -- the original, and still most
+/// common, notion of "interesting" (see `is_execution_error`). A thin convenience wrapper
+/// around `minimize_test_matching` for callers that don't need a different interestingness
+/// criterion (e.g. `crate::testgen::fold_test_result_into_db`).
+pub fn minimize_failing_test(test: &Test) -> Result<Test, DFError> {
+    minimize_test_matching(test, &is_execution_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::functions::{ArgType, ArgVal, FunctionArgument, FunctionSignature};
+    use crate::module_reps::{ModuleLoadMode, NpmModule};
+    use crate::sandbox::SandboxPolicy;
+
+    /// Regression test for the NodeId-renumbering bug described in the doc comment on
+    /// `rebuild_test` above: build a three-call sequential test (`x`, `a`, `c`), where `c`
+    /// references `a`'s return value, drop `x` (forcing `a`/`c` to renumber when rebuilt into
+    /// a fresh arena), and check that the rebuilt `c` references the id `a` is rebuilt with,
+    /// rather than the stale id `a` used to have.
+    #[test]
+    fn rebuild_test_remaps_cross_call_ret_val_references() {
+        let mod_json_path = std::env::temp_dir()
+            .join(format!("nessie_test_minimize_{}.json", std::process::id()));
+        std::fs::write(&mod_json_path, r#"{"lib": "testlib", "fns": []}"#).unwrap();
+        let mod_rep = NpmModule::from_api_spec(
+            mod_json_path.clone(),
+            "testlib".to_string(),
+            None,
+            ModuleLoadMode::Cjs,
+        )
+        .unwrap();
+        std::fs::remove_file(&mod_json_path).unwrap();
+
+        let (_, base_test) = Test::test_one_call(
+            &mod_rep,
+            FunctionCall::new(
+                "x".to_string(),
+                FunctionSignature::new(&[], None),
+                None,
+                None,
+                None,
+                None,
+            ),
+            false,
+            0,
+            "test_dir".to_string(),
+            "test".to_string(),
+            None,
+            SandboxPolicy::allow_all(),
+        );
+
+        let mut fct_tree: indextree::Arena<FunctionCall> = indextree::Arena::new();
+        let x_id = fct_tree.new_node(FunctionCall::new(
+            "x".to_string(),
+            FunctionSignature::new(&[], None),
+            None,
+            None,
+            None,
+            None,
+        ));
+        let a_id = fct_tree.new_node(FunctionCall::new(
+            "a".to_string(),
+            FunctionSignature::new(&[], None),
+            None,
+            None,
+            None,
+            None,
+        ));
+        let old_a_uniq_id = uniq_id_for_node(&fct_tree, a_id);
+        let old_ret_val_name = format!(
+            "ret_val_{}_{}",
+            base_test.mod_js_var_name, old_a_uniq_id
+        );
+        let c_arg = FunctionArgument::new(
+            ArgType::StringType,
+            Some(ArgVal::Variable(old_ret_val_name.clone())),
+        );
+        let c_id = fct_tree.new_node(FunctionCall::new(
+            "c".to_string(),
+            FunctionSignature::new(&[c_arg], None),
+            None,
+            None,
+            None,
+            None,
+        ));
+
+        let test = base_test.with_fct_tree(fct_tree);
+
+        // drop `x`, keep `a` and `c` -- this is what shifts their NodeIds in the rebuilt arena
+        let rebuilt = rebuild_test(&test, &[a_id, c_id]);
+        assert!(x_id != a_id); // sanity: `x` really was a distinct, now-dropped node
+
+        let new_top_level = rebuilt.get_top_level_call_ids();
+        assert_eq!(new_top_level.len(), 2);
+        let new_a_id = new_top_level[0];
+        let new_c_id = new_top_level[1];
+        let new_a_uniq_id = uniq_id_for_node(rebuilt.get_fct_tree(), new_a_id);
+        let new_ret_val_name = format!("ret_val_{}_{}", rebuilt.mod_js_var_name, new_a_uniq_id);
+
+        let new_c_call = rebuilt.get_fct_tree()[new_c_id].get();
+        let serialized_c = serde_json::to_string(new_c_call).unwrap();
+        assert!(
+            serialized_c.contains(&new_ret_val_name),
+            "rebuilt consumer call should reference producer's new id"
+        );
+        if new_ret_val_name != old_ret_val_name {
+            assert!(
+                !serialized_c.contains(&old_ret_val_name),
+                "rebuilt consumer call should not still reference producer's stale old id"
+            );
+        }
+    }
+}
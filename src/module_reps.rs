@@ -5,12 +5,15 @@ use std::convert::TryFrom;
 use std::path::PathBuf;
 use std::str::FromStr;
 
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 
 use crate::consts::DEFAULT_MAX_ARG_LENGTH;
 use crate::errors::*;
 use crate::functions::*;
 use crate::tests::{ExtensionPointID, Test};
+use crate::ts_spec;
+use crate::FxHashMap;
 
 /// Serializable representation of the module,
 /// at the `api_info` stage (i.e., only statically looked at the properties of
@@ -19,11 +22,23 @@ use crate::tests::{ExtensionPointID, Test};
 struct NpmModuleJSON {
     /// Name of the module.
     lib: String,
-    /// Map of functions making up the module,
-    /// indexed by the name of the function.
-    /// Here the functions are the output of the `api_info` phase
-    /// optional string in the hashmap index is the access path of the fct receiver
-    fns: HashMap<String, ModFctAPIJSON>,
+    /// Functions making up the module, output of the `api_info` phase: `serde_json` can't
+    /// serialize a map keyed on anything other than a string/number, so `(access path, name)`
+    /// is flattened out into a list instead (same workaround as `NpmModuleDiscoveryJSON` and
+    /// `decisions::TestGenDBSnapshotJSON`), rather than the `[name, ", ", acc_path].join("")`
+    /// delimiter-joined string key this used to be -- a name containing `", "` itself (a
+    /// space, comma, or quote is all legal in a JS property name) used to silently corrupt
+    /// this round-trip.
+    fns: Vec<NpmModuleFnEntryJSON>,
+}
+
+/// One entry of `NpmModuleJSON::fns`: a function's receiver access path and name, plus the
+/// function itself.
+#[derive(Debug, Serialize, Deserialize)]
+struct NpmModuleFnEntryJSON {
+    acc_path: AccessPathModuleCentred,
+    name: String,
+    fct: ModFctAPIJSON,
 }
 
 /// Serializable representation of the function as discovered by the `api_info`.
@@ -41,6 +56,80 @@ struct ModFctAPIJSON {
     sigs: Vec<FunctionSignature>,
 }
 
+/// How the module under test is loaded into a generated test file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModuleLoadMode {
+    /// CommonJS `require(...)` (the default): synchronous, so the resulting binding is
+    /// usable anywhere in the test, instrumented or not, async-driven or not. Written to a
+    /// plain `.js` test file.
+    Cjs,
+    /// ESM-style dynamic `import(...)`, called from a CommonJS-style `.js` test file, for
+    /// packages that are ESM-only or dual-published and throw when `require`d. The binding
+    /// is only available once the returned promise resolves, so tests using this mode
+    /// always run under the async driver (see `Test::get_code`) to get an `await`-able
+    /// context for it.
+    Esm,
+    /// Static ESM `import * as lib_var from "lib";`, written to a `.mjs` test file so Node
+    /// parses it as a module in its own right rather than interop-loading it from CommonJS.
+    /// Unlike `Esm`, the binding is hoisted and resolved before the rest of the file runs --
+    /// same as `Cjs`'s `require`, it doesn't by itself need the async driver. Functions
+    /// exported by the module (default or named) are reached the same way either `Esm`
+    /// variant binds them: as properties on the single bound namespace object
+    /// (`lib_var.fct_name(...)`, mirroring `Cjs`'s `module.exports` access) -- there's no
+    /// separate per-export binding to plumb through `get_function_call_code`.
+    EsmStatic,
+    /// Same static `import * as lib_var from "lib";` binding as `EsmStatic`, but written to
+    /// a `.ts` test file, for libraries whose entry point is TypeScript-only (no compiled
+    /// `.js`/type-stripped output to load directly). Running the suite requires a
+    /// `ts-node`-compatible loader, registered by the mocha driver (see
+    /// `get_meta_test_code`) rather than by each individual test file.
+    TypeScript,
+}
+
+/// Autocast from strings to ModuleLoadMode
+impl std::str::FromStr for ModuleLoadMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Cjs" => Ok(Self::Cjs),
+            "Esm" => Ok(Self::Esm),
+            "EsmStatic" => Ok(Self::EsmStatic),
+            "TypeScript" => Ok(Self::TypeScript),
+            _ => Err(()),
+        }
+    }
+}
+
+impl ModuleLoadMode {
+    /// Short form label for the module load mode.
+    pub fn label(&self) -> String {
+        match self {
+            Self::Cjs => "Cjs",
+            Self::Esm => "Esm",
+            Self::EsmStatic => "EsmStatic",
+            Self::TypeScript => "TypeScript",
+        }
+        .to_string()
+    }
+
+    /// File extension generated tests using this load mode should be written with.
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            Self::Cjs | Self::Esm => "js",
+            Self::EsmStatic => "mjs",
+            Self::TypeScript => "ts",
+        }
+    }
+}
+
+impl Default for ModuleLoadMode {
+    /// `require`, matching the generator's previous (fixed) behaviour.
+    fn default() -> Self {
+        Self::Cjs
+    }
+}
+
 /// Module class:
 /// - represents the library
 /// - composed of a list of functions
@@ -51,9 +140,114 @@ pub struct NpmModule {
     pub(crate) lib: String,
     /// Optional custom import code for the module.
     pub(crate) import_code: Option<String>,
+    /// How to load this module in generated tests (CommonJS `require` or ESM `import`).
+    pub(crate) module_load_mode: ModuleLoadMode,
     /// Map of functions making up the module,
     /// indexed by the name of the function
-    fns: HashMap<(AccessPathModuleCentred, String), ModuleFunction>,
+    fns: FxHashMap<(AccessPathModuleCentred, String), ModuleFunction>,
+    /// Reverse index over `fns`, rebuilt/kept in sync by every method that replaces or adds to
+    /// `fns` (`set_fns`, `add_fcts_rooted_in_ret_vals`, `canonicalize_paths`). Not part of
+    /// `NpmModule`'s own (de)serialization round trip -- `NpmModule` is never serialized
+    /// directly (see `NpmModuleJSON`/`NpmModuleDiscoveryJSON`), so this is only `#[serde(skip)]`
+    /// to satisfy the struct-level derive; a constructor that deserializes `fns` from scratch
+    /// must rebuild this via `AccessPathIndex::from_fns` itself, same as the three `from_*`
+    /// constructors below do.
+    #[serde(skip)]
+    fns_index: AccessPathIndex,
+}
+
+/// Reverse index over `NpmModule::fns`, modeled on rust-analyzer's `import_map`: a prebuilt map
+/// from query key to items, so "every function callable on receiver path P" and "every access
+/// path exposing function named N" are index lookups instead of a linear scan over every
+/// function in the module -- the scan the extension phase would otherwise need to find nested-
+/// or chained-call candidates rooted at a given receiver.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct AccessPathIndex {
+    /// every `(access path, name)` entry rooted at a given receiver access path
+    by_receiver: FxHashMap<AccessPathModuleCentred, HashSet<(AccessPathModuleCentred, String)>>,
+    /// every `(access path, name)` entry exposing a given bare function name, regardless of
+    /// receiver
+    by_name: FxHashMap<String, HashSet<(AccessPathModuleCentred, String)>>,
+}
+
+impl AccessPathIndex {
+    /// Build an index from scratch over an existing `fns` map.
+    fn from_fns(fns: &FxHashMap<(AccessPathModuleCentred, String), ModuleFunction>) -> Self {
+        let mut index = Self::default();
+        for (acc_path, name) in fns.keys() {
+            index.insert(acc_path, name);
+        }
+        index
+    }
+
+    /// Add one `(acc_path, name)` entry to both indexes. There's no corresponding `remove`:
+    /// nothing in this module deletes individual `fns` entries one at a time -- a bulk
+    /// replacement (`set_fns`) or merge (`canonicalize_paths`) is always the rest of the map
+    /// changing too, so those rebuild the whole index via `from_fns` instead.
+    fn insert(&mut self, acc_path: &AccessPathModuleCentred, name: &str) {
+        self.by_receiver
+            .entry(acc_path.clone())
+            .or_insert_with(HashSet::new)
+            .insert((acc_path.clone(), name.to_string()));
+        self.by_name
+            .entry(name.to_string())
+            .or_insert_with(HashSet::new)
+            .insert((acc_path.clone(), name.to_string()));
+    }
+}
+
+/// Schema version for `NpmModuleDiscoveryJSON`, bumped whenever the shape of the discovery
+/// file format changes in a way that would break reading an older file back in. Checked by
+/// `NpmModule::from_discovery_file` so a stale file is rejected with a clear `DFError`
+/// instead of either a confusing deserialize failure or (worse) silently misinterpreted data.
+pub const DISCOVERY_FILE_VERSION: u32 = 1;
+
+/// Serializable representation of a module's *discovery*-phase output, written by
+/// `NpmModule::write_discovery_file` and read back by `NpmModule::from_discovery_file`.
+/// Unlike `NpmModuleJSON` (the static `api_info` listing, with no signatures yet), this
+/// captures everything discovery learned -- per-function signatures (including recorded
+/// call results), the module's load mode, and any custom import code -- so re-reading a
+/// discovery file is a full round-trip of the `NpmModule` it was written from, rather than
+/// just a function name listing that discovery then has to be entirely re-run against.
+#[derive(Debug, Serialize, Deserialize)]
+struct NpmModuleDiscoveryJSON {
+    /// schema version this file was written with
+    version: u32,
+    lib: String,
+    import_code: Option<String>,
+    module_load_mode: ModuleLoadMode,
+    /// one entry per `(access path, function)`: `serde_json` can't serialize a map keyed on
+    /// anything other than a string/number, so `fns`'s `(AccessPathModuleCentred, String)`
+    /// key is flattened out into a list instead (same workaround as `NpmModuleJSON` and
+    /// `decisions::TestGenDBSnapshotJSON`)
+    fns: Vec<NpmModuleDiscoveryFnEntryJSON>,
+}
+
+/// One entry of `NpmModuleDiscoveryJSON::fns`: the function's receiver access path, plus the
+/// function itself (which already carries its own name).
+#[derive(Debug, Serialize, Deserialize)]
+struct NpmModuleDiscoveryFnEntryJSON {
+    acc_path: AccessPathModuleCentred,
+    fct: ModuleFunction,
+}
+
+impl From<&NpmModule> for NpmModuleDiscoveryJSON {
+    fn from(mod_rep: &NpmModule) -> Self {
+        Self {
+            version: DISCOVERY_FILE_VERSION,
+            lib: mod_rep.lib.clone(),
+            import_code: mod_rep.import_code.clone(),
+            module_load_mode: mod_rep.module_load_mode,
+            fns: mod_rep
+                .get_fns()
+                .iter()
+                .map(|((acc_path, _name), fct)| NpmModuleDiscoveryFnEntryJSON {
+                    acc_path: acc_path.clone(),
+                    fct: fct.clone(),
+                })
+                .collect(),
+        }
+    }
 }
 
 /// Automatically cast from NpmModule back to NpmModuleJSON (for printing to/reading from files)
@@ -64,10 +258,12 @@ impl From<&NpmModule> for NpmModuleJSON {
             fns: mod_rep
                 .get_fns()
                 .iter()
-                .map(|((acc_path, name), mod_fct)| {
-                    ([name, ", ", &acc_path.to_string()].join(""), mod_fct.into())
+                .map(|((acc_path, name), mod_fct)| NpmModuleFnEntryJSON {
+                    acc_path: acc_path.clone(),
+                    name: name.clone(),
+                    fct: mod_fct.into(),
                 })
-                .collect::<HashMap<String, ModFctAPIJSON>>(),
+                .collect(),
         }
     }
 }
@@ -83,36 +279,147 @@ impl std::fmt::Debug for NpmModule {
 }
 
 impl NpmModule {
-    /// Setter for the list of functions in the module.
+    /// Setter for the list of functions in the module. Rebuilds `fns_index` from scratch over
+    /// the new map, so it's never left pointing at the previous `fns`.
     pub fn set_fns(
         &mut self,
-        new_fcts: HashMap<(AccessPathModuleCentred, String), ModuleFunction>,
+        new_fcts: FxHashMap<(AccessPathModuleCentred, String), ModuleFunction>,
     ) {
+        self.fns_index = AccessPathIndex::from_fns(&new_fcts);
         self.fns = new_fcts;
     }
 
     /// Getter for the module functions.
-    pub fn get_fns(&self) -> &HashMap<(AccessPathModuleCentred, String), ModuleFunction> {
+    pub fn get_fns(&self) -> &FxHashMap<(AccessPathModuleCentred, String), ModuleFunction> {
         &self.fns
     }
 
-    /// Mutable getter for the module functions.
-    pub fn get_mut_fns(
+    /// All `(access path, name)` entries rooted at `acc_path`, i.e. every function callable on
+    /// that receiver -- an `AccessPathIndex` lookup instead of a scan over every function in
+    /// the module.
+    pub fn fns_rooted_in(
+        &self,
+        acc_path: &AccessPathModuleCentred,
+    ) -> impl Iterator<Item = &(AccessPathModuleCentred, String)> {
+        self.fns_index.by_receiver.get(acc_path).into_iter().flatten()
+    }
+
+    /// All `(access path, name)` entries exposing the bare function name `name`, regardless of
+    /// receiver -- an `AccessPathIndex` lookup instead of a scan over every function in the
+    /// module.
+    pub fn fns_named(&self, name: &str) -> impl Iterator<Item = &(AccessPathModuleCentred, String)> {
+        self.fns_index.by_name.get(name).into_iter().flatten()
+    }
+
+    /// Collapse access paths known to alias the same underlying function down to a single
+    /// canonical key, merging their `fns` entries (and returning the rewrite so a caller can
+    /// apply the same substitution to whatever else it keeps indexed by access path, e.g.
+    /// `TestGenDB::libs_fcts_weights`).
+    ///
+    /// `aliases` is the set of access-path pairs observed, at runtime, to resolve to the same
+    /// value -- e.g. two different call chains that both happened to return the same handle.
+    /// Nothing in the discovery/test-running pipeline currently extracts these pairs on its
+    /// own (that would mean fingerprinting every recorded `ArgVal`/return value and comparing
+    /// them across calls in `runner`/`code_gen`, which is a separate, considerably larger
+    /// change), so this takes the alias list as an argument rather than discovering it itself;
+    /// a caller that does have fingerprinted call results can feed the pairs it found in
+    /// directly.
+    ///
+    /// Within each equivalence class (the transitive closure of `aliases`), the representative
+    /// is the lowest-cost path by `access_path_cost` -- a bare module import is the most
+    /// stable thing an access path can be rooted in, and each wrapper erodes that a little,
+    /// with `ParamPath` eroding it the most (which parameter position happens to alias a given
+    /// value is the least predictable detail of all of them). Ties are broken by `Display`
+    /// string so the choice is reproducible across runs. No signature is ever dropped: merging
+    /// two `fns` entries unions their `sigs` rather than picking one side.
+    pub fn canonicalize_paths(
         &mut self,
-    ) -> &mut HashMap<(AccessPathModuleCentred, String), ModuleFunction> {
-        &mut self.fns
+        aliases: &[(AccessPathModuleCentred, AccessPathModuleCentred)],
+    ) -> HashMap<AccessPathModuleCentred, AccessPathModuleCentred> {
+        if aliases.is_empty() {
+            return HashMap::new();
+        }
+
+        // union-find over every access path that appears in `aliases`
+        let mut parent: HashMap<AccessPathModuleCentred, AccessPathModuleCentred> = HashMap::new();
+        for (a, b) in aliases {
+            parent.entry(a.clone()).or_insert_with(|| a.clone());
+            parent.entry(b.clone()).or_insert_with(|| b.clone());
+            let root_a = uf_find(&mut parent, a);
+            let root_b = uf_find(&mut parent, b);
+            if root_a != root_b {
+                parent.insert(root_a, root_b);
+            }
+        }
+
+        // group every path that appears in `parent` by its union-find root
+        let mut classes: HashMap<AccessPathModuleCentred, Vec<AccessPathModuleCentred>> =
+            HashMap::new();
+        let paths: Vec<AccessPathModuleCentred> = parent.keys().cloned().collect();
+        for path in paths {
+            let root = uf_find(&mut parent, &path);
+            classes.entry(root).or_insert_with(Vec::new).push(path);
+        }
+
+        // pick the lowest-cost representative per class (ties broken by `Display` string)
+        let mut rewrites: HashMap<AccessPathModuleCentred, AccessPathModuleCentred> =
+            HashMap::new();
+        for members in classes.values() {
+            if members.len() < 2 {
+                continue;
+            }
+            let representative = members
+                .iter()
+                .min_by_key(|path| (access_path_cost(path), path.to_string()))
+                .unwrap()
+                .clone();
+            for member in members {
+                if *member != representative {
+                    rewrites.insert(member.clone(), representative.clone());
+                }
+            }
+        }
+        if rewrites.is_empty() {
+            return rewrites;
+        }
+
+        // rewrite `fns`: anything keyed on a path that got rewritten is merged into its
+        // representative's entry, unioning `sigs` so no mined/discovered signature is lost
+        let old_fns = std::mem::take(&mut self.fns);
+        for ((acc_path, name), mod_fct) in old_fns {
+            let canonical_path = rewrites.get(&acc_path).cloned().unwrap_or(acc_path);
+            match self.fns.entry((canonical_path, name)) {
+                std::collections::hash_map::Entry::Occupied(mut existing) => {
+                    existing.get_mut().sigs.extend(mod_fct.sigs);
+                }
+                std::collections::hash_map::Entry::Vacant(slot) => {
+                    slot.insert(mod_fct);
+                }
+            }
+        }
+
+        // the merge above rewrote `fns` wholesale, so the cheapest correct way to keep
+        // `fns_index` in sync is to rebuild it over the merged map rather than trying to
+        // thread individual insert/remove calls through the loop above
+        self.fns_index = AccessPathIndex::from_fns(&self.fns);
+
+        rewrites
     }
 
     pub fn add_fcts_rooted_in_ret_vals(
         &mut self,
-        accpath_fct_props: &HashMap<AccessPathModuleCentred, Vec<String>>,
+        accpath_fct_props: &IndexMap<AccessPathModuleCentred, Vec<String>>,
     ) {
-        // iterate through all the new functions
+        // iterate through all the new functions, in the same order they appear in the
+        // mined JSON (see `crate::tests::get_function_props_for_acc_paths`) -- `self.fns`
+        // itself is still a `HashMap`, so this doesn't make iterating *it* deterministic
+        // too, but it does mean the *order these insertions happen in* no longer depends
+        // on a HashMap's per-process hash-randomized iteration order.
         // add them as empty `ModuleFunction`s to the module function list
-        let fns = self.get_mut_fns();
         for (accpath, fct_prop_names) in accpath_fct_props.iter() {
             for name in fct_prop_names.iter() {
-                fns.insert(
+                self.fns_index.insert(accpath, name);
+                self.fns.insert(
                     (accpath.clone(), name.to_string()),
                     ModuleFunction {
                         name: name.to_string(),
@@ -128,14 +435,56 @@ impl NpmModule {
         }
     }
 
+    /// Would folding `ext_point_results` into this module (via `add_function_sigs_from_test`)
+    /// introduce an abstract signature this module hasn't recorded for some function yet,
+    /// including for a function it has no entry for at all? Must be called *before*
+    /// `add_function_sigs_from_test`, which mutates `self.fns` and would make every case
+    /// look already-known. Used by `crate::testgen::fold_test_result_into_db` to decide
+    /// whether a test is "interesting" enough to persist via
+    /// `decisions::TestGenDB::record_corpus_case`.
+    pub fn has_novel_signature(
+        &self,
+        test: &Test,
+        ext_point_results: &FxHashMap<ExtensionPointID, (FunctionCallResult, Option<String>)>,
+    ) -> bool {
+        ext_point_results.iter().any(|(ext_point_id, (fct_result, _))| {
+            if matches!(fct_result, FunctionCallResult::ExecutionError(_)) {
+                return false;
+            }
+            let Some(rel_fct) = test.get_fct_call_from_id(ext_point_id) else {
+                return false;
+            };
+            let fct_name = rel_fct.get_name();
+            let base_mod_import = AccessPathModuleCentred::RootPath(self.lib.clone());
+            let fct_acc_path_rep: AccessPathModuleCentred = match rel_fct.get_acc_path() {
+                Some(ap) => ap,
+                None => &base_mod_import,
+            }
+            .clone();
+            let base_path = fct_acc_path_rep
+                .get_base_path()
+                .unwrap_or(&base_mod_import)
+                .clone();
+            match self.fns.get(&(base_path, fct_name.to_string())) {
+                None => true,
+                Some(fct_desc) => !fct_desc
+                    .get_sigs()
+                    .iter()
+                    .any(|sig| sig.get_abstract_sig() == rel_fct.sig.get_abstract_sig()),
+            }
+        })
+    }
+
     pub fn add_function_sigs_from_test(
         &mut self,
         test: &Test,
-        ext_point_results: &HashMap<ExtensionPointID, (FunctionCallResult, Option<String>)>,
+        ext_point_results: &FxHashMap<ExtensionPointID, (FunctionCallResult, Option<String>)>,
     ) {
         for (ext_point_id, (fct_result, _)) in ext_point_results.iter() {
             let rel_fct = test.get_fct_call_from_id(ext_point_id);
-            if let Some(rel_fct) = rel_fct && fct_result != &FunctionCallResult::ExecutionError {
+            if let Some(rel_fct) = rel_fct
+                && !matches!(fct_result, FunctionCallResult::ExecutionError(_))
+            {
                 let fct_name = rel_fct.get_name();
                 let base_mod_import = AccessPathModuleCentred::RootPath(self.lib.clone());
                 let fct_acc_path_rep: AccessPathModuleCentred =
@@ -144,7 +493,7 @@ impl NpmModule {
                         None => &base_mod_import,
                     }.clone();
                 let mut new_sig = rel_fct.sig.clone();
-                new_sig.set_call_res(*fct_result);
+                new_sig.set_call_res(fct_result.clone());
                 if let Some(mut_fct_desc) = self.fns.get_mut(&(
                     (fct_acc_path_rep).clone().get_base_path().unwrap_or_else(|| {
                         &base_mod_import
@@ -157,6 +506,44 @@ impl NpmModule {
         }
     }
 
+    /// Write this module's current state to `path` as a versioned JSON discovery file (see
+    /// `NpmModuleDiscoveryJSON`), for `from_discovery_file` to read back in on a later run
+    /// without having to re-run discovery from scratch.
+    pub fn write_discovery_file(&self, path: &PathBuf) -> Result<(), DFError> {
+        let disc_file = std::fs::File::create(path)
+            .map_err(|_| DFError::WritingDiscoveryFileError(path.to_string_lossy().to_string()))?;
+        serde_json::to_writer_pretty(disc_file, &NpmModuleDiscoveryJSON::from(self))
+            .map_err(|_| DFError::WritingDiscoveryFileError(path.to_string_lossy().to_string()))
+    }
+
+    /// Read back a discovery file written by `write_discovery_file`, fully rehydrating the
+    /// `NpmModule` it was written from (signatures, call results, load mode, and import code
+    /// included) rather than just the function listing `from_api_spec` reads. Rejects a file
+    /// written by an incompatible schema version with `DFError::UnsupportedDiscoveryFileVersion`
+    /// instead of silently misreading it, so a stale discovery file from before a format
+    /// change is caught rather than fed forward as (wrong) data.
+    pub fn from_discovery_file(path: &PathBuf) -> Result<Self, DFError> {
+        let disc_file = std::fs::File::open(path)
+            .map_err(|_| DFError::ReadingDiscoveryFileError(path.to_string_lossy().to_string()))?;
+        let disc_json: NpmModuleDiscoveryJSON = serde_json::from_reader(disc_file)
+            .map_err(|_| DFError::ReadingDiscoveryFileError(path.to_string_lossy().to_string()))?;
+        if disc_json.version != DISCOVERY_FILE_VERSION {
+            return Err(DFError::UnsupportedDiscoveryFileVersion(disc_json.version));
+        }
+        let fns: FxHashMap<(AccessPathModuleCentred, String), ModuleFunction> = disc_json
+            .fns
+            .into_iter()
+            .map(|entry| ((entry.acc_path, entry.fct.name.clone()), entry.fct))
+            .collect();
+        Ok(Self {
+            lib: disc_json.lib,
+            import_code: disc_json.import_code,
+            module_load_mode: disc_json.module_load_mode,
+            fns_index: AccessPathIndex::from_fns(&fns),
+            fns,
+        })
+    }
+
     /// Create an `NpmModule` object from a JSON file resulting from running the `api_info`
     /// phase: this is just a list of all the functions for a module, without having
     /// run the discovery phase yet (i.e., no arg info yet).
@@ -164,6 +551,7 @@ impl NpmModule {
         path: PathBuf,
         _mod_name: String,
         import_code_file: Option<PathBuf>,
+        module_load_mode: ModuleLoadMode,
     ) -> Result<Self, DFError> {
         let file_conts = std::fs::read_to_string(path);
         let file_conts_string = match file_conts {
@@ -187,26 +575,13 @@ impl NpmModule {
         let lib_name = mod_json_rep.lib.clone();
 
         // convert the api_info into module functions (missing signatures until discovery)
-        let fns: HashMap<(AccessPathModuleCentred, String), ModuleFunction> = mod_json_rep
+        let fns: FxHashMap<(AccessPathModuleCentred, String), ModuleFunction> = mod_json_rep
             .fns
             .iter()
-            .map(|(name_and_opt_path, mod_fct_api)| {
-                let mut name_path_iter = name_and_opt_path.split(", ");
-                let name = name_path_iter.next().unwrap();
-                let opt_rec_acc_path_string = name_path_iter.next();
+            .map(|entry| {
                 (
-                    (
-                        match opt_rec_acc_path_string {
-                            Some(acc) => {
-                                AccessPathModuleCentred::from_str(acc).unwrap_or_else(|_| {
-                                    AccessPathModuleCentred::RootPath(lib_name.clone())
-                                })
-                            }
-                            _ => AccessPathModuleCentred::RootPath(lib_name.clone()),
-                        },
-                        name.to_string(),
-                    ),
-                    ModuleFunction::try_from(mod_fct_api),
+                    (entry.acc_path.clone(), entry.name.clone()),
+                    ModuleFunction::try_from(&entry.fct),
                 )
             })
             .filter(|(_name_and_path, opt_mod_fct)| matches!(opt_mod_fct, Ok(_)))
@@ -214,8 +589,75 @@ impl NpmModule {
             .collect();
         Ok(Self {
             lib: lib_name,
+            fns_index: AccessPathIndex::from_fns(&fns),
             fns,
             import_code,
+            module_load_mode,
+        })
+    }
+
+    /// Create an `NpmModule` object from a package's bundled TypeScript declaration file
+    /// (`.d.ts`): pre-seeds each recognized function's `sigs` with `FunctionSignature`s
+    /// mapped from its declared parameter types (see `ts_spec::parse_dts_function_sigs`),
+    /// instead of starting discovery from an empty signature set the way `from_api_spec`
+    /// does. Declarations the parser doesn't recognize (anything other than a named
+    /// top-level `function` declaration) simply don't contribute a function here -- this
+    /// only shrinks the random search space for what it can type, it's not a replacement
+    /// for the `api_info` phase.
+    pub fn from_typescript_spec(
+        path: PathBuf,
+        mod_name: String,
+        import_code_file: Option<PathBuf>,
+        module_load_mode: ModuleLoadMode,
+    ) -> Result<Self, DFError> {
+        let file_conts = std::fs::read_to_string(path);
+        let file_conts_string = match file_conts {
+            Ok(fcs) => fcs,
+            _ => return Err(DFError::SpecFileError),
+        };
+
+        let import_code = match import_code_file {
+            Some(filename) => match std::fs::read_to_string(filename) {
+                Ok(conts) => Some(conts),
+                _ => return Err(DFError::SpecFileError),
+            },
+            None => None,
+        };
+
+        let fns: FxHashMap<(AccessPathModuleCentred, String), ModuleFunction> =
+            ts_spec::parse_dts_function_sigs(&file_conts_string)
+                .into_iter()
+                .map(|(name, sigs)| {
+                    // only trust the arity across all overloads/optional-arg variants if
+                    // they all agree on it -- otherwise fall back to the same "unknown,
+                    // don't gate generation on it" meaning `None` already has for
+                    // `from_api_spec`'s spread-args case
+                    let arities: HashSet<usize> =
+                        sigs.iter().map(|s| s.get_abstract_sig().len()).collect();
+                    let num_api_args = match arities.len() {
+                        1 => sigs.first().map(|s| s.get_abstract_sig().len()),
+                        _ => None,
+                    };
+                    let mut mod_fct = ModuleFunction {
+                        name: name.clone(),
+                        sigs: HashSet::new(),
+                        num_api_args,
+                    };
+                    for sig in sigs {
+                        mod_fct.add_sig(sig);
+                    }
+                    (
+                        (AccessPathModuleCentred::RootPath(mod_name.clone()), name),
+                        mod_fct,
+                    )
+                })
+                .collect();
+        Ok(Self {
+            lib: mod_name,
+            fns_index: AccessPathIndex::from_fns(&fns),
+            fns,
+            import_code,
+            module_load_mode,
         })
     }
 
@@ -248,6 +690,126 @@ impl NpmModule {
     }
 }
 
+/// Registry of multiple `NpmModule`s fuzzed together in the same campaign -- modeled on the
+/// "stack of imported modules" namespace resolution scripting engines like rhai use for their
+/// own module systems. Each registered module keeps its own identity (`RootPath(lib)`, its own
+/// `import_code`); the registry's job is just to own them together and keep their generated-test
+/// variable names from colliding, not to merge them into one flat namespace.
+///
+/// `AccessPathModuleCentred::RootPath` already carries the owning module's name, so an access
+/// path rooted in one module's return value is self-describing about which registered module it
+/// belongs to (see `resolve`) -- nothing about the access path representation had to change for
+/// a value to flow from one module into another's parameters.
+///
+/// NOTE: this is the registry itself -- owning the modules, resolving access paths back to the
+/// module they're rooted in, and producing collision-free import code. Actually interleaving
+/// calls across modules within one generated `Test` (choosing to call a second module's function
+/// on a first module's return value mid-generation) touches `TestGenDB::gen_random_call`,
+/// `Test`/`code_gen`'s single-module-import assumption, and the single-`mod_rep` signatures of
+/// `run_discovery_phase`/`run_testgen_phase` -- a larger, separate change than fits in one
+/// commit. What's wired up here is the part those depend on: a registry to hold the modules, and
+/// a round-robin discovery driver (`run_discovery_phase_multi`) that spends the discovery budget
+/// across all of them.
+#[derive(Debug, Default)]
+pub struct ModuleRegistry {
+    /// registered modules, keyed by library name (same name each carries as its own
+    /// `AccessPathModuleCentred::RootPath`)
+    modules: HashMap<String, NpmModule>,
+    /// disambiguated JS variable name for each registered module's import, resolved once at
+    /// `register` time so it stays stable for the rest of the campaign; see `var_name_for`
+    var_names: HashMap<String, String>,
+}
+
+impl ModuleRegistry {
+    /// Empty registry -- modules are added one at a time via `register`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a module, disambiguating its JS variable name against every module already
+    /// registered. `get_mod_js_var_name`'s hyphen-to-underscore substitution means two
+    /// differently-named libs (e.g. `foo-bar` and `foo_bar`) can collide once bound to
+    /// variables in the same test file; a colliding name has its own (sanitized) lib name
+    /// appended until it's unique.
+    pub fn register(&mut self, mod_rep: NpmModule) {
+        let lib = mod_rep.lib.clone();
+        let mut var_name = mod_rep.get_mod_js_var_name();
+        while self.var_names.values().any(|taken| taken == &var_name) {
+            var_name = [var_name, "_".to_string(), str::replace(&lib, "-", "_")].concat();
+        }
+        self.var_names.insert(lib.clone(), var_name);
+        self.modules.insert(lib, mod_rep);
+    }
+
+    /// Look up a registered module by library name.
+    pub fn get(&self, lib: &str) -> Option<&NpmModule> {
+        self.modules.get(lib)
+    }
+
+    /// Mutable lookup of a registered module by library name.
+    pub fn get_mut(&mut self, lib: &str) -> Option<&mut NpmModule> {
+        self.modules.get_mut(lib)
+    }
+
+    /// Iterate over every registered module, keyed by library name.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &NpmModule)> {
+        self.modules.iter()
+    }
+
+    /// Mutable iteration over every registered module, keyed by library name.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&String, &mut NpmModule)> {
+        self.modules.iter_mut()
+    }
+
+    /// Remove and return a registered module (together forgetting its disambiguated var
+    /// name) -- e.g. so a driver can take exclusive ownership of it for a pass like discovery
+    /// and `register` it back (recomputing its var name) when done.
+    pub fn take(&mut self, lib: &str) -> Option<NpmModule> {
+        self.var_names.remove(lib);
+        self.modules.remove(lib)
+    }
+
+    /// How many modules are currently registered.
+    pub fn len(&self) -> usize {
+        self.modules.len()
+    }
+
+    /// Whether any modules are currently registered.
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+
+    /// JS variable name a registered module's import is bound to in generated tests --
+    /// disambiguated at `register` time, so it's always safe to use alongside every other
+    /// registered module's own variable name in the same test file. `None` if `lib` was never
+    /// registered.
+    pub fn var_name_for(&self, lib: &str) -> Option<&str> {
+        self.var_names.get(lib).map(String::as_str)
+    }
+
+    /// Which registered module a (possibly nested) access path is rooted in, by walking it down
+    /// to its `RootPath` (see `AccessPathModuleCentred::get_root_lib_name`). This is what lets
+    /// an extension point built from one module's return value be resolved back to the right
+    /// module when choosing what to call on it next.
+    pub fn resolve(&self, acc_path: &AccessPathModuleCentred) -> Option<&NpmModule> {
+        self.modules.get(&acc_path.get_root_lib_name())
+    }
+
+    /// JS code to import every registered module, one statement per line, each bound to its
+    /// `var_name_for` (not necessarily each module's own `get_mod_js_var_name`, in case of a
+    /// collision -- see `register`).
+    pub fn get_js_for_all_module_imports(&self, api_src_dir: Option<String>) -> String {
+        self.modules
+            .iter()
+            .map(|(lib, mod_rep)| {
+                let var_name = self.var_names.get(lib).cloned().unwrap_or_else(|| mod_rep.get_mod_js_var_name());
+                mod_rep.get_js_for_module_import_as(api_src_dir.clone(), &var_name)
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
 /// Representation of a function in a given module;
 /// each function has a list of valid signatures
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
@@ -287,6 +849,36 @@ pub enum AccessPathModuleCentred {
     InstancePath(Box<AccessPathModuleCentred>),
 }
 
+/// Path-compressing union-find lookup over the alias map built by `NpmModule::canonicalize_paths`.
+fn uf_find(
+    parent: &mut HashMap<AccessPathModuleCentred, AccessPathModuleCentred>,
+    path: &AccessPathModuleCentred,
+) -> AccessPathModuleCentred {
+    let next = match parent.get(path) {
+        Some(p) if p != path => p.clone(),
+        _ => return path.clone(),
+    };
+    let root = uf_find(parent, &next);
+    parent.insert(path.clone(), root.clone());
+    root
+}
+
+/// Stability cost of an access path, used by `NpmModule::canonicalize_paths` to pick a
+/// canonical representative among a set of paths known to alias the same function: lower is
+/// more stable (more likely to look the same across a repeat of the same discovery run). A
+/// bare module import is as stable as it gets; each wrapper erodes that a little, and
+/// `ParamPath` the most, since which parameter position happens to alias to a given value is
+/// the least predictable detail of all of them.
+fn access_path_cost(path: &AccessPathModuleCentred) -> usize {
+    match path {
+        AccessPathModuleCentred::RootPath(_) => 0,
+        AccessPathModuleCentred::ReturnPath(inner)
+        | AccessPathModuleCentred::FieldAccPath(inner, _)
+        | AccessPathModuleCentred::InstancePath(inner) => 1 + access_path_cost(inner),
+        AccessPathModuleCentred::ParamPath(inner, _) => 3 + access_path_cost(inner),
+    }
+}
+
 impl AccessPathModuleCentred {
     /// Get the base path of the access path (removing the outer recursive level).
     /// Eg. `fs.readFile` has base path `fs`.
@@ -300,6 +892,176 @@ impl AccessPathModuleCentred {
             | Self::InstancePath(ret) => Some(ret),
         }
     }
+
+    /// Walk up to the root of the access path and return the name of the
+    /// module/library it's rooted in.
+    pub fn get_root_lib_name(&self) -> String {
+        match self {
+            Self::RootPath(lib_name) => lib_name.clone(),
+            _ => self.get_base_path().unwrap().get_root_lib_name(),
+        }
+    }
+}
+
+/// Escapes `"` and `\` in a name so it can be written as a double-quoted token by `Display`
+/// and read back losslessly by `parse_quoted_string` -- without this, a function/field name
+/// containing a quote or backslash (both legal in a JS property name) would corrupt the
+/// s-expression it's embedded in.
+fn escape_sexpr_string(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Advances past any run of whitespace, returning the index of the next non-whitespace
+/// character (or `chars.len()` if none remain).
+fn skip_ws(chars: &[char], mut i: usize) -> usize {
+    while i < chars.len() && chars[i].is_whitespace() {
+        i += 1;
+    }
+    i
+}
+
+/// Consumes exactly `c` at position `i`, or fails if it isn't there.
+fn expect_char(chars: &[char], i: usize, c: char) -> Option<usize> {
+    if chars.get(i) == Some(&c) {
+        Some(i + 1)
+    } else {
+        None
+    }
+}
+
+/// Reads one bare (unquoted) token, i.e. a grammar keyword (`module`, `member`, ...) or a
+/// bare number, up to the next whitespace or paren.
+fn parse_atom(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let mut i = start;
+    while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '(' && chars[i] != ')' {
+        i += 1;
+    }
+    if i == start {
+        None
+    } else {
+        Some((chars[start..i].iter().collect(), i))
+    }
+}
+
+/// Reads one double-quoted, backslash-escaped string token (the inverse of
+/// `escape_sexpr_string`), starting at the opening `"`.
+fn parse_quoted_string(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let mut i = expect_char(chars, start, '"')?;
+    let mut out = String::new();
+    loop {
+        match *chars.get(i)? {
+            '"' => return Some((out, i + 1)),
+            '\\' => {
+                out.push(*chars.get(i + 1)?);
+                i += 2;
+            }
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Reads a name (a module name, or the receiver-less position in a `(member ...)`): a
+/// double-quoted, backslash-escaped token if present (the format `Display` writes, to
+/// round-trip losslessly), or -- for backward compatibility with the data-mining tool's own
+/// access-path strings, which write names bare and unquoted (e.g.
+/// `(member join (member exports (module path)))`) -- a bare atom.
+fn parse_name(chars: &[char], i: usize) -> Option<(String, usize)> {
+    if chars.get(i) == Some(&'"') {
+        parse_quoted_string(chars, i)
+    } else {
+        parse_atom(chars, i)
+    }
+}
+
+/// Reads a `(member ...)` field name: quoted or bare (see `parse_name`), the latter parsed as
+/// `FieldNameType::IndexField` if it's a bare number, `FieldNameType::StringField` otherwise.
+/// A *quoted* token is always a `StringField`, even if every character happens to be a digit
+/// (e.g. `obj["123"]`) -- `Display` only ever quotes a `StringField` (see its `FieldAccPath`
+/// arm below), so an unconditional `name.parse::<usize>()` here would silently turn that back
+/// into an `IndexField` and break the `FromStr`/`Display` round trip.
+fn parse_field_name(chars: &[char], i: usize) -> Option<(FieldNameType, usize)> {
+    let is_quoted = chars.get(i) == Some(&'"');
+    let (name, i) = parse_name(chars, i)?;
+    let field = if is_quoted {
+        FieldNameType::StringField(name)
+    } else {
+        match name.parse::<usize>() {
+            Ok(idx) => FieldNameType::IndexField(idx),
+            Err(_) => FieldNameType::StringField(name),
+        }
+    };
+    Some((field, i))
+}
+
+/// Recursive-descent parser over the s-expression grammar `Display` writes: `(module "name")`,
+/// `(return P)`, `(member "name"|N P)`, `(param N P)`, `(new P)`. Replaces the old blanket
+/// `replace("(\"", "")`-style parser, which broke silently on a name containing a space,
+/// comma, quote, or paren -- this tokenizes instead (via `parse_name`/`parse_quoted_string`),
+/// so those characters round-trip losslessly through `Display`/`FromStr` by construction
+/// rather than by accident.
+///
+/// Also accepts the data-mining tool's own access-path convention: bare (unquoted) names, and
+/// every module-level export wrapped in an extra `(member exports (module ...))` layer (e.g.
+/// `path.join` is `(member join (member exports (module path)))`) -- that intermediate
+/// `exports` access is collapsed straight back to the `(module ...)` root, matching the shape
+/// `Display` itself writes for a function reached directly off the module (no separate
+/// `exports` indirection in this crate's own model).
+fn parse_access_path(chars: &[char], start: usize) -> Option<(AccessPathModuleCentred, usize)> {
+    let i = expect_char(chars, start, '(')?;
+    let i = skip_ws(chars, i);
+    let (keyword, i) = parse_atom(chars, i)?;
+    let i = skip_ws(chars, i);
+    match keyword.as_str() {
+        "module" => {
+            let (name, i) = parse_name(chars, i)?;
+            let i = expect_char(chars, skip_ws(chars, i), ')')?;
+            Some((AccessPathModuleCentred::RootPath(name), i))
+        }
+        "return" => {
+            let (inner, i) = parse_access_path(chars, i)?;
+            let i = expect_char(chars, skip_ws(chars, i), ')')?;
+            Some((AccessPathModuleCentred::ReturnPath(Box::new(inner)), i))
+        }
+        "new" => {
+            let (inner, i) = parse_access_path(chars, i)?;
+            let i = expect_char(chars, skip_ws(chars, i), ')')?;
+            Some((AccessPathModuleCentred::InstancePath(Box::new(inner)), i))
+        }
+        "member" => {
+            // only the mining tool's own bare (unquoted) convention gets the `exports`
+            // collapse below -- a *quoted* `(member "exports" (module X))` is a real
+            // string-keyed access path Display itself could have written (see its
+            // `FieldAccPath` arm), and collapsing it too would make it round-trip lossily.
+            let is_bare = chars.get(i) != Some(&'"');
+            let (field_name, i) = parse_field_name(chars, i)?;
+            let (inner, i) = parse_access_path(chars, skip_ws(chars, i))?;
+            let i = expect_char(chars, skip_ws(chars, i), ')')?;
+            if is_bare {
+                if let (FieldNameType::StringField(name), AccessPathModuleCentred::RootPath(_)) =
+                    (&field_name, &inner)
+                {
+                    if name == "exports" {
+                        return Some((inner, i));
+                    }
+                }
+            }
+            Some((
+                AccessPathModuleCentred::FieldAccPath(Box::new(inner), field_name),
+                i,
+            ))
+        }
+        "param" => {
+            let (index_str, i) = parse_atom(chars, i)?;
+            let index = index_str.parse::<ParamIndexType>().ok()?;
+            let (inner, i) = parse_access_path(chars, skip_ws(chars, i))?;
+            let i = expect_char(chars, skip_ws(chars, i), ')')?;
+            Some((AccessPathModuleCentred::ParamPath(Box::new(inner), index), i))
+        }
+        _ => None,
+    }
 }
 
 /// Autocast from strings to access paths
@@ -307,99 +1069,188 @@ impl std::str::FromStr for AccessPathModuleCentred {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // delete all the characters resulting from printing the JSON rep
-        let s = s
-            .to_string()
-            .replace("(\"", "")
-            .replace("\")", "")
-            .replace("StringField", "")
-            .replace("IndexField", "");
-        if s.ends_with(')') {
-            // let s = s.split(")").next().ok_or(())?;
-            let s = s[0..s.len() - 1].to_string();
-            if s.starts_with("(module ") {
-                let mut iter = s.split("(module ");
-                iter.next(); // empty string is first
-                return Ok(AccessPathModuleCentred::RootPath(
-                    iter.next().ok_or(())?.to_string(),
-                ));
-            }
-            // other base case: report AP as module_name.exports.<member>
-            else if s.starts_with("(member exports (module ") {
-                let s = s[0..s.len() - 1].to_string(); // cut off the extra closing paren in this double-case
-                let mut iter = s.split("(member exports (module ");
-                iter.next(); // empty string is first
-                return Ok(AccessPathModuleCentred::RootPath(
-                    iter.next().ok_or(())?.to_string(),
-                ));
-            } else if s.starts_with("(return ") {
-                let mut iter = s.split("(return ");
-                iter.next(); // empty string is first
-                             // get the rest of the path
-                let return_path = iter.intersperse("(return ").collect::<String>();
-                return Ok(AccessPathModuleCentred::ReturnPath(Box::new(
-                    AccessPathModuleCentred::from_str(&return_path)?,
-                )));
-            } else if s.starts_with("(member ") {
-                let mut member_iter = s.split(' ');
-                member_iter.next(); // first string is just "(member"
-                let member_name = member_iter.next().ok_or(())?;
-                let member_name = match member_name.parse::<usize>() {
-                    Ok(val) => FieldNameType::IndexField(val),
-                    _ => FieldNameType::StringField(member_name.to_string()),
-                };
-                // collect the rest of the iterator
-                let member_path = member_iter.intersperse(" ").collect::<String>();
-                return Ok(AccessPathModuleCentred::FieldAccPath(
-                    Box::new(AccessPathModuleCentred::from_str(&member_path)?),
-                    member_name,
-                ));
-            } else if s.starts_with("(parameter ") {
-                let mut param_iter = s.split(' ');
-                param_iter.next(); // first string is just "(param"
-                let param_val = match param_iter.next().ok_or(())?.parse::<ParamIndexType>() {
-                    Ok(val) => val,
-                    _ => {
-                        return Err(());
-                    }
-                };
-                let param_path = param_iter.intersperse(" ").collect::<String>();
-                return Ok(AccessPathModuleCentred::ParamPath(
-                    Box::new(AccessPathModuleCentred::from_str(&param_path)?),
-                    param_val,
-                ));
-            } else if s.starts_with("(new ") {
-                let mut iter = s.split("(new ");
-                iter.next(); // empty string is first
-                             // collect the rest of the path
-                let new_path = iter.intersperse("(new ").collect::<String>();
-                return Ok(AccessPathModuleCentred::InstancePath(Box::new(
-                    AccessPathModuleCentred::from_str(&new_path)?,
-                )));
-            }
+        let chars: Vec<char> = s.chars().collect();
+        let (path, end) = parse_access_path(&chars, 0).ok_or(())?;
+        if skip_ws(&chars, end) == chars.len() {
+            Ok(path)
+        } else {
+            // trailing garbage after a structurally valid path -- reject rather than
+            // silently truncating, same as any other malformed input
+            Err(())
         }
-        Err(())
     }
 }
 
 impl std::fmt::Display for AccessPathModuleCentred {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            Self::RootPath(mod_name) => write!(f, "(module {})", mod_name),
+            Self::RootPath(mod_name) => {
+                write!(f, "(module \"{}\")", escape_sexpr_string(mod_name))
+            }
             Self::ReturnPath(rec_ap_box) => write!(f, "(return {})", *rec_ap_box),
-            Self::FieldAccPath(rec_ap_box, field_name) => write!(
-                f,
-                "({})",
-                format!("member {:?} {}", field_name, *rec_ap_box)
-            ),
+            Self::FieldAccPath(rec_ap_box, field_name) => {
+                let field_str = match field_name {
+                    FieldNameType::StringField(name) => {
+                        format!("\"{}\"", escape_sexpr_string(name))
+                    }
+                    FieldNameType::IndexField(idx) => idx.to_string(),
+                };
+                write!(f, "(member {} {})", field_str, *rec_ap_box)
+            }
             Self::ParamPath(rec_ap_box, param_index) => {
-                write!(f, "({})", format!("param {} {}", param_index, *rec_ap_box))
+                write!(f, "(param {} {})", param_index, *rec_ap_box)
             }
             Self::InstancePath(rec_ap_box) => write!(f, "(new {})", *rec_ap_box),
         }
     }
 }
 
+#[cfg(test)]
+mod access_path_tests {
+    use super::*;
+
+    /// Property test: for every access path `ap` built over a set of field/module names
+    /// chosen to include the characters the s-expression grammar (and its escaping) has to
+    /// handle correctly -- quotes, backslashes, whitespace, parens, and all-digit strings --
+    /// `AccessPathModuleCentred::from_str(&ap.to_string()) == Ok(ap)`. All-digit strings in
+    /// particular are what `parse_field_name` used to get wrong: a *quoted* all-digit name
+    /// (a real string-keyed property, e.g. `obj["123"]`) must stay a `StringField`, not
+    /// silently become an `IndexField`.
+    #[test]
+    fn access_path_display_from_str_round_trip() {
+        let problem_names = [
+            "plain",
+            "123",
+            "with space",
+            "with\"quote",
+            "with\\backslash",
+            "with(paren)",
+            "",
+            "exports",
+        ];
+
+        let mut paths = vec![AccessPathModuleCentred::RootPath("testlib".to_string())];
+        for name in problem_names {
+            let root = AccessPathModuleCentred::RootPath("testlib".to_string());
+            paths.push(AccessPathModuleCentred::FieldAccPath(
+                Box::new(root),
+                FieldNameType::StringField(name.to_string()),
+            ));
+        }
+        for idx in [0_usize, 1, 123] {
+            paths.push(AccessPathModuleCentred::FieldAccPath(
+                Box::new(AccessPathModuleCentred::RootPath("testlib".to_string())),
+                FieldNameType::IndexField(idx),
+            ));
+        }
+        paths.push(AccessPathModuleCentred::ReturnPath(Box::new(
+            AccessPathModuleCentred::RootPath("testlib".to_string()),
+        )));
+        paths.push(AccessPathModuleCentred::ParamPath(
+            Box::new(AccessPathModuleCentred::RootPath("testlib".to_string())),
+            2,
+        ));
+        paths.push(AccessPathModuleCentred::InstancePath(Box::new(
+            AccessPathModuleCentred::RootPath("testlib".to_string()),
+        )));
+
+        for ap in paths {
+            let displayed = ap.to_string();
+            let parsed = AccessPathModuleCentred::from_str(&displayed)
+                .unwrap_or_else(|_| panic!("failed to parse Display output {:?}", displayed));
+            assert_eq!(
+                parsed, ap,
+                "round trip mismatch for {:?} (displayed as {:?})",
+                ap, displayed
+            );
+        }
+    }
+
+    /// Regression test for the specific bug: a quoted all-digit field name (a real
+    /// string-keyed property, e.g. `obj["123"]`) must parse back as a `StringField`, never
+    /// an `IndexField` -- only a *bare* (unquoted) digit run means `IndexField`.
+    #[test]
+    fn quoted_digit_field_name_stays_string_field() {
+        let chars: Vec<char> = "\"123\"".chars().collect();
+        let (field, _) = parse_field_name(&chars, 0).unwrap();
+        assert_eq!(field, FieldNameType::StringField("123".to_string()));
+
+        let chars: Vec<char> = "123".chars().collect();
+        let (field, _) = parse_field_name(&chars, 0).unwrap();
+        assert_eq!(field, FieldNameType::IndexField(123));
+    }
+}
+
+/// A `(access path, list of property/function names)` map, as read from a standalone
+/// mining-input file an analyst curates or hand-edits alongside the mined data proper --
+/// distinct from `crate::tests::get_function_props_for_acc_paths`, which builds the same
+/// logical shape by hand-matching a running test's own JSON instrumentation events, not by
+/// reading a file, so it stays tied to that wire format rather than going through this type.
+/// Keys are always written as `AccessPathModuleCentred`'s s-expression `Display` form; this
+/// wrapper's `Deserialize` impl parses them back with `FromStr` at load time, so a key that
+/// isn't a well-formed access path is a load-time error instead of a silently-dropped entry.
+#[derive(Debug, Clone, Default)]
+pub struct AccPathFctPropsFile(pub IndexMap<AccessPathModuleCentred, Vec<String>>);
+
+impl<'de> Deserialize<'de> for AccPathFctPropsFile {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw: IndexMap<String, Vec<String>> = IndexMap::deserialize(deserializer)?;
+        let mut map = IndexMap::with_capacity(raw.len());
+        for (key, value) in raw {
+            let acc_path = AccessPathModuleCentred::from_str(&key)
+                .map_err(|_| serde::de::Error::custom(format!("invalid access path {:?}", key)))?;
+            map.insert(acc_path, value);
+        }
+        Ok(Self(map))
+    }
+}
+
+/// Which `serde` data format `load_acc_path_fct_props` should parse a mining-input file as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiningInputFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl MiningInputFormat {
+    /// Guess the format from a file's extension (`.yaml`/`.yml` -> `Yaml`, `.toml` -> `Toml`,
+    /// anything else -- including `.json` or no extension at all -- defaults to `Json`,
+    /// matching the format this map has always been produced in).
+    fn from_extension(path: &std::path::Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml") | Some("yml") => Self::Yaml,
+            Some("toml") => Self::Toml,
+            _ => Self::Json,
+        }
+    }
+}
+
+/// Load a standalone mining-input file mapping access paths to the property/function names
+/// mined for them, in JSON (the default, kept as the current behavior), YAML, or TOML -- so
+/// an analyst who keeps curated mining summaries in YAML doesn't have to pre-convert them
+/// first. `format` picks the parser explicitly; `None` guesses from `path`'s extension (see
+/// `MiningInputFormat::from_extension`).
+pub fn load_acc_path_fct_props(
+    path: &std::path::Path,
+    format: Option<MiningInputFormat>,
+) -> Result<IndexMap<AccessPathModuleCentred, Vec<String>>, DFError> {
+    let contents = std::fs::read_to_string(path).map_err(|_| DFError::MinedDataFileError)?;
+    let format = format.unwrap_or_else(|| MiningInputFormat::from_extension(path));
+    let parsed: AccPathFctPropsFile = match format {
+        MiningInputFormat::Json => {
+            serde_json::from_str(&contents).map_err(|_| DFError::MinedDataFileError)?
+        }
+        MiningInputFormat::Yaml => {
+            serde_yaml::from_str(&contents).map_err(|_| DFError::MinedDataFileError)?
+        }
+        MiningInputFormat::Toml => {
+            toml::from_str(&contents).map_err(|_| DFError::MinedDataFileError)?
+        }
+    };
+    Ok(parsed.0)
+}
+
 impl ModuleFunction {
     /// Getter for `num_api_args`.
     pub fn get_num_api_args(&self) -> Option<usize> {
@@ -416,6 +1267,34 @@ impl ModuleFunction {
         self.sigs.insert(sig);
     }
 
+    /// Consolidate `sigs` into a minimal canonical set: repeatedly merge any two
+    /// mergeable signatures (see `FunctionSignature::try_merge`), replacing the merged
+    /// pair with their single, more general unification, until no further merge is
+    /// possible. This collapses the many near-duplicate signatures discovery tends to
+    /// accumulate (one per successful call) down to a handful of general ones.
+    pub fn consolidate_sigs(&mut self) {
+        loop {
+            let cur_sigs: Vec<FunctionSignature> = self.sigs.iter().cloned().collect();
+            let mut found_merge = None;
+            'search: for (i, sig_i) in cur_sigs.iter().enumerate() {
+                for sig_j in cur_sigs.iter().skip(i + 1) {
+                    if let Some(merged) = sig_i.try_merge(sig_j) {
+                        found_merge = Some((sig_i.clone(), sig_j.clone(), merged));
+                        break 'search;
+                    }
+                }
+            }
+            match found_merge {
+                Some((sig_a, sig_b, merged)) => {
+                    self.sigs.remove(&sig_a);
+                    self.sigs.remove(&sig_b);
+                    self.sigs.insert(merged);
+                }
+                None => break,
+            }
+        }
+    }
+
     // Getter for function name.
     pub fn get_name(&self) -> String {
         self.name.clone()
@@ -0,0 +1,629 @@
+//! Structured, streaming reporters for a test-generation campaign: one record per
+//! generated test (file path, function-call tree shape, and classified outcome),
+//! written out as `run_testgen_phase` executes each test rather than buffered up and
+//! serialized all at once at the end, so a large campaign doesn't need to hold every
+//! test's data in memory.
+
+use crate::errors::{
+    CallbackInvocationTiming, DFError, FunctionCallResult, SingleCallCallbackTestResult,
+};
+use crate::module_reps::{ModuleFunction, NpmModule};
+use crate::tests::{ExtensionType, Test, TestDiagnostics};
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// Report format to serialize a test-generation campaign's outcomes into, selectable
+/// alongside `crate::TestGenMode`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ReportFormat {
+    /// JUnit XML, for plugging the generated corpus into CI dashboards that already
+    /// consume it.
+    JUnitXml,
+    /// TAP (Test Anything Protocol), a simple line-oriented format.
+    Tap,
+    /// A JSON array of per-test summary objects.
+    JsonSummary,
+}
+
+/// Autocast from strings to `ReportFormat`.
+impl std::str::FromStr for ReportFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "JUnitXml" => Ok(Self::JUnitXml),
+            "Tap" => Ok(Self::Tap),
+            "JsonSummary" => Ok(Self::JsonSummary),
+            _ => Err(()),
+        }
+    }
+}
+
+impl ReportFormat {
+    /// Short form label for the report format.
+    pub fn label(&self) -> String {
+        match self {
+            Self::JUnitXml => "JUnitXml",
+            Self::Tap => "Tap",
+            Self::JsonSummary => "JsonSummary",
+        }
+        .to_string()
+    }
+}
+
+/// One generated test's outcome, coarsened down to a single classification; a test can
+/// have many extension points (one per function call in its tree), so ties are broken in
+/// favour of the most informative result found anywhere in the tree: any execution error
+/// anywhere makes the whole test `ExecutionError`, otherwise the most "interesting"
+/// callback behaviour observed (async, then sync, then not-called) wins.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TestOutcome {
+    /// some call in the test's tree errored out.
+    ExecutionError,
+    /// a callback was called, asynchronously, somewhere in the test.
+    CallbackCalledAsync,
+    /// a callback was called, synchronously, somewhere in the test.
+    CallbackCalledSync,
+    /// no callback was called anywhere in the test.
+    NoCallbackCalled,
+}
+
+impl TestOutcome {
+    /// Short label used by all three report formats.
+    fn label(&self) -> &'static str {
+        match self {
+            Self::ExecutionError => "ExecutionError",
+            Self::CallbackCalledAsync => "CallbackCalledAsync",
+            Self::CallbackCalledSync => "CallbackCalledSync",
+            Self::NoCallbackCalled => "NoCallbackCalled",
+        }
+    }
+
+    /// Is this outcome a failure, for reporters (like JUnit) that distinguish pass/fail?
+    fn is_failure(&self) -> bool {
+        matches!(self, Self::ExecutionError)
+    }
+}
+
+/// Classify a test's diagnostics (see `Test::execute`) down to one `TestOutcome`.
+/// `None` (the test's process-level execution itself failed, e.g. a timeout, before any
+/// diagnostics could be collected) is always an `ExecutionError`.
+fn classify(results: Option<&TestDiagnostics>) -> TestOutcome {
+    let ext_point_results = match results {
+        Some((ext_point_results, ..)) => ext_point_results,
+        None => return TestOutcome::ExecutionError,
+    };
+    if ext_point_results
+        .values()
+        .any(|(res, _)| matches!(res, FunctionCallResult::ExecutionError(_)))
+    {
+        return TestOutcome::ExecutionError;
+    }
+    // a `MultiCallback` result is classified by its invocations' timing the same way a
+    // `SingleCallback` one is classified by its own: async if any invoked callback
+    // argument's first call came after the function call itself finished, else sync
+    let multi_callback_timing = |res: &FunctionCallResult| match res {
+        FunctionCallResult::MultiCallback(invocations) => invocations
+            .iter()
+            .filter(|cbi| cbi.count > 0)
+            .map(|cbi| cbi.timing)
+            .max_by_key(|timing| matches!(timing, CallbackInvocationTiming::CalledAsync)),
+        _ => None,
+    };
+    if ext_point_results.values().any(|(res, _)| {
+        res == &FunctionCallResult::SingleCallback(SingleCallCallbackTestResult::CallbackCalledAsync)
+            || multi_callback_timing(res) == Some(CallbackInvocationTiming::CalledAsync)
+    }) {
+        return TestOutcome::CallbackCalledAsync;
+    }
+    if ext_point_results.values().any(|(res, _)| {
+        res == &FunctionCallResult::SingleCallback(SingleCallCallbackTestResult::CallbackCalledSync)
+            || multi_callback_timing(res) == Some(CallbackInvocationTiming::CalledSync)
+    }) {
+        return TestOutcome::CallbackCalledSync;
+    }
+    TestOutcome::NoCallbackCalled
+}
+
+/// One call in a test's function-call tree, flattened down to just what the reporters
+/// need: its name and (if nested) the ID of the call it's nested within.
+#[derive(Debug, Clone)]
+pub struct CallShape {
+    /// Name of the function called.
+    pub name: String,
+    /// ID of the nesting parent call, if this call is nested in a callback.
+    pub parent_call_id: Option<String>,
+}
+
+/// Everything a `TestSuiteReporter` needs about one generated test, gathered right after
+/// it's `execute`d.
+pub struct TestReport {
+    /// This test's ID.
+    pub test_id: usize,
+    /// Full path to the generated test file.
+    pub file_path: String,
+    /// Flattened shape of the test's function-call tree.
+    pub calls: Vec<CallShape>,
+    /// Classified outcome of running the test.
+    pub outcome: TestOutcome,
+}
+
+impl TestReport {
+    /// Build a `TestReport` for `test`, given its diagnostics (`None` if `test.execute()`
+    /// itself returned an error, e.g. a timeout, before any diagnostics were collected).
+    pub fn new(test: &Test, results: Option<&TestDiagnostics>) -> Self {
+        Self {
+            test_id: test.get_id(),
+            file_path: test.get_file(),
+            calls: test
+                .get_fct_tree()
+                .iter()
+                .map(|node| CallShape {
+                    name: node.get().get_name().to_string(),
+                    parent_call_id: node.get().get_parent_call_id(),
+                })
+                .collect(),
+            outcome: classify(results),
+        }
+    }
+}
+
+/// One library function's discovered signatures and the outcomes observed for them,
+/// aggregated over a whole campaign from the final `NpmModule` state -- as opposed to
+/// `TestReport`, which is one record per generated test written as the campaign runs, this
+/// is one record per library function, built once at the end (see
+/// `write_function_summary_report`).
+pub struct FunctionSummary {
+    /// Name of the library function.
+    pub name: String,
+    /// How many distinct signatures were discovered for this function.
+    pub num_signatures_discovered: usize,
+    /// How many of those signatures were actually exercised by a test (i.e. have a
+    /// recorded `FunctionCallResult`).
+    pub num_signatures_exercised: usize,
+    /// Exercised signatures whose call results in a callback being invoked, synchronously.
+    pub num_callback_called_sync: usize,
+    /// Exercised signatures whose call results in a callback being invoked, asynchronously.
+    pub num_callback_called_async: usize,
+    /// Exercised signatures whose call never invoked a callback.
+    pub num_no_callback_called: usize,
+    /// Exercised signatures whose call errored out.
+    pub num_execution_errors: usize,
+    /// Exercised signatures that can seed a nested extension point (see
+    /// `FunctionCallResult::can_be_extended`).
+    pub num_nested_extension_points: usize,
+    /// Exercised signatures that can seed a sequential extension point (see
+    /// `FunctionCallResult::can_be_extended`).
+    pub num_sequential_extension_points: usize,
+}
+
+impl FunctionSummary {
+    /// Build a summary of `fct`'s discovered signatures and their outcomes.
+    pub fn new(fct: &ModuleFunction) -> Self {
+        let mut summary = Self {
+            name: fct.get_name(),
+            num_signatures_discovered: fct.get_sigs().len(),
+            num_signatures_exercised: 0,
+            num_callback_called_sync: 0,
+            num_callback_called_async: 0,
+            num_no_callback_called: 0,
+            num_execution_errors: 0,
+            num_nested_extension_points: 0,
+            num_sequential_extension_points: 0,
+        };
+        for sig in fct.get_sigs() {
+            let Some(res) = sig.get_call_res() else {
+                continue;
+            };
+            summary.num_signatures_exercised += 1;
+            match &res {
+                FunctionCallResult::ExecutionError(_) => summary.num_execution_errors += 1,
+                FunctionCallResult::SingleCallback(
+                    SingleCallCallbackTestResult::CallbackCalledSync,
+                ) => summary.num_callback_called_sync += 1,
+                FunctionCallResult::SingleCallback(
+                    SingleCallCallbackTestResult::CallbackCalledAsync,
+                ) => summary.num_callback_called_async += 1,
+                FunctionCallResult::SingleCallback(
+                    SingleCallCallbackTestResult::NoCallbackCalled,
+                ) => summary.num_no_callback_called += 1,
+                FunctionCallResult::MultiCallback(invocations) => {
+                    if invocations.iter().any(|cbi| {
+                        cbi.count > 0 && matches!(cbi.timing, CallbackInvocationTiming::CalledAsync)
+                    }) {
+                        summary.num_callback_called_async += 1;
+                    } else if invocations.iter().any(|cbi| cbi.count > 0) {
+                        summary.num_callback_called_sync += 1;
+                    } else {
+                        summary.num_no_callback_called += 1;
+                    }
+                }
+            }
+            if res.can_be_extended(ExtensionType::Nested) {
+                summary.num_nested_extension_points += 1;
+            }
+            if res.can_be_extended(ExtensionType::Sequential) {
+                summary.num_sequential_extension_points += 1;
+            }
+        }
+        summary
+    }
+}
+
+/// Write a one-shot report of per-library-function signature discovery and outcome
+/// distribution, built from the final `NpmModule` state once a campaign's test generation
+/// finishes -- complementary to the per-test `TestSuiteReporter`s above (which stream one
+/// record per generated test as it runs), this gives one record per library function,
+/// aggregated over every signature discovered for it. Uses the same `ReportFormat` as
+/// `build_reporter`, so it's selectable via the same `--report-format` option.
+pub fn write_function_summary_report(
+    mod_rep: &NpmModule,
+    format: ReportFormat,
+    report_path: &str,
+) -> Result<(), DFError> {
+    let mut summaries: Vec<FunctionSummary> =
+        mod_rep.get_fns().values().map(FunctionSummary::new).collect();
+    summaries.sort_by(|a, b| a.name.cmp(&b.name));
+    match format {
+        ReportFormat::JUnitXml => write_function_summary_junit(&summaries, report_path),
+        ReportFormat::Tap => write_function_summary_tap(&summaries, report_path),
+        ReportFormat::JsonSummary => write_function_summary_json(&summaries, report_path),
+    }
+}
+
+fn write_function_summary_junit(
+    summaries: &[FunctionSummary],
+    report_path: &str,
+) -> Result<(), DFError> {
+    let num_failures = summaries
+        .iter()
+        .filter(|summary| summary.num_execution_errors > 0)
+        .count();
+    let mut out = open_writer(report_path)?;
+    write_all(
+        &mut out,
+        report_path,
+        &format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuite name=\"function-summary\" tests=\"{}\" failures=\"{}\">\n",
+            summaries.len(),
+            num_failures,
+        ),
+    )?;
+    for summary in summaries {
+        let body = format!(
+            "signatures_discovered={} signatures_exercised={} callback_called_sync={} callback_called_async={} no_callback_called={} execution_errors={} nested_extension_points={} sequential_extension_points={}",
+            summary.num_signatures_discovered,
+            summary.num_signatures_exercised,
+            summary.num_callback_called_sync,
+            summary.num_callback_called_async,
+            summary.num_no_callback_called,
+            summary.num_execution_errors,
+            summary.num_nested_extension_points,
+            summary.num_sequential_extension_points,
+        );
+        let testcase = if summary.num_execution_errors > 0 {
+            format!(
+                "\t<testcase name=\"{}\">\n\t\t<failure message=\"{}\" type=\"ExecutionError\" />\n\t</testcase>\n",
+                xml_escape(&summary.name),
+                xml_escape(&body),
+            )
+        } else {
+            format!(
+                "\t<testcase name=\"{}\">\n\t\t<system-out>{}</system-out>\n\t</testcase>\n",
+                xml_escape(&summary.name),
+                xml_escape(&body),
+            )
+        };
+        write_all(&mut out, report_path, &testcase)?;
+    }
+    write_all(&mut out, report_path, "</testsuite>\n")?;
+    out.flush()
+        .map_err(|_| DFError::ReportWritingError(report_path.to_string()))
+}
+
+fn write_function_summary_tap(
+    summaries: &[FunctionSummary],
+    report_path: &str,
+) -> Result<(), DFError> {
+    let mut out = open_writer(report_path)?;
+    write_all(&mut out, report_path, "TAP version 13\n")?;
+    for (i, summary) in summaries.iter().enumerate() {
+        let status = if summary.num_execution_errors > 0 {
+            "not ok"
+        } else {
+            "ok"
+        };
+        let line = format!(
+            "{} {} - {} [{} signatures, {} exercised]\n",
+            status,
+            i + 1,
+            summary.name,
+            summary.num_signatures_discovered,
+            summary.num_signatures_exercised,
+        );
+        write_all(&mut out, report_path, &line)?;
+    }
+    write_all(&mut out, report_path, &format!("1..{}\n", summaries.len()))?;
+    out.flush()
+        .map_err(|_| DFError::ReportWritingError(report_path.to_string()))
+}
+
+fn write_function_summary_json(
+    summaries: &[FunctionSummary],
+    report_path: &str,
+) -> Result<(), DFError> {
+    let mut out = open_writer(report_path)?;
+    write_all(&mut out, report_path, "[\n")?;
+    for (i, summary) in summaries.iter().enumerate() {
+        let separator = if i > 0 { ",\n" } else { "" };
+        let entry = format!(
+            "{}{{\"name\": \"{}\", \"signatures_discovered\": {}, \"signatures_exercised\": {}, \"callback_called_sync\": {}, \"callback_called_async\": {}, \"no_callback_called\": {}, \"execution_errors\": {}, \"nested_extension_points\": {}, \"sequential_extension_points\": {}}}",
+            separator,
+            summary.name.replace('"', "\\\""),
+            summary.num_signatures_discovered,
+            summary.num_signatures_exercised,
+            summary.num_callback_called_sync,
+            summary.num_callback_called_async,
+            summary.num_no_callback_called,
+            summary.num_execution_errors,
+            summary.num_nested_extension_points,
+            summary.num_sequential_extension_points,
+        );
+        write_all(&mut out, report_path, &entry)?;
+    }
+    write_all(&mut out, report_path, "\n]\n")?;
+    out.flush()
+        .map_err(|_| DFError::ReportWritingError(report_path.to_string()))
+}
+
+/// Common interface for serializing a test-generation campaign's outcomes into a
+/// machine-readable report, one test at a time as `run_testgen_phase` executes them --
+/// implementations must not buffer the whole campaign's test data in memory, only
+/// whatever small running state (e.g. counts) their format's header/footer needs.
+pub trait TestSuiteReporter {
+    /// Record one test's outcome, in the order tests are executed.
+    fn report_test(&mut self, report: &TestReport) -> Result<(), DFError>;
+
+    /// Finalize the report (write any trailing counts/closing syntax) and flush it to
+    /// disk. Takes `self` by value (boxed) since most formats can only be finalized once.
+    fn finish(self: Box<Self>) -> Result<(), DFError>;
+}
+
+/// No-op reporter, used when no `--report-format`/`--report-path` is given so
+/// `run_testgen_phase` always has a reporter to call into.
+pub struct NoopReporter;
+
+impl TestSuiteReporter for NoopReporter {
+    fn report_test(&mut self, _report: &TestReport) -> Result<(), DFError> {
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), DFError> {
+        Ok(())
+    }
+}
+
+/// Build the reporter for the given `format`, writing to `report_path`.
+pub fn build_reporter(
+    format: ReportFormat,
+    report_path: &str,
+    suite_name: String,
+) -> Result<Box<dyn TestSuiteReporter>, DFError> {
+    Ok(match format {
+        ReportFormat::JUnitXml => Box::new(JUnitXmlReporter::new(report_path, suite_name)?),
+        ReportFormat::Tap => Box::new(TapReporter::new(report_path)?),
+        ReportFormat::JsonSummary => Box::new(JsonSummaryReporter::new(report_path)?),
+    })
+}
+
+fn open_writer(path: &str) -> Result<BufWriter<File>, DFError> {
+    File::create(path)
+        .map(BufWriter::new)
+        .map_err(|_| DFError::ReportWritingError(path.to_string()))
+}
+
+fn write_all(writer: &mut BufWriter<File>, path: &str, s: &str) -> Result<(), DFError> {
+    writer
+        .write_all(s.as_bytes())
+        .map_err(|_| DFError::ReportWritingError(path.to_string()))
+}
+
+/// Render a test's call-tree shape as a human-readable, comma-separated list of call
+/// names, for formats (JUnit, TAP) that only have room for a short per-test description.
+fn render_call_shape(calls: &[CallShape]) -> String {
+    calls
+        .iter()
+        .map(|call| call.name.clone())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Escape the characters XML requires escaped in text content/attribute values.
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// JUnit XML reporter: since `<testsuite>`'s opening tag needs the final test/failure
+/// counts as attributes, but those aren't known until every test has been reported, the
+/// `<testcase>` elements are streamed out to a scratch file as they come in (so the
+/// campaign's test data is never held in memory) and only stitched together with the
+/// header/footer in `finish`, once the counts are known.
+pub struct JUnitXmlReporter {
+    report_path: String,
+    body_path: String,
+    body_writer: BufWriter<File>,
+    suite_name: String,
+    num_tests: usize,
+    num_failures: usize,
+}
+
+impl JUnitXmlReporter {
+    pub fn new(report_path: &str, suite_name: String) -> Result<Self, DFError> {
+        let body_path = report_path.to_owned() + ".body.tmp";
+        Ok(Self {
+            report_path: report_path.to_string(),
+            body_writer: open_writer(&body_path)?,
+            body_path,
+            suite_name,
+            num_tests: 0,
+            num_failures: 0,
+        })
+    }
+}
+
+impl TestSuiteReporter for JUnitXmlReporter {
+    fn report_test(&mut self, report: &TestReport) -> Result<(), DFError> {
+        self.num_tests += 1;
+        if report.outcome.is_failure() {
+            self.num_failures += 1;
+        }
+        let testcase = if report.outcome.is_failure() {
+            format!(
+                "\t<testcase name=\"{}\" classname=\"{}\">\n\t\t<failure message=\"{}\" type=\"{}\" />\n\t</testcase>\n",
+                xml_escape(&report.file_path),
+                xml_escape(&self.suite_name),
+                xml_escape(&render_call_shape(&report.calls)),
+                report.outcome.label(),
+            )
+        } else {
+            format!(
+                "\t<testcase name=\"{}\" classname=\"{}\">\n\t\t<system-out>{} ({})</system-out>\n\t</testcase>\n",
+                xml_escape(&report.file_path),
+                xml_escape(&self.suite_name),
+                xml_escape(&render_call_shape(&report.calls)),
+                report.outcome.label(),
+            )
+        };
+        write_all(&mut self.body_writer, &self.body_path, &testcase)
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), DFError> {
+        self.body_writer
+            .flush()
+            .map_err(|_| DFError::ReportWritingError(self.body_path.clone()))?;
+        let mut out = open_writer(&self.report_path)?;
+        write_all(
+            &mut out,
+            &self.report_path,
+            &format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">\n",
+                xml_escape(&self.suite_name),
+                self.num_tests,
+                self.num_failures,
+            ),
+        )?;
+        let mut body_file = File::open(&self.body_path)
+            .map_err(|_| DFError::ReportWritingError(self.body_path.clone()))?;
+        std::io::copy(&mut body_file, &mut out)
+            .map_err(|_| DFError::ReportWritingError(self.report_path.clone()))?;
+        write_all(&mut out, &self.report_path, "</testsuite>\n")?;
+        out.flush()
+            .map_err(|_| DFError::ReportWritingError(self.report_path.clone()))?;
+        let _ = std::fs::remove_file(&self.body_path);
+        Ok(())
+    }
+}
+
+/// TAP (Test Anything Protocol) reporter. Unlike JUnit, TAP allows the plan line
+/// (`1..N`) to come after all the test lines instead of before them, so this can stream
+/// straight to the output file with no scratch file needed.
+pub struct TapReporter {
+    report_path: String,
+    writer: BufWriter<File>,
+    num_tests: usize,
+}
+
+impl TapReporter {
+    pub fn new(report_path: &str) -> Result<Self, DFError> {
+        let mut writer = open_writer(report_path)?;
+        write_all(&mut writer, report_path, "TAP version 13\n")?;
+        Ok(Self {
+            report_path: report_path.to_string(),
+            writer,
+            num_tests: 0,
+        })
+    }
+}
+
+impl TestSuiteReporter for TapReporter {
+    fn report_test(&mut self, report: &TestReport) -> Result<(), DFError> {
+        self.num_tests += 1;
+        let status = if report.outcome.is_failure() {
+            "not ok"
+        } else {
+            "ok"
+        };
+        let line = format!(
+            "{} {} - {} [{}] ({})\n",
+            status,
+            self.num_tests,
+            report.file_path,
+            render_call_shape(&report.calls),
+            report.outcome.label(),
+        );
+        write_all(&mut self.writer, &self.report_path, &line)
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), DFError> {
+        write_all(
+            &mut self.writer,
+            &self.report_path,
+            &format!("1..{}\n", self.num_tests),
+        )?;
+        self.writer
+            .flush()
+            .map_err(|_| DFError::ReportWritingError(self.report_path.clone()))
+    }
+}
+
+/// Reporter that streams a JSON array of per-test summary objects, writing each test's
+/// entry out as it's reported rather than buffering the array in memory.
+pub struct JsonSummaryReporter {
+    report_path: String,
+    writer: BufWriter<File>,
+    wrote_any: bool,
+}
+
+impl JsonSummaryReporter {
+    pub fn new(report_path: &str) -> Result<Self, DFError> {
+        let mut writer = open_writer(report_path)?;
+        write_all(&mut writer, report_path, "[\n")?;
+        Ok(Self {
+            report_path: report_path.to_string(),
+            writer,
+            wrote_any: false,
+        })
+    }
+}
+
+impl TestSuiteReporter for JsonSummaryReporter {
+    fn report_test(&mut self, report: &TestReport) -> Result<(), DFError> {
+        let separator = if self.wrote_any { ",\n" } else { "" };
+        self.wrote_any = true;
+        let call_names: Vec<String> = report
+            .calls
+            .iter()
+            .map(|call| format!("\"{}\"", call.name.replace('"', "\\\"")))
+            .collect();
+        let entry = format!(
+            "{}{{\"test_id\": {}, \"file_path\": \"{}\", \"calls\": [{}], \"outcome\": \"{}\"}}",
+            separator,
+            report.test_id,
+            report.file_path.replace('"', "\\\""),
+            call_names.join(", "),
+            report.outcome.label(),
+        );
+        write_all(&mut self.writer, &self.report_path, &entry)
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), DFError> {
+        write_all(&mut self.writer, &self.report_path, "\n]\n")?;
+        self.writer
+            .flush()
+            .map_err(|_| DFError::ReportWritingError(self.report_path.clone()))
+    }
+}
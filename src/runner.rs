@@ -0,0 +1,149 @@
+//! A long-lived `node` worker process that amortizes interpreter and module-load startup
+//! cost across many generated tests, as an alternative to `Test::execute` spawning a
+//! fresh `timeout node <file>` process per test.
+
+use crate::consts;
+use crate::errors::DFError;
+use crate::tests::parse_nessie_events;
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Path to the resident Node.js harness script: reads one test file path per line from
+/// stdin, `require`s it (busting its own require-cache entry so it's re-executed fresh,
+/// while the module under test stays cached and warm across tests), and prints its
+/// instrumentation JSON followed by `SENTINEL` once the test's `beforeExit` handler fires
+/// (see `code_gen::get_instrumented_footer_for_persistent_runner`).
+const HARNESS_SCRIPT: &str = "js_tools/persistent_runner.js";
+
+/// Line the harness prints right after each test's instrumentation JSON, so the Rust
+/// side knows where this test's output ends and the next test file path can be sent.
+/// A single fixed sentinel (rather than one generated per test) is enough here: the
+/// harness is only ever asked about one test at a time (`run_test` blocks until this
+/// test's sentinel, or the timeout, before sending the next path), and a worker that
+/// times out is killed and replaced outright, so there's never a stale worker left
+/// around to emit a late, stray sentinel for an old test.
+pub(crate) const SENTINEL: &str = "__NESSIE_PERSISTENT_RUNNER_TEST_DONE__";
+
+/// Line the harness prints (ahead of `SENTINEL`) when it gave up waiting on a hung test's
+/// `nessieDrain()` after its own `TEST_TIMEOUT_SECONDS`-based deadline -- see
+/// `js_tools/persistent_runner.js`. Seeing this lets `run_test` report the test as timed
+/// out without killing/respawning the worker, since the harness itself already recovered.
+const TIMEOUT_MARKER: &str = "__NESSIE_PERSISTENT_RUNNER_TIMEOUT__";
+
+/// A resident `node` subprocess, fed test file paths over stdin and read from up to
+/// `SENTINEL`. If a test hangs past `TEST_TIMEOUT_SECONDS` or the worker dies outright,
+/// it's killed and a fresh one is spawned transparently on the next `run_test` call --
+/// callers just see a `DFError::TestRunningError` for the test that was in flight.
+pub struct PersistentRunner {
+    child: Child,
+    /// `None` only while a `run_test` call has handed the reader off to its watchdog
+    /// thread; restored on a clean return, left `None` (triggering a respawn) otherwise.
+    reader: Option<BufReader<ChildStdout>>,
+}
+
+impl PersistentRunner {
+    /// Spawn a fresh harness subprocess.
+    pub fn new() -> Result<Self, DFError> {
+        let mut child = Command::new("node")
+            .arg(HARNESS_SCRIPT)
+            .arg(consts::TEST_TIMEOUT_SECONDS.to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|_| DFError::TestRunningError)?;
+        let stdout = child.stdout.take().ok_or(DFError::TestRunningError)?;
+        Ok(Self {
+            child,
+            reader: Some(BufReader::new(stdout)),
+        })
+    }
+
+    /// Run `test_file` on the resident worker and return its parsed instrumentation
+    /// events (see `crate::tests::parse_nessie_events`) along with whether the harness
+    /// itself reported giving up on a hung test (see `TIMEOUT_MARKER`) -- that case
+    /// leaves the worker alive and warm for the next call. A watchdog thread does the
+    /// actual (blocking) read up to `SENTINEL`, as a backstop for a test hanging
+    /// *synchronously* (so the harness's own timer never gets a turn to fire either); if
+    /// nothing arrives within `TEST_TIMEOUT_SECONDS`, or the worker's stdout closes
+    /// outright, the worker is killed and respawned so the next call starts from a clean
+    /// process.
+    pub fn run_test(&mut self, test_file: &str) -> Result<(Vec<serde_json::Value>, bool), DFError> {
+        if self.reader.is_none() {
+            *self = Self::new()?;
+        }
+
+        {
+            let stdin = self.child.stdin.as_mut().ok_or(DFError::TestRunningError)?;
+            if writeln!(stdin, "{}", test_file).is_err() {
+                self.kill_and_clear();
+                return Err(DFError::TestRunningError);
+            }
+        }
+
+        let mut reader = self.reader.take().unwrap();
+        let (result_tx, result_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut output = String::new();
+            loop {
+                let mut line = String::new();
+                match reader.read_line(&mut line) {
+                    Ok(0) | Err(_) => {
+                        let _ = result_tx.send(None); // worker's stdout closed/errored
+                        return;
+                    }
+                    Ok(_) => {
+                        if line.trim_end() == SENTINEL {
+                            let _ = result_tx.send(Some((output, reader)));
+                            return;
+                        }
+                        output.push_str(&line);
+                    }
+                }
+            }
+        });
+
+        match result_rx.recv_timeout(Duration::from_secs(consts::TEST_TIMEOUT_SECONDS)) {
+            Ok(Some((output, reader))) => {
+                self.reader = Some(reader);
+                let soft_timed_out = output.lines().any(|line| line == TIMEOUT_MARKER);
+                // the harness writes this test's instrumentation events to a dedicated
+                // file (see `code_gen::NESSIE_EVENTS_FILE_ENV`), not interleaved into
+                // `output` above -- fall back to parsing `output` itself only for a real
+                // ESM test file, where the harness can't use `fs` (see
+                // `code_gen::get_instrumented_header`)
+                let events_file = test_file.to_string() + "_events.ndjson";
+                let events = match std::fs::read_to_string(&events_file) {
+                    Ok(contents) => {
+                        let _ = std::fs::remove_file(&events_file);
+                        parse_nessie_events(&contents)
+                    }
+                    Err(_) => parse_nessie_events(&output),
+                };
+                Ok((events, soft_timed_out))
+            }
+            _ => {
+                // timed out, or the worker died outright: kill it (if it's still alive)
+                // and leave `reader` as `None` so the next call respawns from scratch
+                self.kill_and_clear();
+                Err(DFError::TestRunningError)
+            }
+        }
+    }
+
+    fn kill_and_clear(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        self.reader = None;
+    }
+}
+
+impl Drop for PersistentRunner {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
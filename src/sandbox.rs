@@ -0,0 +1,65 @@
+//! Capability sandboxing for generated test execution (see `Test::execute`): Nessie feeds
+//! randomly-generated values into arbitrary npm APIs, so without a sandbox a fuzzed call is
+//! free to delete files, open sockets, or spawn processes on the host with the same
+//! authority as the generator itself. `SandboxPolicy` describes what's allowed; `Test::execute`
+//! `--require`s `js_tools/sandbox_shim.js` into the subprocess, which reads the policy (JSON,
+//! passed via `SANDBOX_POLICY_ENV`) and monkey-patches `fs`, `net`/`http`/`https`, and
+//! `child_process` to throw (or no-op, for listeners) on anything outside it -- recording a
+//! `denied_*` instrumentation event for each attempt instead of silently allowing or
+//! silently dropping it, so generation can learn which signatures actually need a dangerous
+//! capability (see `Test::execute`'s `TestDiagnostics`).
+
+use serde::{Deserialize, Serialize};
+
+/// Path to the Node `--require` preload shim `Test::execute` loads ahead of every
+/// generated test (mirrors `crate::runner::HARNESS_SCRIPT`'s convention of a path
+/// relative to the process's working directory).
+pub(crate) const SANDBOX_SHIM_SCRIPT: &str = "js_tools/sandbox_shim.js";
+
+/// Env var `js_tools/sandbox_shim.js` reads its policy from, as a JSON-encoded
+/// `SandboxPolicy`. Kept in sync by hand with the literal baked into that script.
+pub(crate) const SANDBOX_POLICY_ENV: &str = "NESSIE_SANDBOX_POLICY";
+
+/// Capability allow-list enforced around a generated test's subprocess. Deny-by-default:
+/// an empty/default policy blocks all filesystem writes, all network access, and all
+/// child-process spawning, the same way running under an explicit permission set would.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SandboxPolicy {
+    /// Directory prefixes filesystem writes are allowed under (reads are never blocked).
+    /// Always implicitly includes the test's own directory (where `Test::execute` writes
+    /// the test file, its coverage dump, and its instrumentation events file), so the
+    /// generator's own bookkeeping is never mistaken for a denied capability.
+    pub allow_fs_write_dirs: Vec<String>,
+    /// Allow outbound/inbound `net`/`http`/`https` connections.
+    pub allow_network: bool,
+    /// Allow spawning child processes (`child_process.spawn`/`exec`/`execFile`/`fork`).
+    pub allow_child_process: bool,
+}
+
+impl Default for SandboxPolicy {
+    /// Deny everything: no extra writable directories, no network, no child processes.
+    fn default() -> Self {
+        Self {
+            allow_fs_write_dirs: Vec::new(),
+            allow_network: false,
+            allow_child_process: false,
+        }
+    }
+}
+
+impl SandboxPolicy {
+    /// The permissive policy, for campaigns run against a library that's already trusted
+    /// (e.g. first-party code) where sandboxing would only cost overhead for no benefit.
+    pub fn allow_all() -> Self {
+        Self {
+            allow_fs_write_dirs: vec!["/".to_string()],
+            allow_network: true,
+            allow_child_process: true,
+        }
+    }
+
+    /// Serialize to the JSON the shim expects over `SANDBOX_POLICY_ENV`.
+    pub(crate) fn to_env_value(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
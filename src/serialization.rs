@@ -0,0 +1,144 @@
+//! Typed, round-trippable serialization of values captured by instrumented test output.
+//!
+//! Prior to this module, instrumentation logged captured arguments and return values with
+//! nothing more than JS's `.toString()`/`typeof`, which collapses distinct values (the number
+//! `5` and the string `"5"`, a `Date`, a `Buffer`, `NaN`, `BigInt`) into ambiguous text. Instead,
+//! the JS side (see `code_gen::get_instrumented_header`'s `nessieSerialize`) tags every logged
+//! value with a `Conversion` name in a small JSON envelope -- `{"v": <repr>, "t": <tag>, "fmt":
+//! <optional>}` -- and `decode_captured_value` turns that envelope back into a strongly typed
+//! `CapturedValue` on this side.
+
+use serde_json::Value;
+
+/// The kind of conversion a captured value's envelope was tagged with, and (for `Timestamp`) the
+/// optional format string it was serialized with. Parsed from the `"t"`/`"fmt"` fields of a
+/// captured envelope via `FromStr`; the inverse of `Conversion::tag`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conversion {
+    /// A whole number (including `BigInt`s, serialized as decimal strings).
+    Int,
+    /// A floating point number, or one of the `NaN`/`Infinity`/`-Infinity` sentinels.
+    Float,
+    /// A boolean.
+    Bool,
+    /// Raw bytes (a `Buffer`), serialized as base64.
+    Bytes,
+    /// A string.
+    String,
+    /// A `Date`, serialized with the given format (`"iso"` if unspecified).
+    Timestamp(Option<String>),
+    /// A function value (never round-tripped; always printed as the sentinel `"[function]"`).
+    Function,
+}
+
+/// Autocast from the `"t"` tag string of a captured envelope (optionally `"timestamp|<fmt>"`,
+/// with `<fmt>` carrying the format string instead of it being a separate JSON field) to a
+/// `Conversion`.
+impl std::str::FromStr for Conversion {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('|') {
+            Some(("timestamp", fmt)) => Ok(Self::Timestamp(Some(fmt.to_string()))),
+            Some(_) => Err(()),
+            None => match s {
+                "int" => Ok(Self::Int),
+                "float" => Ok(Self::Float),
+                "bool" => Ok(Self::Bool),
+                "bytes" => Ok(Self::Bytes),
+                "string" => Ok(Self::String),
+                "timestamp" => Ok(Self::Timestamp(None)),
+                "function" => Ok(Self::Function),
+                _ => Err(()),
+            },
+        }
+    }
+}
+
+impl Conversion {
+    /// The tag string this conversion is identified by in a captured envelope's `"t"` field
+    /// (the inverse of `FromStr`).
+    pub fn tag(&self) -> String {
+        match self {
+            Self::Int => "int".to_string(),
+            Self::Float => "float".to_string(),
+            Self::Bool => "bool".to_string(),
+            Self::Bytes => "bytes".to_string(),
+            Self::String => "string".to_string(),
+            Self::Timestamp(None) => "timestamp".to_string(),
+            Self::Timestamp(Some(fmt)) => "timestamp|".to_string() + fmt,
+            Self::Function => "function".to_string(),
+        }
+    }
+}
+
+/// A value captured from instrumented test output, decoded from its tagged JSON envelope into
+/// a strongly typed representation -- the Rust-side half of the round trip `nessieSerialize`
+/// sets up on the JS side.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CapturedValue {
+    /// A whole number; `BigInt`s are included here, decimal-parsed from their string form.
+    Int(i64),
+    /// A floating point number.
+    Float(f64),
+    /// One of the `NaN`/`Infinity`/`-Infinity` sentinels, which don't fit in an `f64` literal
+    /// the same way a normal float does.
+    FloatSentinel(String),
+    /// A boolean.
+    Bool(bool),
+    /// Raw bytes, decoded from the envelope's base64 `"v"`.
+    Bytes(Vec<u8>),
+    /// A string.
+    String(String),
+    /// A timestamp, with the format string it was serialized with.
+    Timestamp { repr: String, fmt: String },
+    /// A function value (opaque; never round-tripped to an actual callable).
+    Function,
+}
+
+/// Decode a single captured envelope (`{"v": <repr>, "t": <tag>, "fmt": <optional>}`, as emitted
+/// by `nessieSerialize` in the instrumented JS) into a `CapturedValue`. Returns `None` if the
+/// envelope is missing its `"v"`/`"t"` fields, its `"t"` tag isn't recognized, or `"v"` isn't
+/// shaped the way its tag promises (e.g. non-base64 `"bytes"`, non-numeric `"int"`).
+pub fn decode_captured_value(envelope: &Value) -> Option<CapturedValue> {
+    let v = envelope.get("v")?.as_str()?;
+    let t = envelope.get("t")?.as_str()?;
+    let conversion = Conversion::from_str(t).ok()?;
+    match conversion {
+        Conversion::Int => v.parse::<i64>().ok().map(CapturedValue::Int),
+        Conversion::Float => match v {
+            "NaN" | "Infinity" | "-Infinity" => Some(CapturedValue::FloatSentinel(v.to_string())),
+            _ => v.parse::<f64>().ok().map(CapturedValue::Float),
+        },
+        Conversion::Bool => v.parse::<bool>().ok().map(CapturedValue::Bool),
+        Conversion::Bytes => decode_base64(v).map(CapturedValue::Bytes),
+        Conversion::String => Some(CapturedValue::String(v.to_string())),
+        Conversion::Timestamp(fmt) => Some(CapturedValue::Timestamp {
+            repr: v.to_string(),
+            fmt: fmt.unwrap_or_else(|| "iso".to_string()),
+        }),
+        Conversion::Function => Some(CapturedValue::Function),
+    }
+}
+
+/// Standard base64 alphabet, decoded by hand since this crate otherwise has no use (and so no
+/// existing dependency) for a dedicated base64 crate.
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim_end_matches('=');
+    let mut bits: u32 = 0;
+    let mut num_bits: u32 = 0;
+    let mut out = Vec::with_capacity(s.len() * 3 / 4);
+    for c in s.bytes() {
+        let val = BASE64_ALPHABET.iter().position(|&b| b == c)? as u32;
+        bits = (bits << 6) | val;
+        num_bits += 6;
+        if num_bits >= 8 {
+            num_bits -= 8;
+            out.push((bits >> num_bits) as u8);
+        }
+    }
+    Some(out)
+}
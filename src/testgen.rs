@@ -2,76 +2,524 @@
 
 use crate::code_gen;
 use crate::consts;
-use crate::decisions::TestGenDB;
+use crate::decisions::{self, ExtensionStrategy, TestGenDB};
+use crate::dedup;
 use crate::errors::*;
+use crate::mined_seed_reps::{MinedAPICall, MinedNestingPairJSON};
+use crate::minimize;
 use crate::module_reps::*;
+use crate::reporters::{TestReport, TestSuiteReporter};
+use crate::runner::PersistentRunner;
 use crate::tests::*;
+use crate::TestGenMode;
 
-use rand::Rng;
 use std::convert::TryInto;
 use std::path::PathBuf;
+use std::thread;
 
 /// Generate `num_tests` number of tests, for the specified module.
+/// `use_persistent_runner` switches to the persistent-runner execution backend (a single
+/// resident `node` worker, see `crate::runner::PersistentRunner`): lower per-test
+/// interpreter/module-load startup cost, at the expense of the parallel worker-pool
+/// execution below (a resident worker is inherently single-threaded) and of whole-test V8
+/// coverage collection (see `Test::execute_via_runner`). Otherwise, tests are executed via
+/// the worker-pool backend. `reporter` is sent one `TestReport` per generated test, right
+/// after it's executed (see `crate::reporters`), regardless of which backend runs it.
+/// `corpus` is an optional `(corpus file, lib_name)` pair (see `--corpus-file` in
+/// `main.rs`): if given, every interesting test generated this phase (an execution error,
+/// or a novel signature/extension point -- see `fold_test_result_into_db`) is appended to
+/// it via `decisions::TestGenDB::record_corpus_case`, for a later run's `replay_corpus` to
+/// regenerate and re-check before it does any fresh random generation of its own.
+#[allow(clippy::too_many_arguments)]
 pub fn run_testgen_phase<'cxt>(
     mod_rep: &'cxt mut NpmModule,
     testgen_db: &'cxt mut TestGenDB,
     num_tests: i32,
+    test_gen_mode: &TestGenMode,
+    use_persistent_runner: bool,
+    corpus: Option<(&PathBuf, &str)>,
+    reporter: &mut dyn TestSuiteReporter,
 ) -> Result<(), DFError> {
-    let mut cur_test_id = 1;
-    while cur_test_id <= num_tests.try_into().unwrap() {
-        // get a random extension type
-        let ext_type: ExtensionType = rand::thread_rng().gen();
+    if use_persistent_runner {
+        run_testgen_phase_with_persistent_runner(
+            mod_rep,
+            testgen_db,
+            num_tests,
+            test_gen_mode,
+            corpus,
+            reporter,
+        )
+    } else {
+        run_testgen_phase_with_worker_pool(
+            mod_rep,
+            testgen_db,
+            num_tests,
+            test_gen_mode,
+            corpus,
+            reporter,
+        )
+    }
+}
 
+/// Fold one generated-and-executed test's results into `mod_rep`/`testgen_db`: write out a
+/// minimized reproducer if a call errored, register newly-discovered function properties
+/// and signatures, add the test's extension points to the pool, and (if `corpus` is given)
+/// persist the test if it's interesting; and report its outcome via `reporter`. Shared by
+/// both execution backends in `run_testgen_phase` so they stay in lockstep.
+#[allow(clippy::too_many_arguments)]
+fn fold_test_result_into_db(
+    mod_rep: &mut NpmModule,
+    testgen_db: &mut TestGenDB,
+    cur_test_id: usize,
+    cur_test: &Test,
+    test_results: &TestDiagnostics,
+    ext_type: ExtensionType,
+    rng_draw_count_before: u64,
+    corpus: Option<(&PathBuf, &str)>,
+    reporter: &mut dyn TestSuiteReporter,
+) {
+    if let Err(e) = reporter.report_test(&TestReport::new(cur_test, Some(test_results))) {
+        println!("Failed to write report entry for test {:?}: {:?}", cur_test_id, e);
+    }
+    let has_execution_error = test_results
+        .0
+        .values()
+        .any(|(res, _)| matches!(res, FunctionCallResult::ExecutionError(_)));
+    // if a call in this test errored out, try to shrink it to a minimal reproducer
+    // before it's dropped from the extension-point pool below
+    if has_execution_error {
+        match minimize::minimize_failing_test(cur_test) {
+            Ok(minimized) => {
+                if let Err(e) = minimized.write_test_to_file(false, true, false) {
+                    println!(
+                        "Failed to write minimized reproducer for test {:?}: {:?}",
+                        cur_test_id, e
+                    );
+                }
+            }
+            Err(e) => println!(
+                "Failed to minimize failing test {:?}: {:?}",
+                cur_test_id, e
+            ),
+        }
+    }
+
+    if !test_results.3.is_empty() {
+        println!(
+            "Test {:?} had capabilities denied by the sandbox: {:?}",
+            cur_test_id, test_results.3
+        );
+    }
+
+    // persist this test before `add_function_sigs_from_test` mutates `mod_rep.fns` below --
+    // `has_novel_signature` needs to see the signature table as it was *before* this test's
+    // own results are folded in, to tell whether this test is the one introducing a sig.
+    if let Some((corpus_file, lib_name)) = corpus {
+        let is_interesting =
+            has_execution_error || mod_rep.has_novel_signature(cur_test, &test_results.0);
+        if is_interesting {
+            if let Err(e) = testgen_db.record_corpus_case(
+                corpus_file,
+                lib_name,
+                cur_test,
+                ext_type,
+                rng_draw_count_before,
+            ) {
+                println!(
+                    "Failed to persist corpus case for test {:?}: {:?}",
+                    cur_test_id, e
+                );
+            }
+        }
+    }
+
+    testgen_db.set_cur_test_index(cur_test_id);
+    mod_rep.add_fcts_rooted_in_ret_vals(&test_results.1);
+    mod_rep.add_function_sigs_from_test(cur_test, &test_results.0);
+    // TODO wire up a per-call coverage-collecting harness (e.g. `c8`/`nyc`) and pass the
+    // per-call deltas here instead of `None`, to enable the per-call weight feedback.
+    testgen_db.add_extension_points_for_test(
+        cur_test,
+        &test_results.0,
+        None,
+        Some(&test_results.2),
+    );
+}
+
+/// Generate `num_tests` tests sequentially against a single resident `node` worker (see
+/// `crate::runner::PersistentRunner`), one test at a time: each avoids paying interpreter
+/// startup/module-load cost again, at the cost of running single-threaded and never
+/// collecting whole-test V8 coverage (so extension points from this backend are always
+/// added at the default weight -- see `TestGenDB::add_extension_points_for_test`).
+#[allow(clippy::too_many_arguments)]
+fn run_testgen_phase_with_persistent_runner<'cxt>(
+    mod_rep: &'cxt mut NpmModule,
+    testgen_db: &'cxt mut TestGenDB,
+    num_tests: i32,
+    test_gen_mode: &TestGenMode,
+    corpus: Option<(&PathBuf, &str)>,
+    reporter: &mut dyn TestSuiteReporter,
+) -> Result<(), DFError> {
+    let num_tests: usize = num_tests.try_into().unwrap();
+    let mut runner = PersistentRunner::new()?;
+
+    let mut cur_test_id = 1;
+    while cur_test_id <= num_tests {
+        let rng_draw_count_before = testgen_db.get_rng_draw_count();
+        let ext_type: ExtensionType = testgen_db.gen_random_ext_type();
         let (_cur_fct_id, mut cur_test) = Test::extend(
             mod_rep,
             testgen_db,
             ext_type,
             cur_test_id,
             consts::FRESH_TEST_IF_CANT_EXTEND,
+            test_gen_mode,
         )?;
 
-        // if there's an error in a test execution (e.g., timeout), just keep going with the
-        // rest of the tests but don't add this test to the valid pool
-        // HEURISTIC: don't increment the test ID number. Technically this makes the worst
-        // case complexity infinite, but in practice this doesn't happen enough to be a problem.
-        // Revisit if this ends up being a problem with other packages.
-        let test_results = match cur_test.execute() {
-            Ok(res) => res,
-            Err(_) => {
-                println!(
-                    "Execution error in generating test {:?} -- retrying",
-                    cur_test_id
-                );
-                continue;
+        // before paying for a fresh `node` process, check whether an equivalent test (same
+        // call tree, argument values, and extension type) was already executed this
+        // campaign (see `decisions::calc_test_result_cache_hash`)
+        let result_cache_fingerprint = decisions::calc_test_result_cache_hash(&cur_test, ext_type);
+        let test_results = match testgen_db.lookup_result_cache(result_cache_fingerprint) {
+            Some(cached_results) => {
+                // still write the file out (cheap, local) so the generated suite has one
+                // file per test ID even though its execution was skipped
+                if let Err(e) = cur_test.write_test_to_file(true, false, true) {
+                    println!("Failed to write cache-hit test {:?} to file: {:?}", cur_test_id, e);
+                }
+                cached_results
             }
+            // NOTE: unlike the worker-pool backend, a persistent-runner test that errors out
+            // isn't retried under the same ID either -- its extension points (if any) are
+            // just dropped and generation moves on to the next ID.
+            None => match cur_test.execute_via_runner(&mut runner) {
+                Ok(res) => {
+                    testgen_db.record_test_result(result_cache_fingerprint, res.clone());
+                    res
+                }
+                Err(_) => {
+                    println!("Execution error in generating test {:?} -- skipping", cur_test_id);
+                    if let Err(e) = reporter.report_test(&TestReport::new(&cur_test, None)) {
+                        println!("Failed to write report entry for test {:?}: {:?}", cur_test_id, e);
+                    }
+                    cur_test_id += 1;
+                    continue;
+                }
+            },
         };
 
-        // after running the test, reprint file without all the instrumentation
-        // and as part of a mocha test suite
-        // cur_test.write_test_to_file(
-        //     false, /* no instrumentation */
-        //     true,  /* as part of a mocha test suite */
-        // )?;
-
-        testgen_db.set_cur_test_index(cur_test_id);
-        mod_rep.add_fcts_rooted_in_ret_vals(&test_results.1);
-        mod_rep.add_function_sigs_from_test(&cur_test, &test_results.0);
-        testgen_db.add_extension_points_for_test(&cur_test, &test_results.0);
+        fold_test_result_into_db(
+            mod_rep,
+            testgen_db,
+            cur_test_id,
+            &cur_test,
+            &test_results,
+            ext_type,
+            rng_draw_count_before,
+            corpus,
+            reporter,
+        );
         println!("Test: {:?} of {:?}", cur_test_id, num_tests);
+        cur_test_id += 1;
+    }
 
-        cur_test_id = cur_test_id + 1;
+    write_meta_test(
+        testgen_db.test_dir_path.clone(),
+        testgen_db.test_file_prefix.clone(),
+        num_tests.try_into().unwrap(),
+        testgen_db.get_seed(),
+        mod_rep.module_load_mode,
+    )?;
+    Ok(())
+}
+
+/// Test *generation* (drawing from `testgen_db`'s RNG/extension-point pool) stays
+/// sequential, one batch of up to `pool_size` tests at a time, so a campaign is still
+/// bit-for-bit reproducible from `testgen_db.get_seed()`. Only each batch's `node`
+/// subprocess runs -- the actual bottleneck, since `Test::execute` pays interpreter
+/// startup cost per call -- are dispatched across a worker pool, via `Test::execute_batch`.
+/// That call hands results back in the same order as its input, so `mod_rep`/`testgen_db`
+/// are updated in ascending `cur_test_id` order (not arrival order), keeping the resulting
+/// extension-point graph identical to the sequential version given the same seed.
+#[allow(clippy::too_many_arguments)]
+fn run_testgen_phase_with_worker_pool<'cxt>(
+    mod_rep: &'cxt mut NpmModule,
+    testgen_db: &'cxt mut TestGenDB,
+    num_tests: i32,
+    test_gen_mode: &TestGenMode,
+    corpus: Option<(&PathBuf, &str)>,
+    reporter: &mut dyn TestSuiteReporter,
+) -> Result<(), DFError> {
+    let pool_size = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(consts::DEFAULT_TEST_EXEC_POOL_SIZE);
+    let num_tests: usize = num_tests.try_into().unwrap();
+
+    let mut cur_test_id = 1;
+    while cur_test_id <= num_tests {
+        let batch_end = (cur_test_id + pool_size - 1).min(num_tests);
+
+        // generate this batch's tests sequentially, against the DB state left behind by
+        // the previous batch
+        let mut batch_ids = Vec::with_capacity(batch_end - cur_test_id + 1);
+        let mut batch_tests = Vec::with_capacity(batch_end - cur_test_id + 1);
+        // (ext_type, rng_draw_count_before, result-cache fingerprint), one per batch member
+        // -- needed alongside the batch's own test/execution result to record a corpus
+        // case (see `corpus` above) and to probe/populate `testgen_db`'s result cache.
+        let mut batch_gen_info = Vec::with_capacity(batch_end - cur_test_id + 1);
+        for id in cur_test_id..=batch_end {
+            // get a random extension type, drawn from the generator's centralized, seedable
+            // RNG so that a whole campaign can be replayed bit-for-bit
+            let rng_draw_count_before = testgen_db.get_rng_draw_count();
+            let ext_type: ExtensionType = testgen_db.gen_random_ext_type();
+            let (_cur_fct_id, cur_test) = Test::extend(
+                mod_rep,
+                testgen_db,
+                ext_type,
+                id,
+                consts::FRESH_TEST_IF_CANT_EXTEND,
+                test_gen_mode,
+            )?;
+            let fingerprint = decisions::calc_test_result_cache_hash(&cur_test, ext_type);
+            batch_ids.push(id);
+            batch_tests.push(cur_test);
+            batch_gen_info.push((ext_type, rng_draw_count_before, fingerprint));
+        }
+
+        // before paying for a `node` subprocess, probe the result cache for each test in
+        // the batch (see `decisions::calc_test_result_cache_hash`); only cache misses are
+        // actually dispatched to `Test::execute_batch`, on cloned copies so the batch's own
+        // `cur_test`s (used below for folding/reporting) are left untouched.
+        let mut exec_results: Vec<Option<Result<TestDiagnostics, DFError>>> =
+            (0..batch_tests.len()).map(|_| None).collect();
+        let mut is_cache_hit = vec![false; batch_tests.len()];
+        for (i, &(_, _, fingerprint)) in batch_gen_info.iter().enumerate() {
+            if let Some(cached_results) = testgen_db.lookup_result_cache(fingerprint) {
+                exec_results[i] = Some(Ok(cached_results));
+                is_cache_hit[i] = true;
+            }
+        }
+        let miss_indices: Vec<usize> = (0..batch_tests.len()).filter(|&i| !is_cache_hit[i]).collect();
+        if !miss_indices.is_empty() {
+            let mut miss_tests: Vec<Test> =
+                miss_indices.iter().map(|&i| batch_tests[i].clone()).collect();
+            // run the batch's cache-missing `node` subprocesses concurrently (each test's
+            // `get_file()` path, derived from its own `cur_test_id`, is unique to it);
+            // `execute_batch` hands results back in the same order as `miss_tests`
+            let raw_results = Test::execute_batch(&mut miss_tests);
+            for (&i, result) in miss_indices.iter().zip(raw_results.into_iter()) {
+                if let Ok(ref res) = result {
+                    testgen_db.record_test_result(batch_gen_info[i].2, res.clone());
+                }
+                exec_results[i] = Some(result);
+            }
+        }
+        // a cache-hit test was never written to a file by `execute_batch` -- do that now
+        // (cheap, local) so the generated suite still has one file per test ID even though
+        // its execution was skipped
+        for (i, cur_test) in batch_tests.iter().enumerate() {
+            if is_cache_hit[i] {
+                if let Err(e) = cur_test.write_test_to_file(true, false, true) {
+                    println!("Failed to write cache-hit test {:?} to file: {:?}", batch_ids[i], e);
+                }
+            }
+        }
+        let exec_results: Vec<Result<TestDiagnostics, DFError>> =
+            exec_results.into_iter().map(|r| r.unwrap()).collect();
+
+        // collector: fold this batch's results into `mod_rep`/`testgen_db`, in order
+        for (((id, cur_test), exec_result), (ext_type, rng_draw_count_before, _fingerprint)) in batch_ids
+            .into_iter()
+            .zip(batch_tests.into_iter())
+            .zip(exec_results.into_iter())
+            .zip(batch_gen_info.into_iter())
+        {
+            // if there's an error in a test execution (e.g., timeout), just keep going with
+            // the rest of the batch but don't add this test to the valid pool.
+            // NOTE: the sequential version retried the same `cur_test_id` forever on error;
+            // once generation is batched ahead of execution that ID has already been
+            // consumed (and its extension points already drawn from), so we simply drop
+            // this test instead -- in practice this path is rare enough not to affect
+            // overall campaign size noticeably.
+            let test_results = match exec_result {
+                Ok(res) => res,
+                Err(_) => {
+                    println!("Execution error in generating test {:?} -- skipping", id);
+                    if let Err(e) = reporter.report_test(&TestReport::new(&cur_test, None)) {
+                        println!("Failed to write report entry for test {:?}: {:?}", id, e);
+                    }
+                    continue;
+                }
+            };
+
+            // after running the test, reprint file without all the instrumentation
+            // and as part of a mocha test suite
+            // cur_test.write_test_to_file(
+            //     false, /* no instrumentation */
+            //     true,  /* as part of a mocha test suite */
+            // )?;
+
+            fold_test_result_into_db(
+                mod_rep,
+                testgen_db,
+                id,
+                &cur_test,
+                &test_results,
+                ext_type,
+                rng_draw_count_before,
+                corpus,
+                reporter,
+            );
+            println!("Test: {:?} of {:?}", id, num_tests);
+        }
+
+        cur_test_id = batch_end + 1;
     }
     // print the runner for the mocha test suite
-    write_meta_test(testgen_db.test_dir_path.clone(), num_tests)?;
+    write_meta_test(
+        testgen_db.test_dir_path.clone(),
+        testgen_db.test_file_prefix.clone(),
+        num_tests.try_into().unwrap(),
+        testgen_db.get_seed(),
+        mod_rep.module_load_mode,
+    )?;
+    Ok(())
+}
+
+/// Re-run every case persisted for `lib_name` in the corpus file at `corpus_file` (see
+/// `decisions::TestGenDB::record_corpus_case`) before any fresh random generation happens,
+/// so a regression in a previously-interesting case is caught immediately rather than
+/// waiting to be rediscovered at random -- modeled on proptest's `failure_persistence`.
+/// Each case is regenerated by fast-forwarding a freshly-seeded RNG to the exact point it
+/// was originally drawn from (`decisions::TestGenDB::resume_from_checkpoint`), then
+/// replaying the single `Test::extend` call that produced it; `mined_data`/
+/// `mined_api_call_data` must be the same mined data the original campaign was run with,
+/// same as `resume_from_checkpoint` requires for a weight-model snapshot; likewise,
+/// `config` should be the same resolved `consts::Config` the live `testgen_db` was built
+/// with (see `TestGenDB::get_config`), so a replayed case draws random values under the
+/// same `max_generated_num`/etc. the original campaign used. A fingerprint
+/// mismatch against what was recorded (e.g. after an upstream signature change) is logged
+/// but not fatal -- the case is still replayed and reported, just flagged as drifted.
+/// Replayed cases are executed and reported via `reporter`, but (unlike fresh generation)
+/// are not folded into `mod_rep`/`testgen_db`'s own weights or extension-point pool -- this
+/// is a regression check, not part of the campaign being run.
+#[allow(clippy::too_many_arguments)]
+pub fn replay_corpus(
+    mod_rep: &mut NpmModule,
+    lib_name: &str,
+    corpus_file: &PathBuf,
+    test_dir_path: String,
+    test_file_prefix: String,
+    mined_data: Option<Vec<MinedNestingPairJSON>>,
+    mined_api_call_data: Option<Vec<MinedAPICall>>,
+    api_src_dir: Option<String>,
+    ext_strategy: ExtensionStrategy,
+    config: consts::Config,
+    test_gen_mode: &TestGenMode,
+    reporter: &mut dyn TestSuiteReporter,
+) -> Result<(), DFError> {
+    let cases = TestGenDB::load_corpus(corpus_file, lib_name)?;
+    if cases.is_empty() {
+        return Ok(());
+    }
+    println!(
+        "Replaying {:?} persisted corpus case(s) for {:?} from {:?}...",
+        cases.len(),
+        lib_name,
+        corpus_file
+    );
+    for (i, case) in cases.iter().enumerate() {
+        let mut replay_db = TestGenDB::resume_from_checkpoint(
+            test_dir_path.clone(),
+            test_file_prefix.clone(),
+            mined_data.clone(),
+            mined_api_call_data.clone(),
+            api_src_dir.clone(),
+            case.seed,
+            case.rng_draw_count_before,
+            ext_strategy,
+            config.clone(),
+        );
+        // reserved, out-of-band ID: never collides with a fresh campaign's own `1..=num_tests`
+        let replay_test_id = 0;
+        let (_ext_id, cur_test) = Test::extend(
+            mod_rep,
+            &mut replay_db,
+            case.ext_type,
+            replay_test_id,
+            consts::FRESH_TEST_IF_CANT_EXTEND,
+            test_gen_mode,
+        )?;
+
+        if decisions::corpus_fingerprint(&cur_test) != case.fingerprint {
+            println!(
+                "Corpus case {:?}/{:?} for {:?} no longer regenerates the same calls \
+                 (likely an upstream signature change) -- replaying it anyway",
+                i + 1,
+                cases.len(),
+                lib_name
+            );
+        }
+
+        match cur_test.execute() {
+            Ok(test_results) => {
+                let still_errors = test_results
+                    .0
+                    .values()
+                    .any(|(res, _)| matches!(res, FunctionCallResult::ExecutionError(_)));
+                if !still_errors {
+                    println!(
+                        "Corpus case {:?}/{:?} for {:?} no longer errors -- may have been fixed \
+                         upstream",
+                        i + 1,
+                        cases.len(),
+                        lib_name
+                    );
+                }
+                if let Err(e) = reporter.report_test(&TestReport::new(&cur_test, Some(&test_results))) {
+                    println!("Failed to write report entry for replayed corpus case {:?}: {:?}", i + 1, e);
+                }
+            }
+            Err(e) => println!(
+                "Error replaying corpus case {:?}/{:?} for {:?}: {:?}",
+                i + 1,
+                cases.len(),
+                lib_name,
+                e
+            ),
+        }
+    }
     Ok(())
 }
 
 /// Print the test suite runner for `num_tests` generated tests.
-pub fn write_meta_test(test_dir: String, num_tests: i32) -> Result<(), DFError> {
-    let meta_test_code = code_gen::get_meta_test_code(num_tests);
-    let meta_test_file = PathBuf::from(test_dir + "/metatest.js");
+/// `seed` is the RNG seed this campaign was generated with; it's included as a
+/// comment in the runner so the suite's provenance (and how to replay it, via
+/// `--seed`) is recorded alongside the generated tests. `module_load_mode` is the
+/// campaign's `ModuleLoadMode` (see `code_gen::get_meta_test_code`), which determines the
+/// test files' extension and how the driver loads/invokes each one.
+/// Also runs `dedup::dedup_test_suite` over the just-generated test files, hoisting any
+/// boilerplate it recurs across enough of the suite into a shared helper module -- see
+/// there for exactly what it will (and, mostly, won't) touch.
+pub fn write_meta_test(
+    test_dir: String,
+    test_file_prefix: String,
+    num_tests: i32,
+    seed: u64,
+    module_load_mode: ModuleLoadMode,
+) -> Result<(), DFError> {
+    let meta_test_code = code_gen::get_meta_test_code(num_tests, seed, module_load_mode);
+    let meta_test_file = PathBuf::from(test_dir.clone() + "/metatest.js");
     if matches!(std::fs::write(&meta_test_file, meta_test_code), Err(_)) {
-        return Err(DFError::WritingTestError);
+        return Err(DFError::WritingTestError(
+            meta_test_file.to_string_lossy().to_string(),
+        ));
     }
+
+    let ext = module_load_mode.file_extension();
+    let candidate_test_files: Vec<String> = (1..=num_tests)
+        .map(|i| [&test_dir, "/", &test_file_prefix, &i.to_string(), ".", ext].concat())
+        .collect();
+    dedup::dedup_test_suite(&test_dir, &candidate_test_files)?;
     Ok(())
 }
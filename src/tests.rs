@@ -1,12 +1,16 @@
 //! Representations of the tests and test building components.
 
+use crate::code_gen;
 use crate::consts;
-use crate::decisions::TestGenDB;
+use crate::decisions::{ExtensionEdge, TestGenDB};
 use crate::errors::*;
 use crate::functions::*;
 use crate::module_reps::*;
+use crate::sandbox::SandboxPolicy;
+use crate::FxHashMap;
 use crate::TestGenMode;
 
+use indexmap::IndexMap;
 use indextree::Arena;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
@@ -14,6 +18,8 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::process::Command;
 use std::str::FromStr;
+use std::sync::mpsc;
+use std::thread;
 use strum_macros::EnumIter;
 
 /// Test identifying information: ID and file path.
@@ -126,7 +132,7 @@ impl FunctionCall {
     /// values, corresponding to their type.
     pub fn init_args_with_random(
         &mut self,
-        testgen_db: &TestGenDB,
+        testgen_db: &mut TestGenDB,
         ret_vals_pool: &Vec<ArgValAPTracked>,
         cb_arg_vals_pool: &Vec<ArgVal>,
         mod_rep: &NpmModule,
@@ -163,7 +169,11 @@ pub struct Test {
     /// Whether or not to include the default/basic callback.
     pub(crate) include_basic_callback: bool,
     /// Code for importing the module being tested in this test.
-    pub(crate) js_for_basic_cjs_import: String,
+    pub(crate) js_for_module_import: String,
+    /// How the module is loaded in this test (see `ModuleLoadMode`): determines whether
+    /// the import needs the async driver (`Test::get_code`) to bind before the test body
+    /// can use it, and which file extension the test is written with (`get_file`).
+    pub(crate) module_load_mode: ModuleLoadMode,
     /// Variable representing the import of the module (this is
     /// the root for all the generated library function calls).
     pub(crate) mod_js_var_name: String,
@@ -174,11 +184,38 @@ pub struct Test {
     /// to be part of a `mocha` test suite then the body of the test is inside
     /// of a function, so the `root_level_tabs` is 1.
     pub(crate) root_level_tabs: RefCell<usize>,
+    /// Substring used to filter V8 coverage entries (collected in `execute`) down to
+    /// just the module under test: either the canonicalized `api_src_dir`, or
+    /// `node_modules/<lib name>` if the module was installed rather than given locally.
+    pub(crate) coverage_url_filter: String,
+    /// Capability allow-list enforced around this test's subprocess (see `execute` and
+    /// `crate::sandbox`); inherited from the `TestGenDB` that built this test (see
+    /// `TestGenDB::set_sandbox_policy`) and carried forward unchanged by `extend`.
+    pub(crate) sandbox_policy: SandboxPolicy,
 }
 
 /// ID type for nodes in the test function tree.
 pub type ExtensionPointID = indextree::NodeId;
 
+/// Get the unique ID for the call at `node_id` in `fct_tree`, combining its arena `NodeId`
+/// with its own `parent_call_id`/`parent_arg_position_nesting` (set once, at nesting time --
+/// see `Test::extend`). Free function (rather than only a `Test` method) so it can be
+/// computed against an arena that isn't (yet, or ever) wrapped in a `Test`, e.g.
+/// `crate::minimize::rebuild_test`, which needs ids from both the original and the rebuilt
+/// tree to remap frozen `ret_val_`/`cb_` variable references across the rebuild.
+pub(crate) fn uniq_id_for_node(fct_tree: &Arena<FunctionCall>, node_id: ExtensionPointID) -> String {
+    let fc = fct_tree[node_id].get();
+    node_id.to_string()
+        + &match &fc.parent_call_id {
+            Some(pos) => "_pcid".to_owned() + pos,
+            None => String::new(),
+        }
+        + &match &fc.parent_arg_position_nesting {
+            Some(pos) => "_pos".to_owned() + pos,
+            None => String::new(),
+        }
+}
+
 impl<'cxt> Test {
     /// Constructor.
     pub fn new(
@@ -187,7 +224,11 @@ impl<'cxt> Test {
         test_dir_path: String,
         test_file_prefix: String,
         api_src_dir: Option<String>,
+        sandbox_policy: SandboxPolicy,
     ) -> Test {
+        let coverage_url_filter = api_src_dir
+            .clone()
+            .unwrap_or_else(|| "node_modules/".to_owned() + &mod_rep.lib);
         Self {
             fct_tree: Arena::new(),
             ext_points: Vec::new(),
@@ -197,9 +238,12 @@ impl<'cxt> Test {
                 test_file_prefix,
             },
             include_basic_callback: false,
-            js_for_basic_cjs_import: mod_rep.get_js_for_basic_cjs_import(api_src_dir),
+            js_for_module_import: mod_rep.get_js_for_module_import(api_src_dir),
+            module_load_mode: mod_rep.module_load_mode,
             mod_js_var_name: mod_rep.get_mod_js_var_name(),
             root_level_tabs: RefCell::new(0),
+            coverage_url_filter,
+            sandbox_policy,
         }
     }
 
@@ -279,6 +323,11 @@ impl<'cxt> Test {
             .get_mut()
             .update_cb_args_with_id(ext_node_id.into())?;
 
+        // captured before `cb_arg_pos` is (possibly) moved below, purely so the extension
+        // graph (see `decisions::TestGenDB::record_extension_edge`) can still record which
+        // callback-argument position this extension nested into, if any
+        let cb_arg_pos_for_graph = cb_arg_pos.clone();
+
         // do the extension, if it's a non-empty test
         if let Some(ext_id) = ext_id {
             match ext_type {
@@ -301,6 +350,27 @@ impl<'cxt> Test {
         }
 
         let base_test_root_tabs = *base_test.root_level_tabs.borrow();
+        let parent_test_id = base_test.loc_id.cur_test_id;
+
+        // record this test (and, if it extends another, the edge from its parent) in the
+        // campaign's test-extension graph -- see `decisions::TestGenDB::
+        // record_extension_graph_node`/`record_extension_edge`, consumed by
+        // `crate::graphviz::write_extension_graph_dot`
+        let call_names: Vec<String> = base_test
+            .fct_tree
+            .iter()
+            .map(|node| node.get().get_name().to_owned())
+            .collect();
+        testgen_db.record_extension_graph_node(new_test_id, call_names);
+        if let Some(ext_id) = ext_id {
+            testgen_db.record_extension_edge(ExtensionEdge {
+                parent_test_id,
+                parent_ext_id: ext_id,
+                cb_arg_pos: cb_arg_pos_for_graph,
+                ext_type,
+                child_test_id: new_test_id,
+            });
+        }
 
         // return the new test
         Ok((
@@ -310,9 +380,12 @@ impl<'cxt> Test {
                 ext_points: Vec::new(), // we don't know what the extension points are yet!
                 loc_id: base_test.loc_id.copy_with_new_test_id(new_test_id),
                 include_basic_callback: false,
-                js_for_basic_cjs_import: base_test.js_for_basic_cjs_import,
+                js_for_module_import: base_test.js_for_module_import,
+                module_load_mode: base_test.module_load_mode,
                 mod_js_var_name: base_test.mod_js_var_name,
                 root_level_tabs: RefCell::new(base_test_root_tabs),
+                coverage_url_filter: base_test.coverage_url_filter,
+                sandbox_policy: base_test.sandbox_policy,
             },
         ))
     }
@@ -326,9 +399,13 @@ impl<'cxt> Test {
         test_dir_path: String,
         test_file_prefix: String,
         api_src_dir: Option<String>,
+        sandbox_policy: SandboxPolicy,
     ) -> (ExtensionPointID, Test) {
         let mut fct_tree = Arena::new();
         let one_call_id = fct_tree.new_node(one_call);
+        let coverage_url_filter = api_src_dir
+            .clone()
+            .unwrap_or_else(|| "node_modules/".to_owned() + &mod_rep.lib);
         (
             one_call_id,
             Self {
@@ -340,9 +417,12 @@ impl<'cxt> Test {
                     test_file_prefix,
                 },
                 include_basic_callback,
-                js_for_basic_cjs_import: mod_rep.get_js_for_basic_cjs_import(api_src_dir),
+                js_for_module_import: mod_rep.get_js_for_module_import(api_src_dir),
+                module_load_mode: mod_rep.module_load_mode,
                 mod_js_var_name: mod_rep.get_mod_js_var_name(),
                 root_level_tabs: RefCell::new(0),
+                coverage_url_filter,
+                sandbox_policy,
             },
         )
     }
@@ -353,27 +433,47 @@ impl<'cxt> Test {
     }
 
     /// Getter for the name of the file this test should be printed to;
-    /// this is the full path to the file.
-    fn get_file(&self) -> String {
+    /// this is the full path to the file. The extension follows `self.module_load_mode`
+    /// (`.mjs` for `EsmStatic`, so Node parses it as a module in its own right; `.js`
+    /// otherwise).
+    pub(crate) fn get_file(&self) -> String {
         [
             self.loc_id.test_dir_path.clone(),
             self.loc_id.test_file_prefix.clone(),
         ]
         .join("/")
             + &self.loc_id.cur_test_id.to_string()
-            + ".js"
+            + "."
+            + self.module_load_mode.file_extension()
     }
 
     /// Generate the code for this test and write it to the specified file.
     /// Options for instrumenting the test and for printing it as part of a `mocha`
-    /// test suite.
+    /// test suite. `async_driver`, if set, awaits each top-level call's returned promise
+    /// at its call site and awaits a final `nessieDrain()` completion barrier before the
+    /// test is considered done, instead of firing calls off and forgetting about them
+    /// (see `get_code`) -- only meaningful when `print_instrumented` is also set.
     pub fn write_test_to_file(
         &self,
         print_instrumented: bool,
         print_as_test_fct: bool,
+        async_driver: bool,
     ) -> Result<String, DFError> {
         let cur_test_file = self.get_file();
-        let cur_test = self.get_code(print_instrumented, print_as_test_fct);
+        let cur_test = self.get_code(print_instrumented, print_as_test_fct, async_driver);
+        if matches!(std::fs::write(&cur_test_file, cur_test), Err(_)) {
+            return Err(DFError::WritingTestError(self.get_file().to_string()));
+        }
+        Ok(cur_test_file)
+    }
+
+    /// Like `write_test_to_file`, but for the persistent-runner execution backend (see
+    /// `crate::runner`): always instrumented, never wrapped as a mocha test function, and
+    /// uses `beforeExit`/`sentinel` instead of `exit` to signal completion (see
+    /// `get_code_for_persistent_runner`).
+    fn write_test_to_file_for_persistent_runner(&self, sentinel: &str) -> Result<String, DFError> {
+        let cur_test_file = self.get_file();
+        let cur_test = self.get_code_for_persistent_runner(sentinel);
         if matches!(std::fs::write(&cur_test_file, cur_test), Err(_)) {
             return Err(DFError::WritingTestError(self.get_file().to_string()));
         }
@@ -398,32 +498,209 @@ impl<'cxt> Test {
         let cur_test_file = self.write_test_to_file(
             true,  /* needs to be instrumented for tracking */
             false, /* running these directly */
+            true,  /* deterministically await in-flight work before diagnosing the test */
         )?;
 
+        // dump per-run V8 script coverage here, so we can tell which tests actually
+        // exercise new code in the module under test (see `collect_v8_coverage`)
+        let coverage_dir = cur_test_file.clone() + "_v8_coverage";
+        let _ = std::fs::create_dir_all(&coverage_dir);
+
+        // instrumentation events are written here instead of stdout (see
+        // `code_gen::NESSIE_EVENTS_FILE_ENV`), so a library under test that's chatty on
+        // its own stdout/stderr can't corrupt diagnosis; clear out a stale file left
+        // behind by a previous run at this same test id first.
+        let events_file = cur_test_file.clone() + "_events.ndjson";
+        let _ = std::fs::remove_file(&events_file);
+
         let mut binding = Command::new("timeout"); // timeout if the test doesn't terminate within time bound
         let run_test = binding
             .arg(consts::TEST_TIMEOUT_SECONDS.to_string())
             .arg("node")
-            .arg(&cur_test_file);
+            .arg("--require")
+            .arg(crate::sandbox::SANDBOX_SHIM_SCRIPT)
+            .arg(&cur_test_file)
+            .env("NODE_V8_COVERAGE", &coverage_dir)
+            .env(code_gen::NESSIE_EVENTS_FILE_ENV, &events_file)
+            .env(crate::sandbox::SANDBOX_POLICY_ENV, self.sandbox_policy.to_env_value());
 
+        // the library under test's own stdout/stderr is left free for its own output
+        // (captured here for ad hoc debugging, but not consulted for diagnosis)
         let output = match run_test.output() {
             Ok(output) => output,
             _ => return Err(DFError::TestRunningError), // should never crash, everything is in a try-catch
         };
+        // GNU coreutils' `timeout` exits 124 specifically when it had to kill the child
+        // for running past the deadline, rather than the child exiting on its own.
+        let timed_out = output.status.code() == Some(124);
 
-        let output_json: Value =
-            match serde_json::from_str(match std::str::from_utf8(&output.stdout) {
-                Ok(output_str) => output_str,
-                _ => return Err(DFError::TestOutputParseError),
-            }) {
-                Ok(output_json) => output_json,
-                _ => return Err(DFError::TestOutputParseError),
-            };
+        let events_contents = match std::fs::read_to_string(&events_file) {
+            Ok(contents) => contents,
+            // a test that crashed before printing a single event never creates the file
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            _ => return Err(DFError::TestOutputParseError),
+        };
+        let output_vec = parse_nessie_events(&events_contents);
         // if the test didn't error, then we found a valid signature
         // also, need to update all the extension points if their relevant callbacks were executed
         // and, get the list of new functions available on return values with `ObjectType` type
-        let test_results = diagnose_test_correctness(self, &output_json);
-        Ok(test_results)
+        let (ext_point_results, new_fcts) = diagnose_test_correctness(self, &output_vec, timed_out);
+        let covered_ranges = self.collect_v8_coverage(&coverage_dir);
+        let denied_capabilities = collect_denied_capabilities(&output_vec);
+        let _ = std::fs::remove_dir_all(&coverage_dir);
+        let _ = std::fs::remove_file(&events_file);
+        Ok((ext_point_results, new_fcts, covered_ranges, denied_capabilities))
+    }
+
+    /// Execute `tests` concurrently across a worker pool (size defaulting to
+    /// `std::thread::available_parallelism`, falling back to
+    /// `consts::DEFAULT_TEST_EXEC_POOL_SIZE`): all instrumented files are written up front
+    /// (one worker thread per test, each only ever touching the single `Test` it owns, so
+    /// there's no shared mutable state to guard), then subprocesses are drained as they
+    /// finish. Mirrors `execute` exactly for each test -- same per-test timeout and error
+    /// semantics -- just dispatched across cores instead of paid for serially; the
+    /// single-test path above is untouched. Results come back in the same order as `tests`,
+    /// not completion order, so callers can zip them back up against their inputs.
+    pub fn execute_batch(tests: &mut [Test]) -> Vec<Result<TestDiagnostics, DFError>> {
+        let pool_size = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(consts::DEFAULT_TEST_EXEC_POOL_SIZE);
+
+        let mut results: Vec<Option<Result<TestDiagnostics, DFError>>> =
+            (0..tests.len()).map(|_| None).collect();
+
+        for chunk_start in (0..tests.len()).step_by(pool_size) {
+            let chunk_end = (chunk_start + pool_size).min(tests.len());
+            let (results_tx, results_rx) = mpsc::channel();
+            thread::scope(|scope| {
+                for (offset, test) in tests[chunk_start..chunk_end].iter_mut().enumerate() {
+                    let results_tx = results_tx.clone();
+                    scope.spawn(move || {
+                        let exec_result = test.execute();
+                        let _ = results_tx.send((chunk_start + offset, exec_result));
+                    });
+                }
+            });
+            drop(results_tx);
+            for (index, exec_result) in results_rx.iter() {
+                results[index] = Some(exec_result);
+            }
+        }
+
+        results.into_iter().map(|r| r.unwrap()).collect()
+    }
+
+    /// Like `execute`, but runs the test on a resident `crate::runner::PersistentRunner`
+    /// worker instead of spawning a fresh `timeout node` process -- avoids paying
+    /// interpreter/module-load startup cost per test, at the cost of whole-test V8
+    /// coverage collection: coverage is tied to a `node` process's lifetime rather than a
+    /// single `require`, so this always returns an empty coverage vector. Prefer `execute`
+    /// over this when coverage-guided extension-point weighting (see
+    /// `TestGenDB::add_extension_points_for_test`) matters more to a campaign than raw
+    /// throughput. Note this path does not enforce `self.sandbox_policy`: the worker
+    /// process is spawned once by `PersistentRunner::new` and reused for many tests with
+    /// potentially differing policies, and `--require` can't be re-applied to an
+    /// already-running process -- campaigns that need sandboxing should use `execute`.
+    pub fn execute_via_runner(
+        &mut self,
+        runner: &mut crate::runner::PersistentRunner,
+    ) -> Result<TestDiagnostics, DFError> {
+        let cur_test_file = self.write_test_to_file_for_persistent_runner(crate::runner::SENTINEL)?;
+        // a test hung badly enough to wedge the worker synchronously never reaches here at
+        // all -- `run_test` kills the worker and returns `Err(DFError::TestRunningError)`
+        // itself in that case; `timed_out` below only covers the harness's own soft
+        // recovery from a hung *asynchronous* test (see `crate::runner`)
+        let (output_vec, timed_out) = runner.run_test(&cur_test_file)?;
+        let (ext_point_results, new_fcts) = diagnose_test_correctness(self, &output_vec, timed_out);
+        Ok((ext_point_results, new_fcts, Vec::new(), Vec::new()))
+    }
+
+    /// Parse the per-test V8 coverage dump in `coverage_dir` (written because `execute`
+    /// set `NODE_V8_COVERAGE` before invoking `node`) and return the `(url, startOffset)`
+    /// markers of every covered sub-range belonging to the module under test, filtered via
+    /// `coverage_url_filter`. Returns an empty vector (zero gain) if `coverage_dir` has no
+    /// files in it at all, e.g. because the test crashed before node could write any.
+    /// Note: V8's coverage format reports byte ranges, not source line numbers; mapping
+    /// those back to lines would need the module's original source text, which isn't
+    /// available here, so a covered sub-range's `startOffset` is used as a (coarser, but
+    /// still monotonic) stand-in for "line" wherever this is fed into the rest of the
+    /// weight model.
+    fn collect_v8_coverage(&self, coverage_dir: &str) -> Vec<(String, u64)> {
+        // per-url raw `(startOffset, endOffset, count)` ranges, pooled across every
+        // function in every coverage dump file found (there can be more than one file if
+        // `node` itself forks, though in practice this crate only ever runs one process)
+        let mut ranges_by_url: HashMap<String, Vec<(u64, u64, u64)>> = HashMap::new();
+        let entries = match std::fs::read_dir(coverage_dir) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+        for entry in entries.flatten() {
+            let conts = match std::fs::read_to_string(entry.path()) {
+                Ok(conts) => conts,
+                Err(_) => continue,
+            };
+            let parsed: Value = match serde_json::from_str(&conts) {
+                Ok(parsed) => parsed,
+                Err(_) => continue,
+            };
+            let scripts = match parsed.get("result").and_then(Value::as_array) {
+                Some(scripts) => scripts,
+                None => continue,
+            };
+            for script in scripts {
+                let url = match script.get("url").and_then(Value::as_str) {
+                    Some(url) if url.contains(&self.coverage_url_filter) => url.to_owned(),
+                    _ => continue,
+                };
+                let functions = match script.get("functions").and_then(Value::as_array) {
+                    Some(functions) => functions,
+                    None => continue,
+                };
+                for function in functions {
+                    let ranges = match function.get("ranges").and_then(Value::as_array) {
+                        Some(ranges) => ranges,
+                        None => continue,
+                    };
+                    for range in ranges {
+                        let start = range.get("startOffset").and_then(Value::as_u64).unwrap_or(0);
+                        let end = range.get("endOffset").and_then(Value::as_u64).unwrap_or(start);
+                        let count = range.get("count").and_then(Value::as_u64).unwrap_or(0);
+                        ranges_by_url.entry(url.clone()).or_default().push((start, end, count));
+                    }
+                }
+            }
+        }
+
+        // V8 nests a function's own range and its finer-grained (branch/statement)
+        // sub-ranges at overlapping offsets, so the same byte can appear in more than one
+        // range with different counts. Resolve that with a coordinate-compression sweep
+        // per url: split the url's ranges at every distinct boundary they mention, then for
+        // each resulting elementary sub-interval, its resolved count is the max count of
+        // every range that spans it -- an inner range overrides its coarser enclosing one
+        // wherever they disagree, since max picks whichever range actually reports this
+        // byte as hit.
+        let mut covered = Vec::new();
+        for (url, ranges) in ranges_by_url {
+            let mut boundaries: Vec<u64> = ranges.iter().flat_map(|&(s, e, _)| [s, e]).collect();
+            boundaries.sort_unstable();
+            boundaries.dedup();
+            for window in boundaries.windows(2) {
+                let (seg_start, seg_end) = (window[0], window[1]);
+                if seg_start >= seg_end {
+                    continue;
+                }
+                let resolved_count = ranges
+                    .iter()
+                    .filter(|&&(s, e, _)| s <= seg_start && seg_end <= e)
+                    .map(|&(_, _, count)| count)
+                    .max()
+                    .unwrap_or(0);
+                if resolved_count > 0 {
+                    covered.push((url.clone(), seg_start));
+                }
+            }
+        }
+        covered
     }
 
     /// Getter for the function tree.
@@ -431,17 +708,39 @@ impl<'cxt> Test {
         &self.fct_tree
     }
 
+    /// Get the IDs of the top-level (i.e., not nested in any callback) calls in this
+    /// test, in the order they appear. Used by the delta-debugging minimizer, which
+    /// operates on chunks of this sequence (nested calls travel along with their
+    /// top-level ancestor, since they're spliced out of the tree as a subtree).
+    pub(crate) fn get_top_level_call_ids(&self) -> Vec<ExtensionPointID> {
+        self.fct_tree
+            .iter()
+            .filter(|node| node.parent().is_none())
+            .map(|node| self.fct_tree.get_node_id(node).unwrap())
+            .collect()
+    }
+
+    /// Build a new test, structurally identical to `self` (same location/import metadata),
+    /// but with its call tree replaced by `new_fct_tree`. Used by the delta-debugging
+    /// minimizer to try reduced candidate call sequences without mutating `self`.
+    pub(crate) fn with_fct_tree(&self, new_fct_tree: Arena<FunctionCall>) -> Self {
+        Self {
+            fct_tree: new_fct_tree,
+            ext_points: Vec::new(),
+            loc_id: self.loc_id.clone(),
+            include_basic_callback: self.include_basic_callback,
+            js_for_module_import: self.js_for_module_import.clone(),
+            module_load_mode: self.module_load_mode,
+            mod_js_var_name: self.mod_js_var_name.clone(),
+            root_level_tabs: RefCell::new(*self.root_level_tabs.borrow()),
+            coverage_url_filter: self.coverage_url_filter.clone(),
+            sandbox_policy: self.sandbox_policy.clone(),
+        }
+    }
+
     /// Get the unique ID for a function call node in the test tree.
     pub fn get_uniq_id_for_call(&self, fc: &indextree::Node<FunctionCall>) -> String {
-        self.fct_tree.get_node_id(fc).unwrap().to_string()
-            + &match &fc.get().parent_call_id {
-                Some(pos) => "_pcid".to_owned() + &pos.to_string(),
-                None => String::new(),
-            }
-            + &match &fc.get().parent_arg_position_nesting {
-                Some(pos) => "_pos".to_owned() + &pos.to_string(),
-                None => String::new(),
-            }
+        uniq_id_for_node(&self.fct_tree, self.fct_tree.get_node_id(fc).unwrap())
     }
 
     /// Get the unique ID for the node in the test tree that corresponds to the
@@ -458,6 +757,18 @@ impl<'cxt> Test {
     /// Get the (top-level) library function return values that are accessible at
     /// the extension point specified, along with their access path representations
     /// (wrapped in the `ArgValAPTracked` struct).
+    ///
+    /// This *is* the liveness/scope analysis for return-value bindings: `uniq_id` already
+    /// encodes each call's position in execution order plus its nesting ancestry (see
+    /// `get_uniq_id_for_call`), so comparing `ext_node`'s `uniq_id` against every other
+    /// node's is exactly forward-reachability over the call tree -- a return value is live
+    /// at `ext_id` iff its defining call is strictly earlier (first filter) and `ext_id`
+    /// isn't inside that call's own callback body, where the call hasn't returned yet
+    /// (second filter). `Test::extend` calls this (and `get_cb_arg_values_accessible_from_ext_point`
+    /// below, for callback parameters) to build `ret_vals_pool`/`cb_arg_vals_pool` *before*
+    /// `gen_random_value_of_type` ever runs, so every `AnyType` sample is already drawn
+    /// from exactly the live set -- there's no broader "all values" pool it could
+    /// accidentally reach past.
     pub fn get_ret_values_accessible_from_ext_point(
         &self,
         ext_id: ExtensionPointID,
@@ -497,6 +808,12 @@ impl<'cxt> Test {
 
     /// Get all the callback arguments to (recursive) nesting parents, that are
     /// accessible at the extension point specified.
+    ///
+    /// A callback parameter is only live within that callback's own body subtree, so
+    /// walking `ext_id`'s `ancestors` (rather than the whole tree, as
+    /// `get_ret_values_accessible_from_ext_point` does) is exactly that subtree scoping:
+    /// each ancestor is a call whose callback body `ext_id` is nested inside, so its
+    /// parameters are in scope; anything outside that chain of ancestors isn't.
     pub fn get_cb_arg_values_accessible_from_ext_point(
         &self,
         ext_id: ExtensionPointID,
@@ -514,99 +831,266 @@ impl<'cxt> Test {
     }
 }
 
+/// Results of running a test: per-extension-point outcomes, newly-discovered function
+/// properties on non-primitive return values, the `(url, startOffset)` pairs of V8
+/// coverage ranges the run newly exercised in the module under test (see
+/// `Test::collect_v8_coverage`), and a description of every capability `js_tools/sandbox_shim.js`
+/// denied the test (see `collect_denied_capabilities`) -- always empty for
+/// `execute_via_runner`, which doesn't enforce `Test::sandbox_policy`.
 pub type TestDiagnostics = (
-    HashMap<ExtensionPointID, (FunctionCallResult, Option<String>)>,
-    HashMap<AccessPathModuleCentred, Vec<String>>,
+    FxHashMap<ExtensionPointID, (FunctionCallResult, Option<String>)>,
+    IndexMap<AccessPathModuleCentred, Vec<String>>,
+    Vec<(String, u64)>,
+    Vec<String>,
 );
 
-/// Given the output of running a test, this function parses the output and
-/// returns a list of results that corresponds to the test's tree.
+/// Per-call coverage deltas for a test run: the `(file, line)` pairs hit by the call
+/// at each extension point. Collected externally (e.g. by running the instrumented test
+/// under a coverage tool like `c8`/`nyc`) and fed back into the weight model via
+/// `TestGenDB::add_extension_points_for_test`.
+pub type CallCoverage = FxHashMap<ExtensionPointID, Vec<(String, u64)>>;
+
+/// Per-extension-point outcomes and newly-discovered function properties found while
+/// diagnosing a single test run; the other third of `TestDiagnostics` (the V8 coverage
+/// ranges) is collected separately by `Test::collect_v8_coverage` and merged in by `execute`.
+type ExtPointDiagnostics = (
+    FxHashMap<ExtensionPointID, (FunctionCallResult, Option<String>)>,
+    IndexMap<AccessPathModuleCentred, Vec<String>>,
+);
+
+/// Scan `contents` line-by-line for instrumentation events and parse each as JSON. Lines
+/// read from the dedicated events file (see `code_gen::NESSIE_EVENTS_FILE_ENV`) are bare
+/// JSON, one event per line; lines tagged with `code_gen::NESSIE_EVENT_PREFIX` (the
+/// stdout fallback used when that file isn't available -- see
+/// `code_gen::get_instrumented_header`) have the prefix stripped first. Any other line --
+/// arbitrary output the library under test printed via its own `console.log` calls, or a
+/// crash message on stderr that leaked onto stdout, or the persistent runner's own
+/// sentinel/timeout markers -- fails to parse as JSON and is silently skipped instead of
+/// poisoning the whole parse, and a test that crashes partway through still yields
+/// whatever events were printed before the crash. Shared by `Test::execute` and
+/// `crate::runner::PersistentRunner::run_test`.
+/// Each printed line is actually a `{seq, t, depth, payload}` envelope (see
+/// `get_instrumented_header`) around the flattened-key event object every caller of this
+/// function already knows how to read -- `seq`/`t`/`depth` only matter for ordering/timing/
+/// nesting diagnostics, which nothing downstream needs yet, so `payload` is unwrapped here
+/// rather than rippling the envelope through every consumer.
+pub(crate) fn parse_nessie_events(contents: &str) -> Vec<Value> {
+    contents
+        .lines()
+        .map(|line| line.strip_prefix(code_gen::NESSIE_EVENT_PREFIX).unwrap_or(line))
+        .filter_map(|json_str| serde_json::from_str::<Value>(json_str).ok())
+        .filter_map(|envelope| envelope.get("payload").cloned())
+        .collect()
+}
+
+/// Scan the events parsed out of a test run (see `parse_nessie_events`) for `denied_*`
+/// events: `js_tools/sandbox_shim.js` emits one of these, through the same events-file
+/// channel as every other instrumentation event, each time it blocks a call for falling
+/// outside `Test::sandbox_policy` (e.g. `{"denied_fs_write": "/etc/passwd"}`). Returned as
+/// plain `"<capability>: <detail>"` strings rather than a dedicated type, since nothing
+/// downstream needs more than to display/log them yet.
+fn collect_denied_capabilities(output_vec: &[Value]) -> Vec<String> {
+    output_vec
+        .iter()
+        .filter_map(Value::as_object)
+        .flat_map(|event| event.iter())
+        .filter_map(|(key, detail)| {
+            key.strip_prefix("denied_").map(|capability| {
+                let detail = detail.as_str().map(str::to_owned).unwrap_or_else(|| detail.to_string());
+                format!("{}: {}", capability, detail)
+            })
+        })
+        .collect()
+}
+
+/// Given the events parsed out of a test run (see `parse_nessie_events`), this function
+/// builds a list of results that corresponds to the test's tree.
 /// We can use this to build a list of extension points.
 /// Note: we should only extend a test if it has no execution errors; if there
 /// are execution errors the test has no valid extension points.
-fn diagnose_test_correctness(test: &Test, output_json: &Value) -> TestDiagnostics {
+/// `timed_out` is whether the whole test process was killed for running past
+/// `consts::TEST_TIMEOUT_SECONDS` (see `Test::execute`): a call that never printed its
+/// `done_` event is classified as `ErrorInfo { timed_out, .. }` rather than a bare
+/// synchronous-throw, since we have no caught value to report on in that case.
+fn diagnose_test_correctness(
+    test: &Test,
+    output_vec: &[Value],
+    timed_out: bool,
+) -> ExtPointDiagnostics {
     let fct_tree = test.get_fct_tree();
-    let mut fct_tree_results: HashMap<ExtensionPointID, (FunctionCallResult, Option<String>)> =
-        HashMap::new();
-    let output_vec = match output_json {
-        Value::Array(vec) => vec,
-        _ => {
-            for fc in fct_tree.iter() {
-                fct_tree_results.insert(
-                    fct_tree.get_node_id(fc).unwrap(),
-                    (FunctionCallResult::ExecutionError, None),
-                );
-            }
-            return (fct_tree_results, HashMap::new());
-        }
-    };
+    let mut fct_tree_results: FxHashMap<ExtensionPointID, (FunctionCallResult, Option<String>)> =
+        FxHashMap::default();
     for fc in fct_tree.iter() {
         let fc_id = test.get_uniq_id_for_call(fc).to_string();
-        if matches!(
-            output_vec
-                .iter()
-                .position(|r| r == &json!({"error_".to_owned() + &fc_id: true})),
-            Some(_)
-        ) {
+        let error_key = "error_".to_owned() + &fc_id;
+        if let Some(error_payload) = output_vec.iter().find_map(|r| r.get(&error_key)) {
+            let error_info = ErrorInfo {
+                class_name: error_payload
+                    .get("class_name")
+                    .and_then(Value::as_str)
+                    .map(str::to_owned),
+                message: error_payload
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .map(str::to_owned),
+                stack: error_payload
+                    .get("stack")
+                    .and_then(Value::as_str)
+                    .map(str::to_owned),
+                arg_hint: error_payload
+                    .get("arg_hint")
+                    .and_then(Value::as_u64)
+                    .map(|i| i as usize),
+                is_rejection: error_payload
+                    .get("is_rejection")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false),
+                timed_out: false,
+            };
             fct_tree_results.insert(
                 fct_tree.get_node_id(fc).unwrap(),
-                (FunctionCallResult::ExecutionError, None),
+                (FunctionCallResult::ExecutionError(error_info), None),
             );
-            return (fct_tree_results, HashMap::new());
+            return (fct_tree_results, IndexMap::new());
         }
-        // now look through and see if the callback was executed
-        // and if so, whether or not it was executed sequentially
+        // now look through and see if the callback(s) were executed
+        // and if so, whether or not they were executed sequentially
         let done_pos = output_vec
             .iter()
             .position(|r| r == &json!({"done_".to_owned() + &fc_id: true}));
-        let (mut callback_pos, mut cb_arg_pos) = (None, None);
-        for (i, r) in output_vec.iter().enumerate() {
-            let k = &r["callback_exec_".to_owned() + &fc_id];
-            if !k.is_null() {
-                (callback_pos, cb_arg_pos) = (Some(i), Some(k.to_string()))
-            }
-        }
+        // every `callback_exec_<fc_id>` event (one per invocation, across every callback
+        // argument position of this call -- see `Callback::get_string_rep`) carries the
+        // invoked callback's argument position as its value, so a call's full invocation
+        // history is recovered by scanning for all of them rather than just the last one
+        let cb_execs: Vec<(usize, usize)> = output_vec
+            .iter()
+            .enumerate()
+            .filter_map(|(i, r)| {
+                r["callback_exec_".to_owned() + &fc_id]
+                    .as_u64()
+                    .map(|pos| (i, pos as usize))
+            })
+            .collect();
 
-        fct_tree_results.insert(
-            fct_tree.get_node_id(fc).unwrap(),
-            (
-                match (done_pos, callback_pos) {
-                    (Some(done_index), Some(callback_index)) => {
-                        // if test ends before callback is done executing, it's async
-                        if done_index < callback_index {
-                            FunctionCallResult::SingleCallback(
-                                SingleCallCallbackTestResult::CallbackCalledAsync,
-                            )
-                        }
-                        // else it's sync
-                        else {
-                            FunctionCallResult::SingleCallback(
-                                SingleCallCallbackTestResult::CallbackCalledSync,
-                            )
-                        }
-                    }
-                    (Some(_), None) => FunctionCallResult::SingleCallback(
-                        SingleCallCallbackTestResult::NoCallbackCalled,
-                    ),
-                    // if "done" never prints, there was an error
-                    _ => FunctionCallResult::ExecutionError,
-                },
-                cb_arg_pos,
-            ),
-        );
+        let cb_positions = fc.sig.get_callback_positions();
+        let result = if cb_positions.len() > 1 {
+            // multiple callback arguments: track invocation count, first-call timing and
+            // relative invocation order separately for each argument position
+            diagnose_multi_callback(&cb_positions, &cb_execs, done_pos, timed_out)
+        } else {
+            diagnose_single_callback(&cb_execs, done_pos, timed_out)
+        };
+        let cb_arg_pos = match &result {
+            FunctionCallResult::SingleCallback(_) => {
+                cb_execs.last().map(|&(_, pos)| pos.to_string())
+            }
+            _ => None,
+        };
+        fct_tree_results.insert(fct_tree.get_node_id(fc).unwrap(), (result, cb_arg_pos));
     }
     let new_acc_path_fcts = get_function_props_for_acc_paths(output_vec);
     (fct_tree_results, new_acc_path_fcts)
 }
 
+/// Diagnose a call whose signature has at most one callback argument: `SingleCallback`,
+/// classified sync/async by comparing the position of the last `callback_exec_<fc_id>`
+/// event against `done_pos`, or `ExecutionError` if the call never printed its `done_`
+/// event at all (classified as a timeout if `timed_out` -- the whole test process never
+/// finished -- rather than a caught throw, since nothing was actually caught here).
+fn diagnose_single_callback(
+    cb_execs: &[(usize, usize)],
+    done_pos: Option<usize>,
+    timed_out: bool,
+) -> FunctionCallResult {
+    let callback_pos = cb_execs.last().map(|&(i, _)| i);
+    FunctionCallResult::SingleCallback(match (done_pos, callback_pos) {
+        // if the test ends before the callback is done executing, it's async; else sync
+        (Some(done_index), Some(callback_index)) if done_index < callback_index => {
+            SingleCallCallbackTestResult::CallbackCalledAsync
+        }
+        (Some(_), Some(_)) => SingleCallCallbackTestResult::CallbackCalledSync,
+        (Some(_), None) => SingleCallCallbackTestResult::NoCallbackCalled,
+        // if "done" never prints, there was an error
+        _ => {
+            return FunctionCallResult::ExecutionError(ErrorInfo {
+                timed_out,
+                ..ErrorInfo::default()
+            })
+        }
+    })
+}
+
+/// Diagnose a call whose signature has more than one callback argument: one
+/// `CallbackInvocation` per position in `cb_positions`, giving its invocation count, the
+/// timing of its first invocation relative to `done_pos`, and its rank among the other
+/// invoked positions (ordered by which one fired first). `ExecutionError` if the call
+/// never printed its `done_` event at all (see `diagnose_single_callback` re: `timed_out`).
+fn diagnose_multi_callback(
+    cb_positions: &[usize],
+    cb_execs: &[(usize, usize)],
+    done_pos: Option<usize>,
+    timed_out: bool,
+) -> FunctionCallResult {
+    let Some(done_pos) = done_pos else {
+        return FunctionCallResult::ExecutionError(ErrorInfo {
+            timed_out,
+            ..ErrorInfo::default()
+        });
+    };
+    let mut first_index_and_count: HashMap<usize, (usize, u32)> = HashMap::new();
+    for &(i, pos) in cb_execs.iter() {
+        let entry = first_index_and_count.entry(pos).or_insert((i, 0));
+        entry.0 = entry.0.min(i);
+        entry.1 += 1;
+    }
+    let mut invoked_order: Vec<(usize, usize)> = first_index_and_count
+        .iter()
+        .map(|(&pos, &(first_index, _))| (pos, first_index))
+        .collect();
+    invoked_order.sort_by_key(|&(_, first_index)| first_index);
+    let order_ranks: HashMap<usize, usize> = invoked_order
+        .iter()
+        .enumerate()
+        .map(|(rank, &(pos, _))| (pos, rank))
+        .collect();
+
+    let invocations = cb_positions
+        .iter()
+        .map(|&pos| match first_index_and_count.get(&pos) {
+            Some(&(first_index, count)) => CallbackInvocation {
+                arg_pos: pos,
+                count,
+                timing: if first_index < done_pos {
+                    CallbackInvocationTiming::CalledSync
+                } else {
+                    CallbackInvocationTiming::CalledAsync
+                },
+                order_rank: order_ranks.get(&pos).copied(),
+            },
+            None => CallbackInvocation {
+                arg_pos: pos,
+                count: 0,
+                timing: CallbackInvocationTiming::NotCalled,
+                order_rank: None,
+            },
+        })
+        .collect();
+    FunctionCallResult::MultiCallback(invocations)
+}
+
 /// Get the function properties for a given access path, parsing from the
 /// test output (this amounts to looking for an item in the output that is
 /// a map item where the key is the access path and the value is the list of
-/// properties, and then parsing that).
+/// properties, and then parsing that). Returned as an `IndexMap`, not a `HashMap`, so the
+/// access paths come back in the same order they appear in `output_vec` -- itself ordered
+/// by the mined JSON's own document order, since `serde_json`'s `preserve_order` feature
+/// is enabled (see `Cargo.toml`) -- rather than `HashMap`'s per-process hash-randomized
+/// order. A fixed mining output plus a fixed RNG seed then yields byte-identical generated
+/// tests, run to run, instead of just structurally-equivalent ones.
 fn get_function_props_for_acc_paths(
     output_vec: &[Value],
-) -> HashMap<AccessPathModuleCentred, Vec<String>> {
-    let mut ret_map = HashMap::new();
+) -> IndexMap<AccessPathModuleCentred, Vec<String>> {
+    let mut ret_map = IndexMap::new();
     // `output_vec` is a list of JSON objects
     for val in output_vec.iter() {
         if let Value::Object(m) = val {
@@ -641,8 +1125,26 @@ pub struct ExtensionPoint {
     ext_type: ExtensionType,
 }
 
+impl ExtensionPoint {
+    /// Constructor.
+    pub(crate) fn new(node_id: ExtensionPointID, ext_type: ExtensionType) -> Self {
+        Self { node_id, ext_type }
+    }
+
+    /// Getter for the node (function call) this extension point is at.
+    pub(crate) fn get_node_id(&self) -> ExtensionPointID {
+        self.node_id
+    }
+
+    /// Getter for the type of extension this point allows -- what
+    /// `crate::emitter::Emitter::emit_extension` dispatches on.
+    pub(crate) fn get_ext_type(&self) -> ExtensionType {
+        self.ext_type
+    }
+}
+
 /// Type of test extension.
-#[derive(Debug, Clone, Eq, PartialEq, Copy, EnumIter, Rand)]
+#[derive(Debug, Clone, Eq, PartialEq, Copy, Hash, EnumIter, Rand, Serialize, Deserialize)]
 pub enum ExtensionType {
     /// Sequential function calls.
     Sequential,
@@ -0,0 +1,369 @@
+//! Lightweight parser for TypeScript `.d.ts` declaration files.
+//!
+//! This only understands the slice of the TypeScript grammar that's actually useful here:
+//! top-level (and namespace-nested) `function` declarations, including overloads. Method
+//! signatures inside `interface`/`class` bodies aren't picked up -- the request this exists
+//! for is pre-seeding signatures for the module's *own* exported functions, and those are
+//! declared with the `function` keyword in essentially every bundled `.d.ts` file. Anything
+//! this parser doesn't recognize (generics, conditional/mapped types, class members, ...)
+//! is simply not turned into a signature, rather than guessed at.
+
+use crate::functions::{ArgType, FunctionSignature};
+use std::collections::HashMap;
+
+/// One parsed declaration parameter: its mapped `ArgType`, whether it was declared
+/// optional (`name?: T`), and whether it's a rest parameter (`...name: T[]`).
+struct ParsedParam {
+    ty: ArgType,
+    optional: bool,
+    is_rest: bool,
+}
+
+/// Parse every top-level `function` declaration out of a `.d.ts` file's contents, grouped
+/// by function name (multiple entries for the same name are overloads). Each declaration
+/// contributes one or more `FunctionSignature`s: one per trailing optional parameter that
+/// can be omitted, i.e. `(a: number, b?: string, c?: string)` yields three signatures, of
+/// arity 1, 2 and 3.
+pub(crate) fn parse_dts_function_sigs(contents: &str) -> HashMap<String, Vec<FunctionSignature>> {
+    let chars: Vec<char> = strip_comments(contents).chars().collect();
+    let mut sigs_by_name: HashMap<String, Vec<FunctionSignature>> = HashMap::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if is_function_keyword_at(&chars, i) {
+            if let Some((name, params, rest_i)) = parse_function_decl(&chars, i + "function".len()) {
+                let parsed_params = split_top_level(&params, ',')
+                    .iter()
+                    .filter(|p| !p.trim().is_empty())
+                    .map(|p| parse_param(p))
+                    .collect::<Vec<ParsedParam>>();
+                sigs_by_name
+                    .entry(name)
+                    .or_default()
+                    .extend(signatures_from_params(&parsed_params));
+                i = rest_i;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    sigs_by_name
+}
+
+/// Is there a standalone `function` keyword at position `i` (not e.g. part of a longer
+/// identifier like `myfunction`, and not immediately followed by an identifier char)?
+fn is_function_keyword_at(chars: &[char], i: usize) -> bool {
+    const KW: &str = "function";
+    if i + KW.len() > chars.len() || chars[i..i + KW.len()].iter().collect::<String>() != KW {
+        return false;
+    }
+    let preceded_ok = i == 0 || !is_ident_char(chars[i - 1]);
+    let after = i + KW.len();
+    let followed_ok = after >= chars.len() || !is_ident_char(chars[after]);
+    preceded_ok && followed_ok
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Parse a function declaration's name and parameter list, starting right after the
+/// `function` keyword (so `start` points just past it). Returns the function name, the raw
+/// (unsplit) parameter-list source, and the index to resume scanning from. Returns `None`
+/// if this doesn't look like a named function declaration (e.g. a function *type*, used as
+/// a parameter's own type, rather than a declaration).
+fn parse_function_decl(chars: &[char], start: usize) -> Option<(String, String, usize)> {
+    let mut i = start;
+    i = skip_ws(chars, i);
+    i = skip_if(chars, i, '*'); // generator marker, e.g. `function* gen(...)`
+    i = skip_ws(chars, i);
+    let name_start = i;
+    while i < chars.len() && is_ident_char(chars[i]) {
+        i += 1;
+    }
+    if i == name_start {
+        return None; // anonymous -- a function type, not a declaration
+    }
+    let name = chars[name_start..i].iter().collect::<String>();
+    i = skip_ws(chars, i);
+    // skip the function's own generic type parameters, e.g. `function foo<T>(...)`
+    if i < chars.len() && chars[i] == '<' {
+        i = skip_balanced(chars, i, '<', '>')?;
+        i = skip_ws(chars, i);
+    }
+    if i >= chars.len() || chars[i] != '(' {
+        return None;
+    }
+    let params_start = i + 1;
+    let params_end = find_matching(chars, i, '(', ')')?;
+    let params = chars[params_start..params_end].iter().collect::<String>();
+    // resume scanning after the declaration's terminator, if there is one -- not load-
+    // bearing for parsing (we don't use the return type), just avoids re-scanning the
+    // return-type annotation for a spurious nested `function`
+    let mut end = params_end + 1;
+    while end < chars.len() && chars[end] != ';' && chars[end] != '\n' {
+        end += 1;
+    }
+    Some((name, params, end))
+}
+
+/// Parse one already-comma-split parameter declaration, e.g. `b?: string` or
+/// `...rest: string[]`, into its mapped type, optionality, and rest-ness.
+fn parse_param(param: &str) -> ParsedParam {
+    let param = param.trim();
+    let is_rest = param.starts_with("...");
+    let param = param.strip_prefix("...").unwrap_or(param).trim();
+    // split on the first top-level `:` to separate the parameter name from its type
+    // annotation (untyped params, e.g. in a hand-written `.d.ts`, default to `any`)
+    let colon_pos = split_top_level(param, ':').first().map(|s| s.len());
+    let (name_part, ty) = match colon_pos {
+        Some(len) if len < param.len() => (&param[..len], param[len + 1..].trim()),
+        _ => (param, "any"),
+    };
+    let optional = name_part.trim_end().ends_with('?');
+    ParsedParam {
+        ty: ts_type_to_arg_type(ty),
+        optional,
+        is_rest,
+    }
+}
+
+/// Map a TypeScript type annotation's source text to the closest `ArgType` this system
+/// tracks. Compound/generic types we don't otherwise recognize fall back to `ObjectType`
+/// (a named interface/class/type-alias reference) or `AnyType` (genuinely unconstrained).
+fn ts_type_to_arg_type(ty: &str) -> ArgType {
+    let ty = ty.trim().trim_end_matches(';');
+    if ty.is_empty() || ty == "any" || ty == "unknown" {
+        return ArgType::AnyType;
+    }
+    if ty.contains("=>") {
+        return ArgType::CallbackType;
+    }
+    if ty.ends_with("[]") || ty.starts_with("Array<") || ty.starts_with("ReadonlyArray<") {
+        return ArgType::ArrayType;
+    }
+    // a top-level intersection (`T & U`) isn't representable by any single `ArgType` here, so
+    // it's treated the same as an unconstrained type
+    if split_top_level(ty, '&').len() > 1 {
+        return ArgType::AnyType;
+    }
+    let union_members = split_top_level(ty, '|');
+    if union_members.len() > 1 {
+        // a union of nothing but string literals (`'a' | 'b'`) maps to a constrained
+        // `StringEnum`; anything else falls back to `Union` of the members' own types, or
+        // `AnyType` if even that collapses to a single, uninteresting alternative
+        return match union_members
+            .iter()
+            .map(|m| string_literal_value(m.trim()))
+            .collect::<Option<Vec<String>>>()
+        {
+            Some(literals) => ArgType::StringEnum(literals),
+            None => ArgType::Union(
+                union_members
+                    .iter()
+                    .map(|m| ts_type_to_arg_type(m))
+                    .collect(),
+            ),
+        };
+    }
+    match ty {
+        "number" => ArgType::NumberType,
+        "string" => ArgType::StringType,
+        "boolean" => ArgType::AnyType, // no dedicated boolean `ArgType` is tracked yet
+        _ if ty.starts_with('{') => ArgType::ObjectType,
+        // anything else still in scope per the request: a named interface/class/type-alias
+        _ => ArgType::ObjectType,
+    }
+}
+
+/// If `ty` is a single-quoted or double-quoted string literal type (e.g. `'get'`), return
+/// its unquoted value; otherwise `None`.
+fn string_literal_value(ty: &str) -> Option<String> {
+    let quote = ty.chars().next()?;
+    if (quote == '\'' || quote == '"') && ty.ends_with(quote) && ty.len() >= 2 {
+        Some(ty[1..ty.len() - 1].to_string())
+    } else {
+        None
+    }
+}
+
+/// Build one `FunctionSignature` per arity a call could actually use, given the parsed
+/// (non-rest) parameters in declaration order: the required prefix, plus one more
+/// signature for each trailing optional parameter included.
+fn signatures_from_params(params: &[ParsedParam]) -> Vec<FunctionSignature> {
+    let is_spread = params.iter().any(|p| p.is_rest);
+    let positional: Vec<ArgType> = params
+        .iter()
+        .filter(|p| !p.is_rest)
+        .map(|p| p.ty.clone())
+        .collect();
+    let required_count = params
+        .iter()
+        .filter(|p| !p.is_rest)
+        .take_while(|p| !p.optional)
+        .count();
+    (required_count..=positional.len())
+        .map(|n| {
+            let mut sig = FunctionSignature::from(&positional[..n].to_vec());
+            sig.is_spread_args = is_spread;
+            sig
+        })
+        .collect()
+}
+
+/// Replace `//` and `/* */` comments with spaces (preserving string/template contents and
+/// overall character offsets, so later index math doesn't need to re-derive positions).
+fn strip_comments(contents: &str) -> String {
+    let chars: Vec<char> = contents.chars().collect();
+    let mut out = String::with_capacity(chars.len());
+    let mut i = 0;
+    let mut in_string: Option<char> = None;
+    while i < chars.len() {
+        let c = chars[i];
+        if let Some(quote) = in_string {
+            out.push(c);
+            if c == '\\' && i + 1 < chars.len() {
+                out.push(chars[i + 1]);
+                i += 2;
+                continue;
+            }
+            if c == quote {
+                in_string = None;
+            }
+            i += 1;
+            continue;
+        }
+        match c {
+            '\'' | '"' | '`' => {
+                in_string = Some(c);
+                out.push(c);
+                i += 1;
+            }
+            '/' if chars.get(i + 1) == Some(&'/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i + 1 < chars.len() && !(chars[i] == '*' && chars[i + 1] == '/') {
+                    if chars[i] == '\n' {
+                        out.push('\n');
+                    }
+                    i += 1;
+                }
+                i += 2;
+            }
+            _ => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+fn skip_ws(chars: &[char], mut i: usize) -> usize {
+    while i < chars.len() && chars[i].is_whitespace() {
+        i += 1;
+    }
+    i
+}
+
+fn skip_if(chars: &[char], i: usize, c: char) -> usize {
+    if i < chars.len() && chars[i] == c {
+        i + 1
+    } else {
+        i
+    }
+}
+
+/// Find the index of the `close` bracket matching the `open` bracket at `chars[open_pos]`,
+/// accounting for nesting of all four bracket kinds and skipping over string/template
+/// literal contents so a bracket character inside one doesn't throw off the count.
+fn find_matching(chars: &[char], open_pos: usize, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = open_pos;
+    let mut in_string: Option<char> = None;
+    while i < chars.len() {
+        let c = chars[i];
+        if let Some(quote) = in_string {
+            if c == '\\' {
+                i += 2;
+                continue;
+            }
+            if c == quote {
+                in_string = None;
+            }
+            i += 1;
+            continue;
+        }
+        match c {
+            '\'' | '"' | '`' => in_string = Some(c),
+            _ if c == open => depth += 1,
+            _ if c == close => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Skip a balanced `open`...`close` span starting at `chars[i]` (which must be `open`),
+/// returning the index just past the matching `close`.
+fn skip_balanced(chars: &[char], i: usize, open: char, close: char) -> Option<usize> {
+    find_matching(chars, i, open, close).map(|end| end + 1)
+}
+
+/// Split `s` on every top-level occurrence of `sep` (i.e. not nested inside `()`, `<>`,
+/// `[]`, `{}`, or a string/template literal).
+fn split_top_level(s: &str, sep: char) -> Vec<String> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut in_string: Option<char> = None;
+    let mut cur = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if let Some(quote) = in_string {
+            cur.push(c);
+            if c == '\\' && i + 1 < chars.len() {
+                cur.push(chars[i + 1]);
+                i += 2;
+                continue;
+            }
+            if c == quote {
+                in_string = None;
+            }
+            i += 1;
+            continue;
+        }
+        match c {
+            '\'' | '"' | '`' => {
+                in_string = Some(c);
+                cur.push(c);
+            }
+            '(' | '<' | '[' | '{' => {
+                depth += 1;
+                cur.push(c);
+            }
+            ')' | '>' | ']' | '}' => {
+                depth -= 1;
+                cur.push(c);
+            }
+            _ if c == sep && depth == 0 => {
+                parts.push(cur.clone());
+                cur.clear();
+            }
+            _ => cur.push(c),
+        }
+        i += 1;
+    }
+    parts.push(cur);
+    parts
+}